@@ -0,0 +1,357 @@
+use std::convert::TryFrom;
+use std::iter::Peekable;
+use std::str::Chars;
+
+use crate::shell::Shell;
+use crate::expansion::index_into;
+use crate::types::{ShellError, Variable, Operator};
+
+/// A token inside an arithmetic square bracket.
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Value(Variable),
+    /// A variable or an index into one, including its `$` or `@`.
+    Var(String),
+    Op(Operator),
+    LParen,
+    RParen,
+}
+
+/// A parsed arithmetic expression.
+///
+/// Variables are only looked up when the expression is evaluated.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Value(Variable),
+    Var(String),
+    Neg(Box<Expr>),
+    Binary(Box<Expr>, Operator, Box<Expr>),
+}
+
+impl Expr {
+    /// Returns true if the expression performs any operation,
+    /// i.e. it is not just a single value or variable.
+    pub fn is_operation(&self) -> bool {
+        matches!(self, Expr::Neg(_) | Expr::Binary(..))
+    }
+
+    pub fn evaluate(&self, shell: &mut Shell) -> Result<Variable, ShellError> {
+        match self {
+            Expr::Value(value) => Ok(value.clone()),
+            Expr::Var(var) => {
+                if var.ends_with(']') && var.contains('[') {
+                    index_into(shell, var)
+                } else if let Some(value) = shell.get_variable(&var[1..]) {
+                    Ok(value)
+                } else {
+                    Err(ShellError::from(format!("oyster: variable {} not found", &var[1..])))
+                }
+            }
+            Expr::Neg(expr) => {
+                match expr.evaluate(shell)? {
+                    Variable::Int(int) => int.checked_neg().map(Variable::Int).ok_or_else(|| {
+                        ShellError::from(format!("oyster: integer overflow in `-{}`", int))
+                    }),
+                    Variable::Flt(flt) => Ok(Variable::Flt(-flt)),
+                    var => Err(ShellError::from(
+                        format!("oyster: cannot negate {} `{}`", var.type_name(), var)
+                    )),
+                }
+            }
+            Expr::Binary(lhs, op, rhs) => {
+                let lhs = lhs.evaluate(shell)?;
+                let rhs = rhs.evaluate(shell)?;
+                apply(lhs, *op, rhs)
+            }
+        }
+    }
+}
+
+/// Parses the contents of a square bracket into an arithmetic expression.
+pub fn parse(string: &str) -> Result<Expr, ShellError> {
+    let tokens = tokenize(string)?;
+    if tokens.is_empty() {
+        return Err(ShellError::from("oyster: empty square bracket"))
+    }
+    let mut tokens = tokens.into_iter().peekable();
+    let expr = parse_expr(&mut tokens, 0)?;
+    if let Some(token) = tokens.next() {
+        return Err(ShellError::from(
+            format!("oyster: unexpected token {:?} in square bracket", token)
+        ))
+    }
+    Ok(expr)
+}
+
+fn precedence(op: Operator) -> u8 {
+    use Operator::*;
+    match op {
+        Add | Sub => 1,
+        Mul | Div | Mod => 2,
+        Pow => 3,
+    }
+}
+
+type Tokens = Peekable<std::vec::IntoIter<Token>>;
+
+/// Precedence climbing: parses operators binding at least as tightly as `min_prec`.
+fn parse_expr(tokens: &mut Tokens, min_prec: u8) -> Result<Expr, ShellError> {
+    let mut lhs = parse_unary(tokens)?;
+    while let Some(&Token::Op(op)) = tokens.peek() {
+        let prec = precedence(op);
+        if prec < min_prec {
+            break
+        }
+        tokens.next();
+        // `**` is right associative, everything else is left associative
+        let next_prec = if op == Operator::Pow { prec } else { prec + 1 };
+        let rhs = parse_expr(tokens, next_prec)?;
+        lhs = Expr::Binary(Box::new(lhs), op, Box::new(rhs));
+    }
+    Ok(lhs)
+}
+
+fn parse_unary(tokens: &mut Tokens) -> Result<Expr, ShellError> {
+    match tokens.next() {
+        // unary minus binds looser than `**`, so -2 ** 2 is -4
+        Some(Token::Op(Operator::Sub)) => {
+            Ok(Expr::Neg(Box::new(parse_expr(tokens, precedence(Operator::Pow))?)))
+        }
+        Some(Token::Op(Operator::Add)) => {
+            parse_expr(tokens, precedence(Operator::Pow))
+        }
+        Some(Token::LParen) => {
+            let expr = parse_expr(tokens, 0)?;
+            if tokens.next() != Some(Token::RParen) {
+                return Err(ShellError::from("oyster: unmatched parenthesis in square bracket"))
+            }
+            Ok(expr)
+        }
+        Some(Token::Value(value)) => Ok(Expr::Value(value)),
+        Some(Token::Var(var)) => Ok(Expr::Var(var)),
+        Some(token) => Err(ShellError::from(
+            format!("oyster: unexpected token {:?} in square bracket", token)
+        )),
+        None => Err(ShellError::from("oyster: unexpected end of square bracket")),
+    }
+}
+
+fn tokenize(string: &str) -> Result<Vec<Token>, ShellError> {
+    let mut tokens = Vec::new();
+    let mut chars = string.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            ' ' | '\t' => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::LParen);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::RParen);
+            }
+            '+' | '-' | '/' | '%' => {
+                chars.next();
+                tokens.push(Token::Op(match c {
+                    '+' => Operator::Add,
+                    '-' => Operator::Sub,
+                    '/' => Operator::Div,
+                    _ => Operator::Mod,
+                }));
+            }
+            '*' => {
+                chars.next();
+                if chars.peek() == Some(&'*') {
+                    chars.next();
+                    tokens.push(Token::Op(Operator::Pow));
+                } else {
+                    tokens.push(Token::Op(Operator::Mul));
+                }
+            }
+            '"' => {
+                chars.next();
+                let mut quoted = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => quoted.push(c),
+                        None => return Err(ShellError::from("oyster: unmatched quote")),
+                    }
+                }
+                tokens.push(Token::Value(Variable::Str(quoted)));
+            }
+            '$' | '@' => {
+                chars.next();
+                let mut var = c.to_string();
+                var.push_str(&consume_word(&mut chars));
+                if var.len() == 1 {
+                    return Err(ShellError::from(format!("oyster: invalid variable `{}`", c)))
+                }
+                if chars.peek() == Some(&'[') {
+                    for c in &mut chars {
+                        var.push(c);
+                        if c == ']' {
+                            break
+                        }
+                    }
+                }
+                tokens.push(Token::Var(var));
+            }
+            '0'..='9' | '.' => {
+                let mut number = String::new();
+                while let Some(&c) = chars.peek() {
+                    if !c.is_ascii_digit() && c != '.' {
+                        break
+                    }
+                    number.push(c);
+                    chars.next();
+                }
+                if let Ok(int) = number.parse::<i64>() {
+                    tokens.push(Token::Value(Variable::Int(int)));
+                } else if !number.contains('.') {
+                    return Err(ShellError::from(
+                        format!("oyster: integer literal {} is too large", number)
+                    ))
+                } else if let Ok(flt) = number.parse::<f64>() {
+                    tokens.push(Token::Value(Variable::Flt(flt)));
+                } else {
+                    return Err(ShellError::from(format!("oyster: invalid number {}", number)))
+                }
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                tokens.push(Token::Value(Variable::Str(consume_word(&mut chars))));
+            }
+            c => {
+                return Err(ShellError::from(
+                    format!("oyster: unexpected character `{}` in square bracket", c)
+                ))
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn consume_word(chars: &mut Peekable<Chars>) -> String {
+    let mut word = String::new();
+    while let Some(&c) = chars.peek() {
+        if !c.is_alphanumeric() && c != '_' {
+            break
+        }
+        word.push(c);
+        chars.next();
+    }
+    word
+}
+
+/// Applies a binary operator to two values.
+///
+/// Ints are promoted to floats if the other operand is a float,
+/// and strings can only be concatenated with other strings.
+fn apply(lhs: Variable, op: Operator, rhs: Variable) -> Result<Variable, ShellError> {
+    use Variable::*;
+    match (lhs, rhs) {
+        (Int(lhs), Int(rhs)) => apply_int(lhs, op, rhs),
+        (Int(lhs), Flt(rhs)) => apply_flt(lhs as f64, op, rhs),
+        (Flt(lhs), Int(rhs)) => apply_flt(lhs, op, rhs as f64),
+        (Flt(lhs), Flt(rhs)) => apply_flt(lhs, op, rhs),
+        (Str(lhs), Str(rhs)) if op == Operator::Add => Ok(Str(lhs + &rhs)),
+        (Str(_), Str(_)) => Err(ShellError::from(
+            format!("oyster: operator `{}` is not supported for strings", op)
+        )),
+        (Arr(_), _) | (_, Arr(_)) => Err(ShellError::from("oyster: cannot operate on arrays")),
+        (lhs, rhs) => Err(ShellError::from(
+            format!("oyster: cannot apply `{}` to {} `{}` and {} `{}`",
+                op, lhs.type_name(), lhs, rhs.type_name(), rhs)
+        )),
+    }
+}
+
+fn apply_int(lhs: i64, op: Operator, rhs: i64) -> Result<Variable, ShellError> {
+    use Operator::*;
+    if (op == Div || op == Mod) && rhs == 0 {
+        return Err(ShellError::from("oyster: division by zero"))
+    }
+    let result = match op {
+        Add => lhs.checked_add(rhs),
+        Sub => lhs.checked_sub(rhs),
+        Mul => lhs.checked_mul(rhs),
+        Div => lhs.checked_div(rhs),
+        Mod => lhs.checked_rem(rhs),
+        Pow => {
+            // a negative exponent cannot give an integer result
+            if rhs < 0 {
+                return apply_flt(lhs as f64, op, rhs as f64)
+            }
+            u32::try_from(rhs).ok().and_then(|rhs| lhs.checked_pow(rhs))
+        }
+    };
+    result.map(Variable::Int).ok_or_else(|| {
+        ShellError::from(format!("oyster: integer overflow in `{} {} {}`", lhs, op, rhs))
+    })
+}
+
+fn apply_flt(lhs: f64, op: Operator, rhs: f64) -> Result<Variable, ShellError> {
+    use Operator::*;
+    if (op == Div || op == Mod) && rhs == 0.0 {
+        return Err(ShellError::from("oyster: division by zero"))
+    }
+    let result = match op {
+        Add => lhs + rhs,
+        Sub => lhs - rhs,
+        Mul => lhs * rhs,
+        Div => lhs / rhs,
+        Mod => lhs % rhs,
+        Pow => lhs.powf(rhs),
+    };
+    if result.is_finite() || !lhs.is_finite() || !rhs.is_finite() {
+        Ok(Variable::Flt(result))
+    } else {
+        Err(ShellError::from(format!("oyster: float overflow in `{} {} {}`", lhs, op, rhs)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_arithmetic_evaluation() {
+        let mut shell = Shell::new();
+        shell.add_variable("a", Variable::Int(3));
+        shell.add_variable("b", Variable::Int(4));
+        shell.add_variable("pi", Variable::Flt(3.5));
+        let mut eval = |string: &str| parse(string).and_then(|expr| expr.evaluate(&mut shell));
+
+        assert_eq!(eval("$a + $b * 2").unwrap(), Variable::Int(11));
+        assert_eq!(eval("($a + $b) * 2").unwrap(), Variable::Int(14));
+        assert_eq!(eval("$b - $a - 1").unwrap(), Variable::Int(0));
+        assert_eq!(eval("2 ** 3 ** 2").unwrap(), Variable::Int(512));
+        assert_eq!(eval("-2 ** 2").unwrap(), Variable::Int(-4));
+        assert_eq!(eval("-(1 + $a) % 3").unwrap(), Variable::Int(-1));
+        assert_eq!(eval("$a * $pi").unwrap(), Variable::Flt(10.5));
+        assert_eq!(eval("2 ** -1").unwrap(), Variable::Flt(0.5));
+        assert_eq!(eval("7/2").unwrap(), Variable::Int(3));
+        assert_eq!(eval("one + \"two\"").unwrap(), Variable::from("onetwo"));
+
+        assert!(eval("1 / 0").is_err());
+        assert!(eval("1.5 % 0").is_err());
+        assert!(eval("9223372036854775807 + 1").is_err());
+        assert!(eval("2 ** 64").is_err());
+        assert!(eval("\"a\" - \"b\"").is_err());
+        assert!(eval("\"a\" + 1").is_err());
+        assert!(eval("$nothing + 1").is_err());
+        assert!(eval("(1 + 2").is_err());
+        assert!(eval("1 +").is_err());
+    }
+
+    #[test]
+    fn check_arithmetic_parsing() {
+        assert!(!parse("hello").unwrap().is_operation());
+        assert!(!parse("$var").unwrap().is_operation());
+        assert!(parse("-1").unwrap().is_operation());
+        assert!(parse("1..5").is_err());
+        assert!(parse("1 2").is_err());
+    }
+}
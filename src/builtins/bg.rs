@@ -1,4 +1,3 @@
-use nix::unistd::Pid;
 use nix::sys::signal::{Signal, killpg};
 
 use crate::types::Cmd;
@@ -10,39 +9,20 @@ pub fn run(shell: &mut Shell, cmd: Cmd) -> i32 {
         eprintln!("bg: too many arguments");
         return 1;
     }
-    let mut job_id = 0;
-    let mut pgid = Pid::from_raw(0);
-    if cmd.args.len() == 1 {
-        if shell.jobs.len() > 1 {
-            eprintln!("bg: more than one job running");
+    let job_id = match jobc::resolve_job(shell, cmd.args.get(1).map(String::as_str)) {
+        Ok(id) => id,
+        Err(e) => {
+            eprintln!("bg: {}", e);
             return 2;
         }
-        //this loop should only run once
-        for (id, job) in shell.jobs.iter() {
-            job_id = *id;
-            pgid = job.pgid;
-        }
-    } else if cmd.args.len() == 2 {
-        match cmd.args[1].parse::<i32>() {
-            Ok(id) => {
-                job_id = id;
-                if let Some(job) = shell.get_job_by_id(id) {
-                    pgid = job.pgid;
-                } else {
-                    eprintln!("bg: no such job");
-                    return 2;
-                }
-            }
-            Err(_) => {
-                eprintln!("bg: please enter a numeric id");
-                return 3;
-            }
+    };
+    let pgid = match shell.get_job_by_id(job_id) {
+        Some(job) => job.pgid,
+        None => {
+            eprintln!("bg: job not found");
+            return 2;
         }
-    }
-    if pgid == Pid::from_raw(0) {
-        eprintln!("bg: job not found");
-        return 2;
-    }
+    };
     match killpg(pgid, Signal::SIGCONT) {
         Ok(()) => {
             jobc::mark_job_as_running(shell, job_id, true);
@@ -1,3 +1,4 @@
+use std::io::Write;
 use std::path::PathBuf;
 use std::error::Error;
 use std::env;
@@ -144,7 +145,7 @@ pub fn dirs(shell: &mut Shell, cmd: Cmd) -> i32 {
                 return 3;
             }
         };
-        println!("{}", path);
+        outln!("{}", path);
     } else if cmd.args.len() == 2 {
         let mut display = String::new();
         for (i, path) in shell.dirstack.iter().enumerate() {
@@ -166,17 +167,17 @@ pub fn dirs(shell: &mut Shell, cmd: Cmd) -> i32 {
                 }
             };
             if options.per_line {
-                println!("{}", to_show);
+                outln!("{}", to_show);
             } else {
                 display.push_str(&to_show);
                 display.push(' ');
             }
         }
         if !options.per_line {
-            println!("{}", display);
+            outln!("{}", display);
         }
     } else {
-        println!("{}", shell.dirstack.iter()
+        outln!("{}", shell.dirstack.iter()
             .map(|path| render_path(path, options.fullpath)
                 .unwrap_or(String::from(""))
             )
@@ -0,0 +1,39 @@
+use crate::types::Cmd;
+use crate::shell::Shell;
+use crate::jobc;
+
+/// Removes jobs from the job table, the current job if none are given,
+/// so that the shell no longer tracks them.
+/// With `-h`, the jobs are kept, but the shell can exit while they run.
+pub fn run(shell: &mut Shell, cmd: Cmd) -> i32 {
+    let mut nohup = false;
+    let mut specs = Vec::new();
+    for arg in &cmd.args[1..] {
+        if arg == "-h" {
+            nohup = true;
+        } else {
+            specs.push(Some(arg.as_str()));
+        }
+    }
+    if specs.is_empty() {
+        specs.push(None);
+    }
+    let mut status = 0;
+    for spec in specs {
+        match jobc::resolve_job(shell, spec) {
+            Ok(id) if nohup => {
+                if let Some(job) = shell.jobs.get_mut(&id) {
+                    job.nohup = true;
+                }
+            }
+            Ok(id) => {
+                shell.remove_job(id);
+            }
+            Err(e) => {
+                eprintln!("disown: {}", e);
+                status = 1;
+            }
+        }
+    }
+    status
+}
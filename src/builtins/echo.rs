@@ -0,0 +1,113 @@
+use std::io::{self, Write};
+
+use crate::types::Cmd;
+use crate::shell::Shell;
+
+/// Prints its arguments separated by spaces.
+/// `-n` leaves out the trailing newline and `-e` interprets backslash
+/// escapes such as `\n` and `\t`; `-E` turns `-e` back off.
+pub fn run(_shell: &mut Shell, cmd: Cmd) -> i32 {
+    let mut newline = true;
+    let mut escapes = false;
+    let mut args = cmd.args[1..].iter().peekable();
+    //only words made up entirely of known flags are options
+    while let Some(arg) = args.peek() {
+        if arg.len() < 2 || !arg.starts_with('-') 
+            || !arg[1..].chars().all(|c| "neE".contains(c)) {
+            break
+        }
+        for flag in arg[1..].chars() {
+            match flag {
+                'n' => newline = false,
+                'e' => escapes = true,
+                _ => escapes = false,
+            }
+        }
+        args.next();
+    }
+
+    let mut output = Vec::new();
+    let mut stopped = false;
+    for (i, arg) in args.enumerate() {
+        if i > 0 {
+            output.push(b' ');
+        }
+        if !escapes {
+            output.extend_from_slice(arg.as_bytes());
+        } else if unescape(arg, &mut output) {
+            stopped = true;
+            break
+        }
+    }
+    if newline && !stopped {
+        output.push(b'\n');
+    }
+
+    let mut stdout = io::stdout();
+    if let Err(e) = stdout.write_all(&output)
+        .and_then(|_| stdout.flush()) {
+        eprintln!("echo: write error: {}", e);
+        return 1;
+    }
+    0
+}
+
+/// Pushes `arg` onto `output` with its backslash escapes replaced.
+/// `\0nnn` and `\xHH` give raw bytes, which need not be valid UTF-8.
+/// Returns true if `\c` was found, which suppresses all further output.
+fn unescape(arg: &str, output: &mut Vec<u8>) -> bool {
+    let mut chars = arg.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            output.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            continue
+        }
+        match chars.next() {
+            Some('n') => output.push(b'\n'),
+            Some('t') => output.push(b'\t'),
+            Some('r') => output.push(b'\r'),
+            Some('a') => output.push(b'\x07'),
+            Some('b') => output.push(b'\x08'),
+            Some('e') => output.push(b'\x1b'),
+            Some('f') => output.push(b'\x0c'),
+            Some('v') => output.push(b'\x0b'),
+            Some('\\') => output.push(b'\\'),
+            Some('c') => return true,
+            Some('0') => {
+                let mut value = 0;
+                for _ in 0..3 {
+                    //a digit that would take the value past one byte is left as text
+                    match chars.peek().and_then(|c| c.to_digit(8)) {
+                        Some(digit) if value * 8 + digit <= 0o377 => value = value * 8 + digit,
+                        _ => break,
+                    }
+                    chars.next();
+                }
+                output.push(value as u8);
+            }
+            Some('x') => {
+                let mut value = 0;
+                let mut digits = 0;
+                while digits < 2 {
+                    match chars.peek().and_then(|c| c.to_digit(16)) {
+                        Some(digit) => value = value * 16 + digit,
+                        None => break,
+                    }
+                    chars.next();
+                    digits += 1;
+                }
+                if digits == 0 {
+                    output.extend_from_slice(b"\\x");
+                } else {
+                    output.push(value as u8);
+                }
+            }
+            Some(other) => {
+                output.push(b'\\');
+                output.extend_from_slice(other.encode_utf8(&mut [0; 4]).as_bytes());
+            }
+            None => output.push(b'\\'),
+        }
+    }
+    false
+}
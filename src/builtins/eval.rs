@@ -0,0 +1,65 @@
+use std::path::Path;
+
+use crate::types::{
+    Cmd,
+    ParseResult,
+    TokenizeResult,
+};
+use crate::shell::Shell;
+use crate::parser::Lexer;
+use crate::execute::execute_jobs;
+use crate::scripting::execute_scriptfile;
+
+/// Joins its arguments with spaces and runs the result as a command.
+pub fn run(shell: &mut Shell, cmd: Cmd) -> i32 {
+    let mut input = cmd.args[1..].join(" ");
+    input.push('\n');
+    let tokens = match Lexer::tokenize(&input) {
+        TokenizeResult::Good(tokens) => tokens,
+        TokenizeResult::EmptyCommand => return 0,
+        _ => {
+            eprintln!("eval: incomplete command");
+            return 2;
+        }
+    };
+    let jobs = match Lexer::parse_tokens(shell, tokens) {
+        Ok(ParseResult::Good(jobs)) => jobs,
+        Ok(_) => {
+            eprintln!("eval: incomplete command");
+            return 2;
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            return 2;
+        }
+    };
+    match execute_jobs(shell, jobs, false) {
+        Ok((status, _)) => status,
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+/// Runs a script in the current shell, so that any variables, aliases
+/// and functions it defines are kept. Any arguments after the file name
+/// are passed in as positional parameters.
+pub fn source(shell: &mut Shell, mut cmd: Cmd) -> i32 {
+    if cmd.args.len() < 2 {
+        eprintln!("source: no file specified");
+        return 2;
+    }
+    let args = cmd.args.split_off(2);
+    if !Path::new(&cmd.args[1]).is_file() {
+        eprintln!("source: no such file {}", cmd.args[1]);
+        return 1;
+    }
+    match execute_scriptfile(shell, &cmd.args[1], args) {
+        Ok(status) => status,
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
@@ -21,7 +21,7 @@ pub fn run(shell: &mut Shell, cmd: Cmd) -> i32 {
         }
     }
     for (_i, job) in shell.jobs.iter() {
-        if !job.firstcmd.starts_with("nohup ") {
+        if !job.nohup && !job.firstcmd.starts_with("nohup ") {
             eprintln!("oyster: there are still jobs running!");
             eprintln!("use exit 1 to force exit.");
             return 0;
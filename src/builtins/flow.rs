@@ -0,0 +1,69 @@
+use crate::types::{Cmd, ControlFlow};
+use crate::shell::Shell;
+
+/// `break [n]` - Breaks out of the nth enclosing loop.
+pub fn break_loop(shell: &mut Shell, cmd: Cmd) -> i32 {
+    match loop_count(shell, &cmd) {
+        Some(n) => {
+            shell.control_flow = Some(ControlFlow::Break(n));
+            0
+        }
+        None => 1,
+    }
+}
+
+/// `continue [n]` - Skips to the next iteration of the nth enclosing loop.
+pub fn continue_loop(shell: &mut Shell, cmd: Cmd) -> i32 {
+    match loop_count(shell, &cmd) {
+        Some(n) => {
+            shell.control_flow = Some(ControlFlow::Continue(n));
+            0
+        }
+        None => 1,
+    }
+}
+
+/// `return [status]` - Returns from the current function.
+pub fn return_func(shell: &mut Shell, cmd: Cmd) -> i32 {
+    if !shell.in_func() {
+        eprintln!("oyster: return: can only return from a function");
+        return 1;
+    }
+    if cmd.args.len() > 2 {
+        eprintln!("oyster: too many arguments");
+        return 1;
+    }
+    let status = match cmd.args.get(1) {
+        Some(status) => match status.parse::<i32>() {
+            Ok(status) => status,
+            Err(_) => {
+                eprintln!("oyster: return: numeric codes only");
+                return 2;
+            }
+        }
+        None => 0,
+    };
+    shell.control_flow = Some(ControlFlow::Return(status));
+    status
+}
+
+/// Validates the loop count for `break` and `continue`.
+/// Counts larger than the number of enclosing loops apply to the outermost loop.
+fn loop_count(shell: &Shell, cmd: &Cmd) -> Option<usize> {
+    if shell.loop_depth == 0 {
+        eprintln!("oyster: {}: only meaningful in a loop", cmd.args[0]);
+        return None;
+    }
+    if cmd.args.len() > 2 {
+        eprintln!("oyster: too many arguments");
+        return None;
+    }
+    match cmd.args.get(1).map(|n| n.parse::<usize>()) {
+        Some(Ok(n)) if n > 0 => Some(n.min(shell.loop_depth)),
+        Some(_) => {
+            eprintln!("oyster: {}: loop count must be a positive integer", cmd.args[0]);
+            None
+        }
+        None => Some(1),
+    }
+}
@@ -0,0 +1,107 @@
+use std::io::{self, Write};
+
+use regex::Regex;
+
+use crate::shell::Shell;
+use crate::types::Cmd;
+use crate::history::{HistEntry, format_timestamp};
+use super::write_error;
+
+pub fn run(shell: &mut Shell, cmd: Cmd) -> i32 {
+    let mut show_meta = false;
+    let mut args = cmd.args[1..].iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-c" => {
+                if let Err(e) = shell.history.clear() {
+                    eprintln!("history: could not clear history file: {}", e);
+                    return 1;
+                }
+                return 0;
+            }
+            "-d" => {
+                let idx = match args.next().map(|idx| idx.parse::<usize>()) {
+                    Some(Ok(idx)) => idx,
+                    Some(Err(_)) => {
+                        eprintln!("history: please enter a numeric index");
+                        return 3;
+                    }
+                    None => {
+                        eprintln!("history: -d requires an index");
+                        return 2;
+                    }
+                };
+                match shell.history.remove(idx) {
+                    Ok(Some(_)) => return 0,
+                    Ok(None) => {
+                        eprintln!("history: no entry at index {}", idx);
+                        return 1;
+                    }
+                    Err(e) => {
+                        eprintln!("history: could not update history file: {}", e);
+                        return 1;
+                    }
+                }
+            }
+            "-g" => {
+                let pattern = match args.next() {
+                    Some(pattern) => pattern,
+                    None => {
+                        eprintln!("history: -g requires a pattern");
+                        return 2;
+                    }
+                };
+                let re = match Regex::new(pattern) {
+                    Ok(re) => re,
+                    Err(e) => {
+                        eprintln!("history: invalid pattern: {}", e);
+                        return 2;
+                    }
+                };
+                let matches = shell.history.search(&re);
+                for (idx, entry) in &matches {
+                    if let Err(e) = print_entry(*idx, entry, show_meta) {
+                        return write_error(e);
+                    }
+                }
+                return if matches.is_empty() {1} else {0};
+            }
+            "-t" => {
+                show_meta = true;
+            }
+            n => {
+                let count = match n.parse::<usize>() {
+                    Ok(count) => count,
+                    Err(_) => {
+                        eprintln!("history: unknown option `{}`", n);
+                        return 2;
+                    }
+                };
+                let len = shell.history.len();
+                let start = len.saturating_sub(count);
+                for (idx, entry) in shell.history.entries()[start..].iter().enumerate() {
+                    if let Err(e) = print_entry(start + idx + 1, entry, show_meta) {
+                        return write_error(e);
+                    }
+                }
+                return 0;
+            }
+        }
+    }
+    for (idx, entry) in shell.history.entries().iter().enumerate() {
+        if let Err(e) = print_entry(idx + 1, entry, show_meta) {
+            return write_error(e);
+        }
+    }
+    0
+}
+
+fn print_entry(idx: usize, entry: &HistEntry, show_meta: bool) -> io::Result<()> {
+    if show_meta {
+        writeln!(io::stdout(), "{:>5}  {}  [{:>3}]  {}",
+            idx, format_timestamp(entry.timestamp), entry.status, entry.cmd
+        )
+    } else {
+        writeln!(io::stdout(), "{:>5}  {}", idx, entry.cmd)
+    }
+}
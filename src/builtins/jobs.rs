@@ -1,5 +1,9 @@
+use std::io::{self, Write};
+
 use crate::shell::Shell;
-use crate::types::Cmd;
+use crate::types::{Cmd, JobTrack};
+use crate::jobc;
+use super::write_error;
 
 pub fn run(shell: &mut Shell, cmd: Cmd) -> i32 {
     if cmd.args.len() > 2 {
@@ -7,29 +11,32 @@ pub fn run(shell: &mut Shell, cmd: Cmd) -> i32 {
         return 2
     }
     if shell.jobs.is_empty() {
-        println!("No jobs to print");
+        outln!("No jobs to print");
         return 0
     } else if cmd.args.len() == 2 {
-        match cmd.args[1].parse::<i32>() {
+        match jobc::resolve_job(shell, Some(&cmd.args[1])) {
             Ok(id) => {
-                if let Some(job) = shell.get_job_by_id(id) {
-                    println!("[{}] {} {} {}\n",
-                    job.id, job.pgid, job.firstcmd, job.status);
-                    return 0
-                } else {
-                    eprintln!("jobs: job with id {} not found", id);
-                    return 1
+                if let Err(e) = print_job(shell, &shell.jobs[&id]) {
+                    return write_error(e);
                 }
+                return 0
             }
-            Err(_) => {
-                eprintln!("jobs: please enter a numeric id");
-                return 3
+            Err(e) => {
+                eprintln!("jobs: {}", e);
+                return 1
             }
         }
     }
-    for (_id, job) in &shell.jobs {
-        println!("[{}] {} {} {}\n",
-        job.id, job.pgid, job.firstcmd, job.status);
+    for job in shell.jobs.values() {
+        if let Err(e) = print_job(shell, job) {
+            return write_error(e);
+        }
     }
     0
-}
\ No newline at end of file
+}
+
+/// Prints a job, marking the current job with `+` and the previous with `-`.
+fn print_job(shell: &Shell, job: &JobTrack) -> io::Result<()> {
+    writeln!(io::stdout(), "[{}]{} {} {} {}\n",
+    job.id, shell.job_marker(job.id), job.pgid, job.firstcmd, job.status)
+}
@@ -0,0 +1,121 @@
+use std::io::Write;
+use std::convert::TryFrom;
+use std::str::FromStr;
+
+use nix::unistd::Pid;
+use nix::sys::signal::{self, Signal, killpg};
+
+use crate::types::{Cmd, JobStatus};
+use crate::shell::Shell;
+
+/// Sends a signal to processes or jobs, SIGTERM by default.
+/// Jobs are given as job specs like `%1`, and signals by name or number,
+/// as in `kill -INT %1` or `kill -s 9 1234`.
+/// `kill -l` lists the signals that can be sent, and signal 0
+/// only checks that the processes exist.
+pub fn run(shell: &mut Shell, cmd: Cmd) -> i32 {
+    let mut signal = Some(Signal::SIGTERM);
+    let mut targets = &cmd.args[1..];
+    match targets.first().map(String::as_str) {
+        None => {
+            eprintln!("kill: not enough arguments");
+            return 2;
+        }
+        Some("-l") => {
+            for signal in Signal::iterator() {
+                outln!("{:2}) {}", signal as i32, &signal.as_str()[3..]);
+            }
+            return 0;
+        }
+        Some("-s") | Some("-n") => {
+            if targets.len() < 2 {
+                eprintln!("kill: {} requires a signal", targets[0]);
+                return 2;
+            }
+            signal = match parse_signal(&targets[1]) {
+                Some(signal) => signal,
+                None => {
+                    eprintln!("kill: unknown signal {}", targets[1]);
+                    return 2;
+                }
+            };
+            targets = &targets[2..];
+        }
+        Some(flag) if flag.len() > 1 && flag.starts_with('-') => {
+            signal = match parse_signal(&flag[1..]) {
+                Some(signal) => signal,
+                None => {
+                    eprintln!("kill: unknown signal {}", &flag[1..]);
+                    return 2;
+                }
+            };
+            targets = &targets[1..];
+        }
+        _ => {}
+    }
+    if targets.is_empty() {
+        eprintln!("kill: no process or job specified");
+        return 2;
+    }
+
+    let mut status = 0;
+    for target in targets {
+        let result = if target.starts_with('%') {
+            match shell.find_job(target) {
+                Ok(id) => {
+                    let job = &shell.jobs[&id];
+                    //a stopped job has to be woken up to act on the signal
+                    let wake = job.status == JobStatus::Stopped 
+                        && signal.is_some()
+                        && signal != Some(Signal::SIGCONT) 
+                        && signal != Some(Signal::SIGSTOP);
+                    killpg(job.pgid, signal).and_then(|_| {
+                        if wake {
+                            killpg(job.pgid, Signal::SIGCONT)
+                        } else {
+                            Ok(())
+                        }
+                    })
+                }
+                Err(e) => {
+                    eprintln!("kill: {}", e);
+                    status = 1;
+                    continue
+                }
+            }
+        } else {
+            match target.parse::<i32>() {
+                Ok(pid) => signal::kill(Pid::from_raw(pid), signal),
+                Err(_) => {
+                    eprintln!("kill: invalid process id {}", target);
+                    status = 1;
+                    continue
+                }
+            }
+        };
+        if let Err(e) = result {
+            eprintln!("kill: {}: {}", target, e);
+            status = 1;
+        }
+    }
+    status
+}
+
+/// Parses a signal given by number or by name, with or without
+/// the `SIG` prefix and in any case. Signal 0 is parsed as `Some(None)`,
+/// which sends nothing but still checks the target.
+fn parse_signal(name: &str) -> Option<Option<Signal>> {
+    if let Ok(number) = name.parse::<i32>() {
+        if number == 0 {
+            return Some(None);
+        }
+        return Signal::try_from(number).ok().map(Some);
+    }
+    let name = name.to_uppercase();
+    let signal = if name.starts_with("SIG") {
+        Signal::from_str(&name)
+    } else {
+        Signal::from_str(&format!("SIG{}", name))
+    };
+    signal.ok().map(Some)
+}
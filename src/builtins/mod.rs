@@ -1,11 +1,96 @@
+/// Writes a line to stdout like `println!`, but returns from the builtin
+/// with status 1 if it cannot be written, such as when the command
+/// reading from a pipe has already exited.
+macro_rules! outln {
+    ($($arg:tt)*) => {
+        if let Err(e) = writeln!(std::io::stdout(), $($arg)*) {
+            return crate::builtins::write_error(e)
+        }
+    };
+}
+
 pub mod cd;
 pub mod bg;
 pub mod fg;
 pub mod jobs;
+pub mod wait;
+pub mod disown;
 pub mod set;
 pub mod dirstack;
 pub mod alias;
 pub mod export;
 pub mod exit;
 pub mod which;
-pub mod show;
\ No newline at end of file
+pub mod show;
+pub mod history;
+pub mod namespace;
+pub mod flow;
+pub mod eval;
+pub mod echo;
+pub mod kill;
+pub mod read;
+pub mod setopt;
+
+use std::io;
+
+use crate::types::Cmd;
+use crate::shell::Shell;
+
+/// Reports that a builtin could not write its output, returning the
+/// status it exits with. A closed pipe is not reported, as the
+/// reader exiting early is not an error.
+pub fn write_error(e: io::Error) -> i32 {
+    if e.kind() != io::ErrorKind::BrokenPipe {
+        eprintln!("oyster: write error: {}", e);
+    }
+    1
+}
+
+/// A builtin command, returning its exit status.
+pub type Builtin = fn(&mut Shell, Cmd) -> i32;
+
+/// Every builtin command, by name.
+///
+/// Builtins run in the shell process wherever they are in a pipeline,
+/// and in a forked child when the pipeline is in the background
+/// or its output is being captured.
+pub const BUILTINS: &[(&str, Builtin)] = &[
+    ("cd", |shell, cmd| cd::run(shell, cmd, false)),
+    ("bg", bg::run),
+    ("fg", fg::run),
+    ("jobs", jobs::run),
+    ("wait", wait::run),
+    ("disown", disown::run),
+    ("alias", alias::set),
+    ("unalias", alias::unset),
+    ("let", set::run),
+    ("unset", set::unset),
+    ("local", set::local),
+    ("which", which::run),
+    ("show", show::run),
+    ("pushd", dirstack::pushd),
+    ("popd", dirstack::popd),
+    ("dirs", dirstack::dirs),
+    ("history", history::run),
+    ("namespace", namespace::run),
+    ("break", flow::break_loop),
+    ("continue", flow::continue_loop),
+    ("return", flow::return_func),
+    ("eval", eval::run),
+    ("source", eval::source),
+    ("echo", echo::run),
+    ("kill", kill::run),
+    ("read", read::run),
+    ("setopt", setopt::setopt),
+    ("unsetopt", setopt::unsetopt),
+    ("set", setopt::set),
+    ("export", |_, cmd| export::run(cmd)),
+    ("exit", exit::run),
+];
+
+/// Looks up the builtin command called `name`.
+pub fn lookup(name: &str) -> Option<Builtin> {
+    BUILTINS.iter()
+        .find(|(builtin, _)| *builtin == name)
+        .map(|(_, func)| *func)
+}
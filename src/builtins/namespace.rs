@@ -0,0 +1,46 @@
+use std::io::Write;
+
+use crate::shell::Shell;
+use crate::types::Cmd;
+use crate::namespace::Namespace;
+
+/// Registers a shell function as a namespace, or lists the namespaces.
+///
+/// `namespace <name> <func>` registers a namespace,
+/// `namespace -r <name>` removes it.
+pub fn run(shell: &mut Shell, cmd: Cmd) -> i32 {
+    match cmd.args.len() {
+        1 => {
+            for (name, namespace) in shell.namespaces.list() {
+                match namespace {
+                    Namespace::Builtin(_) => outln!("{}: builtin", name),
+                    Namespace::Func(func) => outln!("{}: function {}", name, func),
+                }
+            }
+            0
+        }
+        3 if cmd.args[1] == "-r" => {
+            if let Err(e) = shell.namespaces.remove(&cmd.args[2]) {
+                eprintln!("namespace: {}", e);
+                return 2;
+            }
+            0
+        }
+        3 => {
+            if cmd.args[1].is_empty() || 
+            !cmd.args[1].chars().all(|c| c.is_alphanumeric() || c == '_') {
+                eprintln!("namespace: use alphanumeric characters and underscores only");
+                return 3;
+            }
+            if let Err(e) = shell.namespaces.register_func(&cmd.args[1], &cmd.args[2]) {
+                eprintln!("namespace: {}", e);
+                return 2;
+            }
+            0
+        }
+        _ => {
+            eprintln!("namespace: incorrect number of arguments");
+            1
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use std::time::{Duration, Instant};
+
+use nix::unistd::{self, isatty};
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::sys::termios::{self, LocalFlags, SetArg};
+use nix::Error;
+use nix::errno::Errno;
+
+use crate::shell::Shell;
+use crate::types::{Cmd, Variable as Var};
+
+const DEFAULT_IFS: &str = " \t\n";
+
+/// The status returned when `-t` runs out, as in bash.
+const TIMEOUT_STATUS: i32 = 142;
+
+/// Reads a line from stdin and splits it by `$IFS` into variables.
+///
+/// Each word is assigned to the next variable, with the last one getting
+/// the rest of the line. Values are typed as with `let`, unless the name
+/// is preceded by `str`. With no names, the line is saved to `REPLY`.
+pub fn run(shell: &mut Shell, cmd: Cmd) -> i32 {
+    let mut prompt = None;
+    let mut silent = false;
+    let mut timeout = None;
+    let mut count = None;
+    let mut delim = b'\n';
+    let mut array = false;
+    let mut targets: Vec<(String, bool)> = Vec::new();
+    let mut as_str = false;
+
+    let mut args = cmd.args[1..].iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-p" | "-t" | "-n" | "-d" if targets.is_empty() => {
+                let value = match args.next() {
+                    Some(value) => value,
+                    None => {
+                        eprintln!("read: {} requires an argument", arg);
+                        return 2;
+                    }
+                };
+                match arg.as_str() {
+                    "-p" => prompt = Some(value.clone()),
+                    "-t" => match value.parse::<f64>() {
+                        Ok(secs) if secs >= 0.0 => {
+                            //capped, as a deadline too far away overflows
+                            timeout = Some(Duration::from_secs_f64(secs.min(u32::MAX as f64)))
+                        }
+                        _ => {
+                            eprintln!("read: invalid timeout `{}`", value);
+                            return 2;
+                        }
+                    }
+                    "-n" => match value.parse::<usize>() {
+                        Ok(n) => count = Some(n),
+                        Err(_) => {
+                            eprintln!("read: invalid count `{}`", value);
+                            return 2;
+                        }
+                    }
+                    //an empty delimiter reads up to a null byte
+                    _ => delim = value.bytes().next().unwrap_or(0),
+                }
+            }
+            "-s" if targets.is_empty() => silent = true,
+            "-a" if targets.is_empty() => array = true,
+            "str" if !as_str => as_str = true,
+            name => {
+                if name.starts_with('-') && targets.is_empty() {
+                    eprintln!("read: unknown option `{}`", name);
+                    return 2;
+                }
+                if name.is_empty()
+                    || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                    eprintln!("read: `{}` is not a valid variable name", name);
+                    return 2;
+                }
+                targets.push((name.to_string(), as_str));
+                as_str = false;
+            }
+        }
+    }
+    //a trailing `str` is the name of the variable
+    if as_str {
+        targets.push((String::from("str"), false));
+    }
+    if targets.is_empty() {
+        targets.push((String::from("REPLY"), false));
+    }
+    if array && targets.len() > 1 {
+        eprintln!("read: -a takes a single variable");
+        return 2;
+    }
+
+    let tty = isatty(0).unwrap_or(false);
+    if let Some(prompt) = prompt {
+        if tty {
+            eprint!("{}", prompt);
+        }
+    }
+    let (input, status) = match read_input(tty, silent, timeout, count, delim) {
+        Ok(result) => result,
+        Err(e) => {
+            eprintln!("read: {}", e);
+            return 1;
+        }
+    };
+    if status == TIMEOUT_STATUS {
+        return status;
+    }
+
+    let ifs = shell.get_variable("IFS")
+        .map(|ifs| ifs.to_string())
+        .unwrap_or_else(|| String::from(DEFAULT_IFS));
+    let typed = |value: String, as_str: bool| {
+        if as_str {Var::Str(value)} else {Var::from(value)}
+    };
+    if array {
+        let (name, as_str) = targets.remove(0);
+        let elements = split_fields(&input, &ifs, None).into_iter()
+            .map(|field| typed(field, as_str))
+            .collect();
+        shell.add_variable(&name, Var::Arr(elements));
+    } else {
+        let mut fields = split_fields(&input, &ifs, Some(targets.len())).into_iter();
+        for (name, as_str) in targets {
+            let field = fields.next().unwrap_or_default();
+            shell.add_variable(&name, typed(field, as_str));
+        }
+    }
+    status
+}
+
+/// Reads from stdin one byte at a time, so that nothing after
+/// the delimiter is consumed.
+///
+/// Returns the input read and the exit status; 1 if end of file was
+/// reached before the delimiter, or `TIMEOUT_STATUS` if the timeout ran out.
+fn read_input(
+    tty: bool,
+    silent: bool,
+    timeout: Option<Duration>,
+    count: Option<usize>,
+    delim: u8,
+) -> nix::Result<(String, i32)> {
+    //the terminal has to give us input before a newline for -n and -d
+    let saved = if tty && (silent || count.is_some() || delim != b'\n') {
+        let saved = termios::tcgetattr(0)?;
+        let mut raw = saved.clone();
+        if silent {
+            raw.local_flags.remove(LocalFlags::ECHO);
+        }
+        if count.is_some() || delim != b'\n' {
+            raw.local_flags.remove(LocalFlags::ICANON);
+        }
+        termios::tcsetattr(0, SetArg::TCSANOW, &raw)?;
+        Some(saved)
+    } else {
+        None
+    };
+
+    let result = read_bytes(timeout, count, delim);
+    if let Some(saved) = saved {
+        termios::tcsetattr(0, SetArg::TCSANOW, &saved)?;
+    }
+    let (bytes, status) = result?;
+    Ok((String::from_utf8_lossy(&bytes).into_owned(), status))
+}
+
+fn read_bytes(
+    timeout: Option<Duration>,
+    count: Option<usize>,
+    delim: u8,
+) -> nix::Result<(Vec<u8>, i32)> {
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let mut bytes = Vec::new();
+    let mut chars = 0;
+    //continuation bytes still to come for the last character
+    let mut pending: usize = 0;
+    let mut byte = [0; 1];
+    loop {
+        if matches!(count, Some(count) if chars >= count) && pending == 0 {
+            return Ok((bytes, 0));
+        }
+        if let Some(deadline) = deadline {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            let mut fds = [PollFd::new(0, PollFlags::POLLIN)];
+            let millis = remaining.as_millis().min(i32::MAX as u128) as i32;
+            match poll(&mut fds, millis) {
+                Ok(0) => return Ok((bytes, TIMEOUT_STATUS)),
+                //a background job changing state interrupts the wait
+                Err(Error::Sys(Errno::EINTR)) => continue,
+                result => {
+                    result?;
+                }
+            }
+        }
+        if unistd::read(0, &mut byte)? == 0 {
+            return Ok((bytes, 1));
+        }
+        if byte[0] == delim && pending == 0 {
+            return Ok((bytes, 0));
+        }
+        if byte[0] & 0xC0 == 0x80 {
+            pending = pending.saturating_sub(1);
+        } else {
+            chars += 1;
+            pending = (byte[0].leading_ones() as usize).saturating_sub(1);
+        }
+        bytes.push(byte[0]);
+    }
+}
+
+/// Splits `input` into fields by the characters in `ifs`.
+///
+/// Whitespace in `ifs` is trimmed from the ends of the input, and a run
+/// of it separates fields; any other `ifs` character separates fields
+/// on its own. If `max` is given, the last field holds the rest of
+/// the input.
+fn split_fields(input: &str, ifs: &str, max: Option<usize>) -> Vec<String> {
+    let is_ifs_space = |c: char| c.is_whitespace() && ifs.contains(c);
+    let mut rest = input.trim_matches(is_ifs_space);
+    let mut fields = Vec::new();
+    while !rest.is_empty() {
+        if Some(fields.len() + 1) == max {
+            fields.push(rest.to_string());
+            break
+        }
+        let end = rest.find(|c| ifs.contains(c)).unwrap_or(rest.len());
+        fields.push(rest[..end].to_string());
+        rest = rest[end..].trim_start_matches(is_ifs_space);
+        //a single non-whitespace separator, with any whitespace around it
+        if let Some(c) = rest.chars().next() {
+            if ifs.contains(c) {
+                rest = rest[c.len_utf8()..].trim_start_matches(is_ifs_space);
+            }
+        }
+    }
+    fields
+}
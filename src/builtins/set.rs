@@ -1,7 +1,7 @@
 use regex::Regex;
 
 use crate::shell::Shell;
-use crate::types::{Cmd, Variable as Var};
+use crate::types::{Cmd, Map, Variable as Var};
 
 pub fn run(shell: &mut Shell, cmd: Cmd) -> i32 {
     let re = Regex::new(r"[a-zA-Z0-9_]+").unwrap();
@@ -31,6 +31,18 @@ pub fn run(shell: &mut Shell, cmd: Cmd) -> i32 {
             let mut input = cmd.args[4].clone();
             input.pop();
             shell.add_variable(&cmd.args[2], Var::Arr(split_arr(&input[1..])));
+        } else if cmd.args[1] == "map" {
+            if !(cmd.args[4].starts_with("{") && cmd.args[4].ends_with("}")) {
+                eprintln!("let: cannot parse '{}' as map", cmd.args[4]);
+                return 2;
+            }
+            match parse_map(&cmd.args[4]) {
+                Ok(map) => shell.add_map(&cmd.args[2], map),
+                Err(e) => {
+                    eprintln!("let: {}", e);
+                    return 2;
+                }
+            }
         } else {
             eprintln!("let: invalid type specification")
         }
@@ -44,7 +56,19 @@ pub fn run(shell: &mut Shell, cmd: Cmd) -> i32 {
             eprintln!("let: invalid syntax");
             return 1;
         }
-        if cmd.args[3].starts_with("[") && cmd.args[3].ends_with("]") {
+        if cmd.args[1].contains("[") && cmd.args[1].ends_with("]") {
+            // let <map>[<key>] = <value>
+            return insert_into_map(shell, &cmd.args[1], &cmd.args[3]);
+        }
+        if cmd.args[3].starts_with("{") && cmd.args[3].ends_with("}") {
+            match parse_map(&cmd.args[3]) {
+                Ok(map) => shell.add_map(&cmd.args[1], map),
+                Err(e) => {
+                    eprintln!("let: {}", e);
+                    return 2;
+                }
+            }
+        } else if cmd.args[3].starts_with("[") && cmd.args[3].ends_with("]") {
             let mut input = cmd.args[3].clone();
             input.pop();
             shell.add_variable(&cmd.args[1], Var::Arr(split_arr(&input[1..])));
@@ -58,8 +82,155 @@ pub fn run(shell: &mut Shell, cmd: Cmd) -> i32 {
     0
 }
 
+/// Declares variables local to the function being executed.
+///
+/// Takes the same syntax as `let`; `local <name>` on its own
+/// declares an empty local variable.
+pub fn local(shell: &mut Shell, cmd: Cmd) -> i32 {
+    let (name, value) = match cmd.args.len() {
+        2 => (&cmd.args[1], None),
+        4 => (&cmd.args[1], Some(&cmd.args[3])),
+        5 => (&cmd.args[2], Some(&cmd.args[4])),
+        _ => {
+            eprintln!("local: invalid syntax");
+            return 1;
+        }
+    };
+    if name.contains('[') || cmd.args[1] == "map"
+        || matches!(value, Some(value) if value.starts_with('{')) {
+        eprintln!("local: maps cannot be local");
+        return 2;
+    }
+    if let Err(e) = shell.add_local(name, Var::Str(String::new())) {
+        eprintln!("{}", e);
+        return 1;
+    }
+    if value.is_some() {
+        // the variable is now local, so let assigns to it
+        return run(shell, cmd);
+    }
+    0
+}
+
 fn split_arr(input: &str) -> Vec<Var> {
     input.split(",").map(|string| {
         Var::from(string.trim())
     }).collect()
-}
\ No newline at end of file
+}
+
+/// Removes variables and maps, or keys from a map with `unset <map>[<key>]`.
+pub fn unset(shell: &mut Shell, cmd: Cmd) -> i32 {
+    if cmd.args.len() < 2 {
+        eprintln!("unset: not enough arguments");
+        return 1;
+    }
+    let mut status = 0;
+    for name in &cmd.args[1..] {
+        if let Some((map_name, key)) = split_index(name) {
+            let removed = shell.get_map_mut(map_name).and_then(|map| map.remove(key));
+            if removed.is_none() {
+                eprintln!("unset: no key {} in map {}", key, map_name);
+                status = 2;
+            }
+        } else if shell.remove_map(name).is_none() 
+            && shell.remove_variable(name).is_none() {
+            eprintln!("unset: no variable {} in shell", name);
+            status = 2;
+        }
+    }
+    status
+}
+
+fn insert_into_map(shell: &mut Shell, target: &str, value: &str) -> i32 {
+    let (map_name, key) = match split_index(target) {
+        Some(split) => split,
+        None => {
+            eprintln!("let: invalid map key `{}`", target);
+            return 1;
+        }
+    };
+    if value.starts_with("{") && value.ends_with("}") {
+        eprintln!("let: maps cannot be nested");
+        return 2;
+    }
+    if shell.get_variable(map_name).is_some() {
+        eprintln!("let: variable {} is not a map", map_name);
+        return 2;
+    }
+    if shell.get_map(map_name).is_none() {
+        shell.add_map(map_name, Map::new());
+    }
+    // safe to unwrap because the map was added above
+    shell.get_map_mut(map_name).unwrap().insert(key, Var::from(value));
+    0
+}
+
+/// Splits `name[key]` into `name` and `key`.
+fn split_index(string: &str) -> Option<(&str, &str)> {
+    let (name, key) = string.strip_suffix(']')?.split_once('[')?;
+    let key = key.trim_matches(|c| c == '"' || c == '\'');
+    if name.is_empty() || key.is_empty() {
+        return None
+    }
+    Some((name, key))
+}
+
+/// Parses a map literal of the form `{key: value, key2: value2}`.
+/// Keys and values can be quoted, and unquoted values are type-inferred.
+fn parse_map(input: &str) -> Result<Map, String> {
+    let mut map = Map::new();
+    let inner = &input[1..input.len() - 1];
+    for entry in split_outside_quotes(inner, ',') {
+        if entry.trim().is_empty() {
+            continue
+        }
+        let mut parts = split_outside_quotes(&entry, ':').into_iter();
+        let (key, value) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(key), Some(value), None) => (key, value),
+            _ => return Err(format!("invalid map entry `{}`", entry.trim())),
+        };
+        let key = unquote(key.trim());
+        if key.is_empty() {
+            return Err(format!("empty key in map entry `{}`", entry.trim()))
+        }
+        let value = value.trim();
+        let value = if value.len() >= 2 && (value.starts_with('"') && value.ends_with('"')
+            || value.starts_with('\'') && value.ends_with('\'')) {
+            Var::Str(unquote(value).to_string())
+        } else {
+            Var::from(value)
+        };
+        map.insert(key, value);
+    }
+    Ok(map)
+}
+
+fn split_outside_quotes(input: &str, delim: char) -> Vec<String> {
+    let mut parts = Vec::new();
+    let mut part = String::new();
+    let mut quote: Option<char> = None;
+    for c in input.chars() {
+        match c {
+            '"' | '\'' if quote.is_none() => quote = Some(c),
+            c if quote == Some(c) => quote = None,
+            c if c == delim && quote.is_none() => {
+                parts.push(part.clone());
+                part.clear();
+                continue
+            }
+            _ => {}
+        }
+        part.push(c);
+    }
+    parts.push(part);
+    parts
+}
+
+fn unquote(string: &str) -> &str {
+    if string.len() >= 2 && (string.starts_with('"') && string.ends_with('"')
+        || string.starts_with('\'') && string.ends_with('\'')) {
+        &string[1..string.len() - 1]
+    } else {
+        string
+    }
+}
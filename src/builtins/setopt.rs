@@ -0,0 +1,96 @@
+use std::io::{self, Write};
+
+use crate::types::{Cmd, ShellOptions, ShellError};
+use crate::shell::Shell;
+use super::write_error;
+
+/// Turns on the shell options given. With no arguments,
+/// lists every option and whether it is on.
+pub fn setopt(shell: &mut Shell, cmd: Cmd) -> i32 {
+    set_options(shell, cmd, true)
+}
+
+/// Turns off the shell options given.
+pub fn unsetopt(shell: &mut Shell, cmd: Cmd) -> i32 {
+    set_options(shell, cmd, false)
+}
+
+/// Sets shell options with flags, as in `set -eu -o pipefail`.
+/// `+` instead of `-` turns an option off.
+pub fn set(shell: &mut Shell, cmd: Cmd) -> i32 {
+    let args = &cmd.args[1..];
+    if args.is_empty() || (args.len() == 1 && (args[0] == "-o" || args[0] == "+o")) {
+        return list_options(shell).map_or_else(write_error, |_| 0);
+    }
+    match apply_flags(&mut shell.options, args) {
+        Ok(used) if used < args.len() => {
+            eprintln!("set: unexpected argument `{}`", args[used]);
+            2
+        }
+        Ok(_) => 0,
+        Err(e) => {
+            eprintln!("set: {}", e);
+            2
+        }
+    }
+}
+
+/// Sets options from flags such as `-e`, `+x` and `-o pipefail`,
+/// stopping at the first argument that is not a flag, or after `--`.
+///
+/// Returns the number of arguments used.
+pub fn apply_flags(options: &mut ShellOptions, args: &[String]) -> Result<usize, ShellError> {
+    let mut used = 0;
+    while let Some(arg) = args.get(used) {
+        let value = match arg.chars().next() {
+            Some('-') => true,
+            Some('+') => false,
+            _ => break,
+        };
+        if arg == "--" {
+            used += 1;
+            break
+        }
+        if arg.len() == 1 {
+            break
+        }
+        used += 1;
+        if &arg[1..] == "o" {
+            let name = args.get(used).ok_or_else(|| {
+                ShellError::from(format!("{} requires an option name", arg))
+            })?;
+            options.set(name, value)?;
+            used += 1;
+            continue
+        }
+        for flag in arg[1..].chars() {
+            let name = ShellOptions::flag_name(flag).ok_or_else(|| {
+                ShellError::from(format!("unknown option `{}{}`", &arg[..1], flag))
+            })?;
+            options.set(name, value)?;
+        }
+    }
+    Ok(used)
+}
+
+fn set_options(shell: &mut Shell, cmd: Cmd, value: bool) -> i32 {
+    if cmd.args.len() == 1 {
+        return list_options(shell).map_or_else(write_error, |_| 0);
+    }
+    let mut status = 0;
+    for name in &cmd.args[1..] {
+        if let Err(e) = shell.options.set(name, value) {
+            eprintln!("{}: {}", cmd.args[0], e);
+            status = 1;
+        }
+    }
+    status
+}
+
+fn list_options(shell: &Shell) -> io::Result<()> {
+    for name in ShellOptions::NAMES {
+        let state = if shell.options.get(name) == Some(true) {"on"} else {"off"};
+        writeln!(io::stdout(), "{:<12} {}", name, state)?;
+    }
+    Ok(())
+}
@@ -1,7 +1,10 @@
+use std::io::Write;
+
 use crate::shell::Shell;
 use crate::types::{
     Cmd,
 };
+use super::write_error;
 
 pub fn run(shell: &mut Shell, cmd: Cmd) -> i32 {
     if cmd.args.len() < 2 {
@@ -17,7 +20,9 @@ pub fn run(shell: &mut Shell, cmd: Cmd) -> i32 {
         match cmd.args[1].as_str() {
             "-f" => {
                 if let Some(func) = shell.funcs().get(to_find) {
-                    func.print();
+                    if let Err(e) = func.print() {
+                        return write_error(e);
+                    }
                 } else {
                     eprintln!("show: could not find function {} in shell", to_find);
                     return 2;
@@ -25,7 +30,13 @@ pub fn run(shell: &mut Shell, cmd: Cmd) -> i32 {
             }
             "-v" => {
                 if let Some(var) = shell.get_variable(to_find) {
-                    var.print();
+                    if let Err(e) = var.print() {
+                        return write_error(e);
+                    }
+                } else if let Some(map) = shell.get_map(to_find) {
+                    if let Err(e) = map.print() {
+                        return write_error(e);
+                    }
                 } else {
                     eprintln!("show: could not find variable {} in shell", to_find);
                     return 2;
@@ -33,7 +44,7 @@ pub fn run(shell: &mut Shell, cmd: Cmd) -> i32 {
             }
             "-a" => {
                 if let Some(alias) = shell.get_alias(to_find) {
-                    println!("{}", alias);
+                    outln!("{}", alias);
                 } else {
                     eprintln!("show: could not find alias {} in shell", to_find)
                 }
@@ -48,11 +59,19 @@ pub fn run(shell: &mut Shell, cmd: Cmd) -> i32 {
             return 1;
         }
         if let Some(func) = shell.funcs().get(&cmd.args[1]) {
-            func.print();
+            if let Err(e) = func.print() {
+                return write_error(e);
+            }
         } else if let Some(var) = shell.get_variable(&cmd.args[1]) {
-            var.print();
+            if let Err(e) = var.print() {
+                return write_error(e);
+            }
+        } else if let Some(map) = shell.get_map(&cmd.args[1]) {
+            if let Err(e) = map.print() {
+                return write_error(e);
+            }
         } else if let Some(alias) = shell.get_alias(&cmd.args[1]) {
-            println!("{}", alias);
+            outln!("{}", alias);
         } else {
             eprintln!(
                 "show: could not find matching variable, function or alias in shell"
@@ -0,0 +1,56 @@
+use nix::unistd::Pid;
+
+use crate::types::{Cmd, JobStatus, JobTrack};
+use crate::shell::Shell;
+use crate::jobc;
+
+/// Waits for jobs or processes to finish, returning the exit status
+/// of the last one. With no arguments, waits for every running
+/// background job and returns 0.
+pub fn run(shell: &mut Shell, cmd: Cmd) -> i32 {
+    if cmd.args.len() == 1 {
+        let jobs: Vec<JobTrack> = shell.jobs.values()
+            .filter(|job| job.background && job.status == JobStatus::Running)
+            .cloned()
+            .collect();
+        for job in jobs {
+            jobc::wait_for_job(shell, &job);
+        }
+        return 0;
+    }
+    let mut status = 0;
+    for arg in &cmd.args[1..] {
+        status = if arg.starts_with('%') {
+            match shell.find_job(arg) {
+                Ok(id) => {
+                    let job = shell.jobs[&id].clone();
+                    jobc::wait_for_job(shell, &job)
+                }
+                Err(e) => {
+                    eprintln!("wait: {}", e);
+                    127
+                }
+            }
+        } else if let Ok(pid) = arg.parse::<i32>() {
+            let pid = Pid::from_raw(pid);
+            let recorded = shell.jobs.values().find_map(|job| {
+                job.pids.iter()
+                    .position(|job_pid| *job_pid == pid)
+                    .map(|idx| job.statuses[idx])
+            });
+            match recorded {
+                //the process has already finished
+                Some(Some(status)) => status,
+                Some(None) => jobc::wait_on_job(shell, pid, true),
+                None => {
+                    eprintln!("wait: pid {} is not a child of this shell", pid);
+                    127
+                }
+            }
+        } else {
+            eprintln!("wait: {}: not a pid or job spec", arg);
+            2
+        };
+    }
+    status
+}
@@ -1,3 +1,5 @@
+use std::io::Write;
+
 use crate::types::Cmd;
 use crate::shell::Shell;
 
@@ -6,38 +8,27 @@ pub fn run(shell: &mut Shell, mut cmd: Cmd) -> i32 {
     let mut failed: i32 = 0;
     for arg in cmd.args {
         match arg.as_str() {
-            name @ "cd" |
-            name @ "which" |
-            name @ "eval" |
-            name @ "source" |
-            name @ "export" |
-            //name @ "echo" |
-            //name @ "kill" |
-            name @ "alias" |
-            name @ "let" |
-            name @ "show" |
-            name @ "dirs" |
-            name @ "pushd" |
-            name @ "popd" |
-            name @ "exit" => {
-                println!("{}: built in shell command", name);
+            name if super::lookup(name).is_some() => {
+                outln!("{}: built in shell command", name);
             }
             n@ "for" |
             n@ "while" |
             n@ "if" |
             n@ "else" |
             n@ "elif" |
+            n@ "match" |
+            n@ "case" |
             n@ "end" |
             n@ "func" |
             n@ "endfn" |
             n@ "done" => {
-                println!("{}: shell reserved word", n);
+                outln!("{}: shell reserved word", n);
             }
             _ => {
                 match shell.search_in_path(&arg) {
                     Ok(path) => {
                         if let Some(pathname) = path.to_str() {
-                            println!("{}", pathname);
+                            outln!("{}", pathname);
                         } else {
                             eprintln!("error: path conversion failed");
                             failed += 1;
@@ -0,0 +1,208 @@
+use std::env;
+use std::path::PathBuf;
+
+use crate::types::{ShellError, ShellOptions};
+
+pub const USAGE: &str = "\
+usage: oyster [options] [file [args...]]
+       oyster [options] -c command [name [args...]]
+       oyster [options] -s [args...]
+
+options:
+  -c command      run command and exit
+  -s              read commands from stdin
+  -i              run interactively
+  -l, --login     run as a login shell
+  --norc          do not read the rc file
+  --rcfile file   read file instead of the rc file
+  --version       print the version and exit
+  -eCfux          set shell options, see `setopt`
+  -o option       set a shell option by name; +o unsets it";
+
+/// Where the shell reads its commands from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Input {
+    /// The user, at the prompt.
+    Interactive,
+    /// The string given with `-c`.
+    Command(String),
+    /// A script file.
+    File(String),
+    /// Stdin, when it is not a terminal or `-s` was given.
+    Stdin,
+}
+
+/// The command line arguments the shell was started with.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Args {
+    pub input: Input,
+    /// The name of the shell or script, used as `$0`.
+    pub name: String,
+    /// The positional parameters.
+    pub params: Vec<String>,
+    pub login: bool,
+    pub norc: bool,
+    pub rcfile: Option<PathBuf>,
+    pub version: bool,
+    pub options: ShellOptions,
+}
+
+impl Args {
+    /// Parses the shell's arguments, including the name it was run with.
+    ///
+    /// `stdin_tty` decides whether the shell reads from the prompt
+    /// or from stdin when it is not given a script.
+    pub fn parse(args: Vec<String>, stdin_tty: bool) -> Result<Self, ShellError> {
+        let mut args = args.into_iter();
+        let name = args.next().unwrap_or_else(|| String::from("oyster"));
+        let mut parsed = Args {
+            input: Input::Interactive,
+            //a leading dash is how login(1) starts a login shell
+            login: name.starts_with('-'),
+            name,
+            params: Vec::new(),
+            norc: false,
+            rcfile: None,
+            version: false,
+            options: ShellOptions::default(),
+        };
+        let mut command = false;
+        let mut from_stdin = false;
+        let mut interactive = false;
+
+        let mut rest = Vec::new();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--" => break,
+                "--version" => parsed.version = true,
+                "--login" => parsed.login = true,
+                "--norc" => parsed.norc = true,
+                "--rcfile" => {
+                    let path = args.next().ok_or_else(|| {
+                        ShellError::from("--rcfile requires a file name")
+                    })?;
+                    parsed.rcfile = Some(PathBuf::from(path));
+                }
+                "-o" | "+o" => {
+                    let option = args.next().ok_or_else(|| {
+                        ShellError::from(format!("{} requires an option name", arg))
+                    })?;
+                    parsed.options.set(&option, arg.starts_with('-'))?;
+                }
+                _ if arg.starts_with("--") => {
+                    return Err(ShellError::from(format!("unknown option `{}`", arg)))
+                }
+                _ if arg.len() > 1 && (arg.starts_with('-') || arg.starts_with('+')) => {
+                    let value = arg.starts_with('-');
+                    for flag in arg[1..].chars() {
+                        match flag {
+                            'c' if value => command = true,
+                            's' if value => from_stdin = true,
+                            'i' if value => interactive = true,
+                            'l' if value => parsed.login = true,
+                            _ => {
+                                let option = ShellOptions::flag_name(flag).ok_or_else(|| {
+                                    ShellError::from(
+                                        format!("unknown option `{}{}`", &arg[..1], flag)
+                                    )
+                                })?;
+                                parsed.options.set(option, value)?;
+                            }
+                        }
+                    }
+                }
+                _ => {
+                    rest.push(arg);
+                    break
+                }
+            }
+        }
+        rest.extend(args);
+        let mut rest = rest.into_iter();
+
+        parsed.input = if command {
+            let cmd = rest.next().ok_or_else(|| {
+                ShellError::from("-c requires a command")
+            })?;
+            if let Some(name) = rest.next() {
+                parsed.name = name;
+            }
+            Input::Command(cmd)
+        } else if from_stdin {
+            Input::Stdin
+        } else if let Some(file) = rest.next() {
+            parsed.name = file.clone();
+            Input::File(file)
+        } else if stdin_tty || interactive {
+            Input::Interactive
+        } else {
+            Input::Stdin
+        };
+        parsed.params = rest.collect();
+        Ok(parsed)
+    }
+}
+
+/// Returns the directory holding the user's config files:
+/// `$XDG_CONFIG_HOME/oyster`, or `~/.config/oyster` if it is not set.
+pub fn config_dir() -> Option<PathBuf> {
+    match env::var("XDG_CONFIG_HOME") {
+        Ok(dir) if !dir.is_empty() => Some(PathBuf::from(dir).join("oyster")),
+        _ => env::var("HOME").ok().map(|home| {
+            PathBuf::from(home).join(".config").join("oyster")
+        }),
+    }
+}
+
+/// Returns the path of the rc file, read by interactive shells.
+pub fn rc_file() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("oysterrc"))
+}
+
+/// Returns the profile files read by login shells, in order.
+pub fn profile_files() -> Vec<PathBuf> {
+    let mut files = vec![PathBuf::from("/etc/oyster/profile")];
+    files.extend(config_dir().map(|dir| dir.join("profile")));
+    files
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Args {
+        let args = args.iter().map(|arg| arg.to_string()).collect();
+        Args::parse(args, true).unwrap()
+    }
+
+    #[test]
+    fn check_arg_parsing() {
+        let args = parse(&["oyster", "-ex", "-o", "pipefail", "script.osh", "-u", "two"]);
+        assert_eq!(args.input, Input::File(String::from("script.osh")));
+        assert_eq!(args.name, "script.osh");
+        assert_eq!(args.params, vec!["-u", "two"]);
+        assert!(args.options.errexit && args.options.xtrace && args.options.pipefail);
+        assert!(!args.options.nounset);
+
+        let args = parse(&["-oyster", "-c", "echo $0 $1", "name", "one"]);
+        assert_eq!(args.input, Input::Command(String::from("echo $0 $1")));
+        assert_eq!(args.name, "name");
+        assert_eq!(args.params, vec!["one"]);
+        assert!(args.login);
+
+        let args = parse(&["oyster", "-ls", "--norc", "--rcfile", "rc", "a"]);
+        assert_eq!(args.input, Input::Stdin);
+        assert_eq!(args.params, vec!["a"]);
+        assert!(args.login && args.norc);
+        assert_eq!(args.rcfile, Some(PathBuf::from("rc")));
+
+        assert_eq!(parse(&["oyster"]).input, Input::Interactive);
+        let args = vec![String::from("oyster")];
+        assert_eq!(Args::parse(args, false).unwrap().input, Input::Stdin);
+
+        for bad in &[&["oyster", "-c"][..], &["oyster", "-q"], &["oyster", "--bogus"]] {
+            let args = bad.iter().map(|arg| arg.to_string()).collect();
+            assert!(Args::parse(args, true).is_err());
+        }
+    }
+}
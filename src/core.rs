@@ -1,39 +1,49 @@
-use std::fs::File;
-use std::io::Read;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
 use std::os::unix::io::{
     RawFd,
+    AsRawFd,
     FromRawFd,
 };
 use std::env;
 use std::process;
+use std::time::{Duration, Instant};
 use std::ffi::{CString, CStr};
 
 use nix::unistd::{
     Pid,
     getpid,
+    getpgid,
     setpgid,
     isatty,
     fork, 
     pipe, 
+    pipe2,
     execve, 
     dup, dup2, 
     close,
     ForkResult
 };
-use nix::sys::signal::{signal, Signal, SigHandler};
+use nix::fcntl::{fcntl, FcntlArg, OFlag};
+use nix::sys::signal::{signal, killpg, Signal, SigHandler};
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::sys::wait::{waitpid, WaitStatus};
 use nix::Error;
 use nix::errno::Errno;
 
 use crate::types::*;
 use crate::jobc;
 use crate::shell::{self, Shell};
-use crate::builtins::*;
+use crate::builtins::{self, Builtin};
+
+/// File descriptors of the shell's own stdio are moved
+/// at least this high while a builtin is running.
+const SAVED_FD_MIN: RawFd = 10;
 
 /// Even lower level, it deconstructs the job
 /// and passes raw parameters to the final function.
 pub fn run_pipeline(
     shell: &mut Shell, 
-    job_id: i32,
     cmds: Vec<Cmd>,
     background: bool, 
     capture: bool) -> Result<(bool, CommandResult), ShellError> {
@@ -44,18 +54,45 @@ pub fn run_pipeline(
 
     //making vec of pipes
     let mut pipes = Vec::new();
+    //close-on-exec, since the pipes are still open in the shell
+    //while the commands after them are forked, and they would otherwise
+    //keep them open and never see EOF
     for _ in 0..cmds.len() - 1 {
-        pipes.push(pipe()?);
+        pipes.push(pipe2(OFlag::O_CLOEXEC)?);
     }
 
     let isatty = isatty(1)?;
 
-    let mut pgid = Pid::from_raw(0);
+    let mut pgid = shell.pgid;
     let mut idx: usize = 0;
     let mut children = Vec::new();
 
+    let cmds_count = cmds.len();
+    //the exit status of each command, in pipeline order
+    let mut statuses = vec![0; cmds_count];
+
     for cmd in cmds {
 
+        //builtins run in the shell process, so that they can change the
+        //shell's state, unless the pipeline is backgrounded or captured
+        let builtin = builtins::lookup(&cmd.cmd).filter(|_| !capture && !background);
+        if let Some(builtin) = builtin {
+            //a builtin writes to a temporary file instead of a pipe, since
+            //nothing reads from it until the commands after it are started
+            if idx + 1 < cmds_count {
+                let (read_end, write_end) = pipes[idx];
+                close(read_end)?;
+                close(write_end)?;
+                pipes[idx] = shell::buffer_as_fds()?;
+            }
+            statuses[idx] = run_builtin(shell, builtin, cmd, idx, &pipes)?;
+            if idx + 1 < cmds_count {
+                close(pipes[idx].1)?;
+            }
+            idx += 1;
+            continue;
+        }
+
         let params = CommandParams{
             isatty: isatty,
             background: background,
@@ -64,7 +101,6 @@ pub fn run_pipeline(
         };
 
         let childpid = run_command(
-            job_id,
             cmd, 
             idx, 
             &mut pgid,
@@ -75,45 +111,266 @@ pub fn run_pipeline(
             &mut cmdresult,
         )?;
         if childpid > 0 && !background {
-            children.push(childpid);
+            children.push((idx, childpid));
         }
         idx += 1;
     }
 
-    let mut status: i32 = 0;
-    for childpid in children {
-        status = jobc::wait_on_job(
+    //the commands have their own copies of the read ends, and the shell
+    //closing its copies lets a command writing to one that has exited see EPIPE
+    for pipe in &pipes {
+        close(pipe.0)?;
+    }
+
+    let mut stopped = false;
+    for (idx, childpid) in children {
+        statuses[idx] = jobc::wait_on_job(
             shell, 
-            pgid, 
             Pid::from_raw(childpid), 
             true
         );
-        cmdresult.status = status;
+        stopped |= statuses[idx] == STOPPED;
     }
-    
-    for pipe in pipes {
-        close(pipe.0)?;
-        match close(pipe.1) {
-            Ok(_) => {},
-            Err(_) => {}
+    if !background {
+        cmdresult.status = pipeline_status(&statuses, shell.options.pipefail);
+        if !capture {
+            let pipestatus = statuses.iter()
+                .map(|status| Variable::Int(*status as i64))
+                .collect();
+            shell.add_variable("pipestatus", Variable::Arr(pipestatus));
         }
     }
-
-    if background {
+    
+    if background && isatty {
         if let Some(job) = shell.get_job_by_pgid(pgid) {
             eprintln!("[{}] {} {}", job.id, job.pgid, job.firstcmd);
         }
     }
     
-    if status == STOPPED {
-        jobc::mark_job_as_stopped(shell, job_id);
+    if stopped {
+        if let Some(job_id) = shell.get_job_by_pgid(pgid).map(|job| job.id) {
+            jobc::mark_job_as_stopped(shell, job_id);
+        }
     }
     Ok((term_given, cmdresult))
 }
 
+/// Runs a builtin in the shell process, with its stdio connected to the
+/// pipeline and its redirects applied. The shell's own file descriptors
+/// are restored once the builtin returns.
+fn run_builtin(
+    shell: &mut Shell,
+    builtin: Builtin,
+    cmd: Cmd,
+    idx: usize,
+    pipes: &[(RawFd, RawFd)],
+) -> Result<i32, ShellError> {
+    let to_pipe = idx < pipes.len();
+
+    let mut fds = Vec::new();
+    if idx > 0 {
+        fds.push(0);
+    }
+    if to_pipe {
+        fds.push(1);
+    }
+    if to_pipe && cmd.pipe_stderr {
+        fds.push(2);
+    }
+    fds.extend(cmd.redirects.iter().map(|redirect| redirect.fd));
+    fds.sort_unstable();
+    fds.dedup();
+
+    io::stdout().flush()?;
+    //moving the shell's own file descriptors out of the way,
+    //None if the file descriptor was not open to begin with
+    let saved: Vec<(RawFd, Option<RawFd>)> = fds.into_iter().map(|fd| {
+        (fd, fcntl(fd, FcntlArg::F_DUPFD_CLOEXEC(SAVED_FD_MIN)).ok())
+    }).collect();
+
+    let connected = connect_pipes(&cmd, idx, pipes)
+        .and_then(|_| apply_redirects(&cmd.redirects, shell.options.noclobber));
+    let status = match connected {
+        Ok(()) => builtin(shell, cmd),
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    };
+    //if stdout was closed or its reader has exited, whatever is left
+    //in the buffer is thrown away instead of being printed later
+    if io::stdout().flush().is_err() {
+        if let Ok(null) = OpenOptions::new().write(true).open("/dev/null") {
+            let _ = dup2(null.as_raw_fd(), 1);
+            let _ = io::stdout().flush();
+        }
+    }
+
+    for (fd, saved) in saved.into_iter().rev() {
+        match saved {
+            Some(saved) => {
+                dup2(saved, fd)?;
+                close(saved)?;
+            }
+            None => {
+                let _ = close(fd);
+            }
+        }
+    }
+    Ok(status)
+}
+
+/// Connects a builtin running in the shell process to the pipes
+/// on either side of it.
+fn connect_pipes(cmd: &Cmd, idx: usize, pipes: &[(RawFd, RawFd)]) -> Result<(), String> {
+    const PIPE_CONNECT_ERR: &str = "oyster: failed to connect pipes";
+    if idx > 0 {
+        dup2(pipes[idx - 1].0, 0).map_err(|_| PIPE_CONNECT_ERR.to_string())?;
+    }
+    if idx < pipes.len() {
+        dup2(pipes[idx].1, 1).map_err(|_| PIPE_CONNECT_ERR.to_string())?;
+        if cmd.pipe_stderr {
+            dup2(pipes[idx].1, 2).map_err(|_| PIPE_CONNECT_ERR.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+/// Applies redirects strictly left to right,
+/// so `2>&1 >file` and `>file 2>&1` do different things.
+///
+/// With `noclobber`, `>` refuses to truncate an existing file.
+///
+/// Returns an error message naming the redirect that failed.
+fn apply_redirects(redirects: &[Redirection], noclobber: bool) -> Result<(), String> {
+    for redirect in redirects {
+        let fd = redirect.fd;
+        let file = match &redirect.target {
+            Redirect::HereDoc { body, .. } => {
+                shell::heredoc_as_fd(body)
+            }
+            Redirect::FromFile(path) => {
+                shell::open_file_as_fd(path)
+            }
+            Redirect::ReadWrite(path) => {
+                shell::open_file_rw_as_fd(path)
+            }
+            Redirect::ToFile { path, append: false, clobber: false } if noclobber => {
+                match shell::create_new_fd_from_file(path) {
+                    Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                        return Err(format!("oyster: {}: cannot overwrite existing file", redirect))
+                    }
+                    file => file,
+                }
+            }
+            Redirect::ToFile { path, append, .. } => {
+                shell::create_fd_from_file(path, *append)
+            }
+            Redirect::Duplicate(target) => {
+                if *target != fd {
+                    dup2(*target, fd).map_err(|_| {
+                        format!("oyster: {}: bad file descriptor", redirect)
+                    })?;
+                }
+                continue
+            }
+            Redirect::Close => {
+                //closing an fd that isn't open is not an error
+                let _ = close(fd);
+                continue
+            }
+        };
+        let file = file.map_err(|e| format!("oyster: {}: {}", redirect, e))?;
+        if file != fd {
+            let duplicated = dup2(file, fd);
+            let _ = close(file);
+            duplicated.map_err(|e| format!("oyster: {}: {}", redirect, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Runs a shell function in a forked child and captures its stdout.
+/// Any changes the function makes to the shell are not kept.
+///
+/// The function runs in its own process group, along with any commands
+/// it starts. If `timeout` is given and the function takes longer,
+/// the whole group is killed and an error is returned.
+pub fn capture_func(
+    shell: &mut Shell,
+    name: &str,
+    args: Vec<String>,
+    timeout: Option<Duration>,
+) -> Result<(i32, String), ShellError> {
+    let (read_end, write_end) = pipe()?;
+    match fork()? {
+        ForkResult::Child => {
+            setpgid(Pid::from_raw(0), Pid::from_raw(0))
+                .unwrap_or_exit("oyster: failed to set pgid for child", 2);
+            shell.pgid = getpid();
+            close(read_end).unwrap_or_exit("oyster: could not close pipe file descriptor", 4);
+            dup2(write_end, 1).unwrap_or_exit("oyster: failed to duplicate file descriptor", 3);
+            close(write_end).unwrap_or_exit("oyster: could not close pipe file descriptor", 4);
+            let status = match shell.execute_func(name, args) {
+                Ok((status, _)) => status,
+                Err(e) => {
+                    eprintln!("{}", e);
+                    1
+                }
+            };
+            io::stdout().flush().unwrap_or_exit("oyster: could not flush stdout", 4);
+            process::exit(status);
+        }
+        ForkResult::Parent{child, ..} => {
+            //set here as well, in case the timeout runs out before the child has
+            let _ = setpgid(child, child);
+            close(write_end)?;
+            let mut output = unsafe {File::from_raw_fd(read_end)};
+            let mut captured = Vec::new();
+            //a timeout too far away to add to the clock is no timeout at all
+            let deadline = timeout.and_then(|timeout| Instant::now().checked_add(timeout));
+            let mut buf = [0; 4096];
+            loop {
+                if let Some(deadline) = deadline {
+                    let remaining = deadline.saturating_duration_since(Instant::now());
+                    let mut fds = [PollFd::new(read_end, PollFlags::POLLIN)];
+                    let millis = remaining.as_millis().min(i32::MAX as u128) as i32;
+                    match poll(&mut fds, millis) {
+                        Ok(0) => {
+                            let _ = killpg(child, Signal::SIGKILL);
+                            let _ = waitpid(child, None);
+                            //a command run by the function may have the terminal
+                            if isatty(0).unwrap_or(false) {
+                                let _ = shell::give_terminal_to(getpgid(None)?);
+                            }
+                            return Err(ShellError::from(
+                                format!("oyster: {}: timed out", name)
+                            ))
+                        }
+                        Err(Error::Sys(Errno::EINTR)) => continue,
+                        result => {
+                            result?;
+                        }
+                    }
+                }
+                match output.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => captured.extend_from_slice(&buf[..n]),
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+                    Err(e) => return Err(e.into()),
+                }
+            }
+            let status = match waitpid(child, None)? {
+                WaitStatus::Exited(_, status) => status,
+                _ => 1,
+            };
+            Ok((status, String::from_utf8_lossy(&captured).into_owned()))
+        }
+    }
+}
+
 /// This is one deep-ass core function.
 fn run_command(
-    id: i32,
     cmd: Cmd, 
     idx: usize, 
     pgid: &mut Pid,
@@ -144,7 +401,7 @@ fn run_command(
         ForkResult::Child => {
             //setting process groups
             let pid = getpid();
-            if idx == 0 {
+            if *pgid == Pid::from_raw(0) { //first command to be forked
                 *pgid = pid; //setting pgid to own pid
                 setpgid(Pid::from_raw(0), pid)
                     .unwrap_or_exit(PGID_SET_ERR, 2);
@@ -157,6 +414,8 @@ fn run_command(
                 signal(Signal::SIGINT, SigHandler::SigDfl)?;
                 signal(Signal::SIGQUIT, SigHandler::SigDfl)?;
                 signal(Signal::SIGTSTP, SigHandler::SigDfl)?;
+                //ignored by the shell, so that builtins can see EPIPE
+                signal(Signal::SIGPIPE, SigHandler::SigDfl)?;
             }
 
             //connecting up pipes for commands to read from
@@ -182,127 +441,29 @@ fn run_command(
                 }
             }
 
-            let mut stdout_redirected = false;
-            //let mut stderr_redirected = false;
-
-            for redirect in &cmd.redirects {
-                
-                if redirect.0 == "2" && redirect.2 == "&1" {
-                    if idx < pipes_count {
-                        let fds = pipes[idx];
-                        dup2(fds.1, 2).unwrap_or_exit(FD_DUPLICATE_ERR, 3);
-                    } else if !params.capture_output {
-                        let fd = dup(2).unwrap_or_exit(FD_DUPLICATE_ERR, 3);
-                        dup2(fd, 2).unwrap_or_exit(FD_DUPLICATE_ERR, 3);
-                    }
-                    //ya wanna redirect wih output capture enabled?
-                    //sure, don't blame me for any shitfuckery that happens.
-                } else if redirect.0 == "1" && redirect.2 == "&2" {
-                    if idx < pipes_count || !params.capture_output {
-                        let fd = dup(2).unwrap_or_exit(FD_DUPLICATE_ERR, 3);
-                        dup2(fd, 1).unwrap_or_exit(FD_DUPLICATE_ERR, 3);
-                    }
-                } else if redirect.0 == "1" && redirect.2 == "&1" ||
-                          redirect.0 == "2" && redirect.2 == "&2" {
-                    //do nothing because no one would do this
-                    //but we need to emulate zsh's behaviour
-                    //if we didn't catch this case, 
-                    //1>&1 would redirect stdin to a file called "&1"
-                } else if redirect.2 == "0" && redirect.1 == Redirect::FromStdin {
-                    let fd = shell::open_file_as_fd(&redirect.0);
-                    dup2(fd, 0).unwrap_or_exit(FD_DUPLICATE_ERR, 3);
-                } else {
-                    let to_append = redirect.1 == Redirect::Append;
-                    let fd = shell::create_fd_from_file(&redirect.2, to_append);
-                    if redirect.0 == "1" {
-                        dup2(fd, 1).unwrap_or_exit(FD_DUPLICATE_ERR, 3);
-                        stdout_redirected = true;
-                    } else {
-                        dup2(fd, 2).unwrap_or_exit(FD_DUPLICATE_ERR, 3);
-                        //stderr_redirected = true;
-                    }
-                }
+            if idx == pipes_count && params.capture_output {
+                close(fds_capture_stdout.0).unwrap_or_exit(PIPE_END_CLOSE_ERR, 4);
+                dup2(fds_capture_stdout.1, 1).unwrap_or_exit(FD_DUPLICATE_ERR, 3);
+                close(fds_capture_stdout.1).unwrap_or_exit(PIPE_END_CLOSE_ERR, 4);
             }
 
-            if idx == pipes_count && params.capture_output {
-                if !stdout_redirected {
-                    close(fds_capture_stdout.0).unwrap_or_exit(PIPE_END_CLOSE_ERR, 4);
-                    dup2(fds_capture_stdout.1, 1).unwrap_or_exit(FD_DUPLICATE_ERR, 3);
-                    close(fds_capture_stdout.1).unwrap_or_exit(PIPE_END_CLOSE_ERR, 4);
-                }
-                // if !stderr_redirected {
-                //     close(fds_capture_stderr.0).unwrap_or_exit(PIPE_END_CLOSE_ERR, 4);
-                //     dup2(fds_capture_stderr.1, 2).unwrap_or_exit(FD_DUPLICATE_ERR, 3);
-                //     close(fds_capture_stderr.1).unwrap_or_exit(PIPE_END_CLOSE_ERR, 4);
-                // }
+            if let Err(e) = apply_redirects(&cmd.redirects, shell.options.noclobber) {
+                eprintln!("{}", e);
+                process::exit(1);
             }
 
-            match cmd.cmd.as_str() {
-                "cd" => {
-                    let status = cd::run(shell, cmd, false);
-                    process::exit(status);
-                }
-                "bg" => {
-                    let status = bg::run(shell, cmd);
-                    process::exit(status);                
-                }
-                "fg" => {
-                    let status = fg::run(shell, cmd);
-                    process::exit(status);                
-                }
-                "jobs" => {
-                    let status = jobs::run(shell, cmd);
-                    process::exit(status);
-                }
-                "alias" => {
-                    let status = alias::set(shell, cmd);
-                    process::exit(status);
-                }
-                "unalias" => {
-                    let status = alias::unset(shell, cmd);
-                    process::exit(status);
-                }
-                "let" => {
-                    let status = set::run(shell, cmd);
-                    process::exit(status);
-                }
-                "which" => {
-                    let status = which::run(shell, cmd);
-                    process::exit(status);
-                }
-                "show" => {
-                    let status = show::run(shell, cmd);
-                    process::exit(status);
-                }
-                "pushd" => {
-                    let status = dirstack::pushd(shell, cmd);
-                    process::exit(status);
-                }
-                "popd" => {
-                    let status = dirstack::popd(shell, cmd);
-                    process::exit(status);
-                }
-                "dirs" => {
-                    let status = dirstack::dirs(shell, cmd);
-                    process::exit(status);
-                }
-                "eval" => {
-                }
-                "source" => {
-                }
-                "export" => {
-                    let status = export::run(cmd);
-                    process::exit(status);
-                }
-                "echo" => {
-                }
-                "kill" => {
-                }
-                "exit" => {
-                    let status = exit::run(shell, cmd);
-                    process::exit(status);
+            //builtins in a background pipeline, or whose output
+            //is being captured, run in the child
+            if let Some(builtin) = builtins::lookup(&cmd.cmd) {
+                //the child is not exec'd, so the pipes are not closed
+                //on exec, and the commands after it would never see EOF
+                for &(read_end, write_end) in pipes.iter() {
+                    let _ = close(read_end);
+                    let _ = close(write_end);
                 }
-                _ => {}
+                let status = builtin(shell, cmd);
+                io::stdout().flush().unwrap_or_exit("oyster: could not flush stdout", 4);
+                process::exit(status);
             }
 
             let c_cmd = if !cmd.cmd.contains("/") {
@@ -349,15 +510,17 @@ fn run_command(
             process::exit(1);
         }
         ForkResult::Parent{child,..} => {
-            if !params.capture_output && params.isatty && idx == 0 {
+            if !params.capture_output && *pgid == Pid::from_raw(0) {
                 *pgid = child;
-                if !params.background {
+                if params.isatty && !params.background {
                     *term_given = shell::give_terminal_to(child)?;
                 }
             }
 
             match setpgid(child, *pgid) {
                 Ok(()) => {}
+                //the child has already exec'd, after setting its own pgid
+                Err(Error::Sys(Errno::EACCES)) => {}
                 Err(e) => { 
                     eprintln!("Could not set child pgid from parent: {}", e); 
                     return Err(e.into());
@@ -375,12 +538,12 @@ fn run_command(
                 }
             }
 
-            if params.isatty && !params.capture_output {
+            //tracked even without a terminal, so that scripts can wait on jobs
+            if !params.capture_output {
                 shell.add_cmd_to_job(
-                    id, 
                     child, 
                     *pgid, 
-                    cmd.cmd.clone(),
+                    cmd.args.join(" "),
                     params.background
                 );
             }
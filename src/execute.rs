@@ -1,4 +1,6 @@
+use std::io::{self, Write};
 use std::path::Path;
+use std::process;
 
 use nix::unistd::getpgid;
 
@@ -10,6 +12,7 @@ use crate::types::{
     CommandResult,
     ShellError,
     ExecType,
+    Variable,
 };
 use crate::core;
 use crate::shell::{
@@ -18,8 +21,10 @@ use crate::shell::{
 use crate::expansion::{
     expand_braces,
     expand_variables,
+    check_unbound,
     expand_tilde,
     substitute_commands,
+    index_into,
 };
 use crate::builtins::*;
 use crate::scripting::*;
@@ -70,11 +75,14 @@ pub fn execute_jobs(
     let jobs = extract_constructs(jobs)?;
 
     for job in jobs {
+        //break, continue or return was called
+        if shell.control_flow.is_some() {
+            break
+        }
         match job {
             ExecType::Job(job) => {
-                if job.cmds[0].cmd.0 == Quote::NQuote
-                && job.cmds[0].cmd.1 == "return" {
-                    return Ok((0, String::new()))
+                if shell.options.xtrace {
+                    eprintln!("+ {}", job);
                 }
                 execif = job.execnext;
                 if let Some(execcond) = execif {
@@ -112,13 +120,14 @@ pub fn execute_jobs(
                                 result = execute(shell, job, false, capture)?;
                             }
                             captured.push_str(&result.stdout);
+                            exit_on_failure(shell, result.status, capture);
                             continue;
                         }
                         Exec::Background => { //run jobs asynchronously
                             if job.cmds[0].cmd.1.ends_with("()") {
                                 result = execute_func(shell, job)?.into();
                             } else {
-                                result = execute(shell, job, false, capture)?;
+                                result = execute(shell, job, true, capture)?;
                             }
                             captured.push_str(&result.stdout);
                             continue;
@@ -131,6 +140,7 @@ pub fn execute_jobs(
                         result = execute(shell, job, false, capture)?;
                     }
                     captured.push_str(&result.stdout);
+                    exit_on_failure(shell, result.status, capture);
                 }
             }
             ExecType::Script(script) => {
@@ -144,8 +154,17 @@ pub fn execute_jobs(
     Ok((result.status, captured))
 }
 
+/// Exits the shell if a job failed and `errexit` is set.
+/// Jobs run for command substitution never exit the shell.
+fn exit_on_failure(shell: &Shell, status: i32, capture: bool) {
+    if shell.options.errexit && status != 0 && !capture {
+        let _ = io::stdout().flush();
+        process::exit(status);
+    }
+}
+
 /// Lower level control. Executes single pipeline.
-/// Checks for builtins without pipeline
+/// Checks for variable assignments and implicit cd without pipeline
 pub fn execute(
     shell: &mut Shell, 
     job: Job, 
@@ -173,77 +192,11 @@ pub fn execute(
             let status = cd::run(shell, cmd, true);
             return Ok(CommandResult::from_status(status))
         }
-        match cmd.cmd.as_str() {
-            "cd" => {
-                let status = cd::run(shell, cmd, false);
-                return Ok(CommandResult::from_status(status));
-            }
-            "bg" => {
-                let status = bg::run(shell, cmd);
-                return Ok(CommandResult::from_status(status));
-            }
-            "fg" => {
-                let status = fg::run(shell, cmd);
-                return Ok(CommandResult::from_status(status));
-            }
-            "jobs" => {
-                let status = jobs::run(shell, cmd);
-                return Ok(CommandResult::from_status(status));
-            }
-            "alias" => {
-                let status = alias::set(shell, cmd);
-                return Ok(CommandResult::from_status(status));
-            }
-            "unalias" => {
-                let status = alias::unset(shell, cmd);
-                return Ok(CommandResult::from_status(status));
-            }
-            "let" => {
-                let status = set::run(shell, cmd);
-                return Ok(CommandResult::from_status(status));
-            }
-            "which" => {
-                let status = which::run(shell, cmd);
-                return Ok(CommandResult::from_status(status));
-            }
-            "show" => {
-                let status = show::run(shell, cmd);
-                return Ok(CommandResult::from_status(status));
-            }
-            "pushd" => {
-                let status = dirstack::pushd(shell, cmd);
-                return Ok(CommandResult::from_status(status));
-            }
-            "popd" => {
-                let status = dirstack::popd(shell, cmd);
-                return Ok(CommandResult::from_status(status));
-            }
-            "dirs" => {
-                let status = dirstack::dirs(shell, cmd);
-                return Ok(CommandResult::from_status(status));
-            }
-            "eval" => {
-            }
-            "source" => {
-            }
-            "export" => {
-                let status = export::run(cmd);
-                return Ok(CommandResult::from_status(status));
-            }
-            "echo" => {
-            }
-            "kill" => {
-            }
-            "exit" => {
-                let status = exit::run(shell, cmd);
-                return Ok(CommandResult::from_status(status));
-            }
-            _ => {}
-        }
     }
-    
+
+    //builtins are run in-process by run_pipeline
     let (given, result) = core::run_pipeline(
-        shell, job.id, cmds, background, capture
+        shell, cmds, background, capture
     )?;
     if given {
         let pgid = getpgid(None)?;
@@ -263,7 +216,7 @@ fn extract_constructs(jobs: Vec<Job>) -> Result<Vec<ExecType>, ShellError> {
     for job in jobs {
         if job.cmds[0].cmd.0 == Quote::NQuote {
             match job.cmds[0].cmd.1.as_str() {
-                "for" | "while" | "if" => {
+                "for" | "while" | "if" | "match" => {
                     if !in_construct {
                         in_construct = true; 
                     }
@@ -301,22 +254,33 @@ fn execute_func(shell: &mut Shell, job: Job) -> Result<(i32, String), ShellError
         for (quote, mut string) in job.cmds[0].args[1..].to_vec() {
             match quote {
                 Quote::NQuote => {
-                    expand_variables(shell, &mut string);
+                    // arrays are passed in as separate arguments
+                    if let Some(name) = string.strip_prefix('@') {
+                        if let Some(Variable::Arr(arr)) = shell.get_variable(name) {
+                            func_args.extend(arr.iter().map(|var| var.to_string()));
+                            continue
+                        }
+                    }
+                    expand_variables(shell, &mut string)?;
                     expand_tilde(shell, &mut string);
                     func_args.push(string);
                 }
                 Quote::Variable => {
-                    if let Some(var) = shell.get_variable(&string[1..]) {
+                    if string.ends_with("]") {
+                        func_args.push(index_into(shell, &string)?.to_string());
+                    } else if let Some(var) = shell.get_variable(&string[1..]) {
                         func_args.push(var.to_string());
+                    } else {
+                        check_unbound(shell, &string[1..])?;
                     }
                 }
                 Quote::DQuote => {
-                    expand_variables(shell, &mut string);
+                    expand_variables(shell, &mut string)?;
                     string = substitute_commands(shell, &string)?;
                     func_args.push(string);
                 }
                 Quote::BQuote => {
-                    expand_variables(shell, &mut string);
+                    expand_variables(shell, &mut string)?;
                     func_args.extend(substitute_commands(shell, &string)?
                         .split_whitespace().map(|s| s.to_string())
                         .collect::<Vec<String>>()
@@ -332,7 +296,7 @@ fn execute_func(shell: &mut Shell, job: Job) -> Result<(i32, String), ShellError
                     func_args.extend(expand_braces(shell, string)?);
                 }
                 Quote::NmSpce => {
-                    func_args.push(string);
+                    func_args.extend(crate::namespace::expand(shell, &string)?);
                 }
                 Quote::SQuote => {
                     func_args.push(string);
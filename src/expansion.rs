@@ -14,6 +14,7 @@ use crate::types::{
     Variable,
 };
 use crate::execute;
+use crate::history::HistEntry;
 
 pub fn expand_tilde(shell: &mut Shell, string: &mut String) {  
     let home = env::var("HOME").unwrap_or(String::new());
@@ -223,6 +224,46 @@ pub fn expand_braces(shell: &mut Shell, mut string: String) -> Result<Vec<String
 }
 
 pub fn expand_range(shell: &mut Shell, brkt: &str) -> Result<Vec<String>, ShellError> {
+    let (mut start, end, step_by, up_to_equals) = parse_range(shell, brkt)?;
+    let mut to_return = Vec::new();
+    if start < end {
+        while start < end {
+            to_return.push(start.to_string());
+            start += step_by as i32;
+        }
+    } else {
+        while start > end {
+            to_return.push(start.to_string());
+            start -= step_by as i32;
+        }
+    }
+    if up_to_equals {
+        to_return.push(end.to_string());
+    }
+    Ok(to_return)
+}
+
+/// Checks whether `value` is one of the numbers `expand_range`
+/// would produce, without expanding the range.
+pub fn range_contains(shell: &mut Shell, brkt: &str, value: i64) -> Result<bool, ShellError> {
+    let (start, end, step_by, up_to_equals) = parse_range(shell, brkt)?;
+    let (start, end, step_by) = (start as i64, end as i64, step_by as i64);
+    if up_to_equals && value == end {
+        return Ok(true)
+    }
+    let offset = if start < end && (start..end).contains(&value) {
+        value - start
+    } else if start > end && value <= start && value > end {
+        start - value
+    } else {
+        return Ok(false)
+    };
+    Ok(if step_by == 0 { offset == 0 } else { offset % step_by == 0 })
+}
+
+/// Splits a range such as `1..10`, `$a..=$b` or `10..0..2` into its
+/// start, end and step, and whether the end is included.
+fn parse_range(shell: &mut Shell, brkt: &str) -> Result<(i32, i32, u32, bool), ShellError> {
     let mut range: Vec<String> = brkt.split("..").filter(
         |string| !string.is_empty()
     ).map(|string| string.to_string()).collect();
@@ -267,35 +308,30 @@ pub fn expand_range(shell: &mut Shell, brkt: &str) -> Result<Vec<String>, ShellE
             }
         }
     }
-    let (mut start, end) = (numeric[0], numeric[1]);
-    let mut to_return = Vec::new();
-    if start < end {
-        while start < end {
-            to_return.push(start.to_string());
-            start += step_by as i32;
-        }
-    } else {
-        while start > end {
-            to_return.push(start.to_string());
-            start -= step_by as i32;
-        }
-    }
-    if up_to_equals {
-        to_return.push(end.to_string());
-    }
-    Ok(to_return)
+    Ok((numeric[0], numeric[1], step_by, up_to_equals))
 }
 
-pub fn expand_variables(shell: &Shell, string: &mut String) {
+pub fn expand_variables(shell: &Shell, string: &mut String) -> Result<(), ParseError> {
     lazy_static! {
-        static ref RE_VAR: Regex = Regex::new(r"\$[a-zA-Z0-9_]+").unwrap();
+        static ref RE_VAR: Regex = Regex::new(r"\$([a-zA-Z0-9_]+|[@#])").unwrap();
         static ref RE_ARR: Regex = Regex::new(r"@[a-zA-Z0-9_]+\[[0-9]+\]").unwrap();
+        static ref RE_IDX: Regex = Regex::new(r"\$[a-zA-Z0-9_]+\[[^\]]+\]").unwrap();
+    }
+    for capture in RE_IDX.captures_iter(&string.clone()) {
+        if let Some(capture) = capture.get(0) {
+            if let Ok(var) = index_into(shell, capture.as_str()) {
+                *string = string.replacen(capture.as_str(), &var.to_string(), 1);
+            } else {
+                *string = string.replacen(capture.as_str(), "", 1);
+            }
+        }
     }
     for capture in RE_VAR.captures_iter(&string.clone()) {
         if let Some(capture) = capture.get(0) {
             if let Some(var) = shell.get_variable(&capture.as_str()[1..]) {
                 *string = string.replacen(capture.as_str(), &var.to_string(), 1);
             } else {
+                check_unbound(shell, &capture.as_str()[1..])?;
                 *string = string.replacen(capture.as_str(), "", 1);
             }
         }
@@ -309,6 +345,17 @@ pub fn expand_variables(shell: &Shell, string: &mut String) {
             }
         }
     }
+    Ok(())
+}
+
+/// Fails if the `nounset` option is on, for expanding
+/// the variable `name` that is not set.
+pub fn check_unbound(shell: &Shell, name: &str) -> Result<(), ParseError> {
+    //the positional parameters are always allowed to be empty
+    if shell.options.nounset && name != "@" && name != "#" {
+        return Err(ParseError::UnboundVariable(name.to_string()))
+    }
+    Ok(())
 }
 
 pub fn index_into(shell: &Shell, string: &str) 
@@ -328,6 +375,21 @@ pub fn index_into(shell: &Shell, string: &str)
             var_name.push(c);
         }
     }
+    // the index can itself be a variable
+    if idx.starts_with('$') {
+        idx = shell.get_variable(&idx[1..])
+            .map(|var| var.to_string())
+            .unwrap_or_default();
+    }
+    if let Some(map) = shell.get_map(&var_name) {
+        let key = idx.trim_matches(|c| c == '"' || c == '\'');
+        return match map.get(key) {
+            Some(var) => Ok(var.clone()),
+            None => Err(
+                ShellError::from(format!("error: no key {} in map {}", key, var_name))
+            )
+        }
+    }
     let idx: usize = if let Ok(int) = idx.parse() {
         int
     } else {
@@ -353,6 +415,28 @@ pub fn index_into(shell: &Shell, string: &str)
     }
 }
 
+/// Expands `@keys(map)` and `@values(map)` into the keys or values of a map.
+/// Returns `None` if the word is not of either form.
+pub fn expand_map_iter(shell: &Shell, word: &str) -> Option<Result<Vec<String>, ShellError>> {
+    lazy_static! {
+        static ref RE_MAPITER: Regex = Regex::new(
+            r"^@(keys|values)\(([a-zA-Z0-9_]+)\)$"
+        ).unwrap();
+    }
+    let captures = RE_MAPITER.captures(word)?;
+    let map = match shell.get_map(&captures[2]) {
+        Some(map) => map,
+        None => return Some(Err(
+            ShellError::from(format!("error: no map {} found", &captures[2]))
+        ))
+    };
+    if &captures[1] == "keys" {
+        Some(Ok(map.keys()))
+    } else {
+        Some(Ok(map.values().iter().map(|var| var.to_string()).collect()))
+    }
+}
+
 pub fn expand_glob(string: &str) -> Result<Vec<String>, ParseError> {
     let mut to_return = Vec::new();
     for path in glob(string)? {
@@ -371,6 +455,238 @@ pub fn replace_aliases(shell: &Shell, word: String) -> String {
     word
 }
 
+/// Performs csh-style history expansion on a line of input.
+///
+/// Supports the event designators `!!`, `!n`, `!-n`, `!string` and
+/// `!?string?`, optionally followed by a word designator (`:n`, `:^`,
+/// `:$` or `:*`), as well as the shorthands `!$`, `!^` and `!*`,
+/// and `^old^new` quick substitution on the previous command.
+///
+/// A `!` is left alone when it is escaped, single-quoted, followed
+/// by whitespace, `=`, `(`, `[` or `"`, or when it negates a condition
+/// (e.g. `if !true`).
+pub fn expand_history(shell: &Shell, line: &str) -> Result<String, ShellError> {
+    let entries = shell.history.entries();
+    if line.starts_with('^') {
+        return quick_substitute(entries, line)
+    }
+    let chars: Vec<char> = line.chars().collect();
+    let mut expanded = String::new();
+    let mut in_squote = false;
+    let mut in_dquote = false;
+    //a `!` in a bracketed condition such as `[!$done]` is a negation
+    let mut brackets: usize = 0;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\\' if !in_squote => {
+                expanded.push(c);
+                if let Some(next) = chars.get(i + 1) {
+                    expanded.push(*next);
+                    i += 1;
+                }
+            }
+            '\'' if !in_dquote => {
+                in_squote = !in_squote;
+                expanded.push(c);
+            }
+            '"' if !in_squote => {
+                in_dquote = !in_dquote;
+                expanded.push(c);
+            }
+            '[' if !in_squote && !in_dquote => {
+                brackets += 1;
+                expanded.push(c);
+            }
+            ']' if !in_squote && !in_dquote => {
+                brackets = brackets.saturating_sub(1);
+                expanded.push(c);
+            }
+            '!' if !in_squote && brackets == 0 => {
+                let literal = match chars.get(i + 1) {
+                    None => true,
+                    Some(next) => next.is_whitespace()
+                        || matches!(next, '=' | '(' | '[' | '"'),
+                };
+                if literal || negates_condition(&expanded) {
+                    expanded.push(c);
+                } else {
+                    let (text, consumed) = expand_designator(entries, &chars[i + 1..])?;
+                    expanded.push_str(&text);
+                    i += consumed;
+                }
+            }
+            _ => {
+                expanded.push(c);
+            }
+        }
+        i += 1;
+    }
+    Ok(expanded)
+}
+
+/// Checks whether a `!` at the end of `preceding` starts a negated condition.
+fn negates_condition(preceding: &str) -> bool {
+    if !preceding.ends_with(char::is_whitespace) {
+        return false
+    }
+    matches!(preceding.split_whitespace().last(), Some("if") | Some("elif") | Some("while"))
+}
+
+/// Expands a single history designator, not including the leading `!`.
+/// Returns the expanded text and the number of characters consumed.
+fn expand_designator(entries: &[HistEntry], designator: &[char])
+-> Result<(String, usize), ShellError> {
+    let mut consumed = 0;
+    let entry = match designator[0] {
+        '!' => {
+            consumed += 1;
+            entries.last()
+        }
+        // shorthand for !!:$, !!:^ and !!:*
+        '$' | '^' | '*' => {
+            let entry = entries.last().ok_or_else(|| event_not_found(&designator[..1]))?;
+            let words = select_words(&entry.cmd, &designator[..1])?;
+            return Ok((words, 1))
+        }
+        '-' | '0'..='9' => {
+            let backwards = designator[0] == '-';
+            if backwards {
+                consumed += 1;
+            }
+            let digits: String = designator[consumed..].iter()
+                .take_while(|c| c.is_ascii_digit())
+                .collect();
+            consumed += digits.len();
+            match digits.parse::<usize>() {
+                Ok(n) if backwards => entries.len().checked_sub(n)
+                    .and_then(|idx| entries.get(idx)),
+                Ok(n) => n.checked_sub(1).and_then(|idx| entries.get(idx)),
+                Err(_) => None,
+            }
+        }
+        '?' => {
+            consumed += 1;
+            let search: String = designator[consumed..].iter()
+                .take_while(|c| **c != '?')
+                .collect();
+            consumed += search.chars().count();
+            if designator.get(consumed) == Some(&'?') {
+                consumed += 1;
+            }
+            entries.iter().rev().find(|entry| entry.cmd.contains(&search))
+        }
+        _ => {
+            let prefix: String = designator.iter()
+                .take_while(|c| {
+                    !c.is_whitespace() && !matches!(c, ':' | ';' | '|' | '&' | '<' | '>' | '"' | '\'')
+                })
+                .collect();
+            consumed += prefix.chars().count();
+            entries.iter().rev().find(|entry| entry.cmd.starts_with(&prefix))
+        }
+    };
+    let entry = entry.ok_or_else(|| event_not_found(&designator[..consumed]))?;
+    // optional word designator
+    if designator.get(consumed) == Some(&':') {
+        let word: Vec<char> = match designator.get(consumed + 1) {
+            Some(c) if matches!(c, '$' | '^' | '*') => vec![*c],
+            Some(c) if c.is_ascii_digit() => designator[consumed + 1..].iter()
+                .take_while(|c| c.is_ascii_digit())
+                .cloned()
+                .collect(),
+            _ => return Ok((entry.cmd.clone(), consumed)),
+        };
+        let words = select_words(&entry.cmd, &word)?;
+        return Ok((words, consumed + 1 + word.len()))
+    }
+    Ok((entry.cmd.clone(), consumed))
+}
+
+/// Selects words out of a history entry with a word designator.
+fn select_words(cmd: &str, designator: &[char]) -> Result<String, ShellError> {
+    let words = split_history_words(cmd);
+    let selected = match designator {
+        ['$'] => words.last().cloned(),
+        ['^'] => words.get(1).cloned(),
+        ['*'] => Some(words.iter().skip(1).cloned().collect::<Vec<String>>().join(" ")),
+        digits => {
+            let idx: String = digits.iter().collect();
+            idx.parse::<usize>().ok().and_then(|idx| words.get(idx).cloned())
+        }
+    };
+    selected.ok_or_else(|| {
+        ShellError::from(format!(
+            "oyster: :{}: bad word specifier",
+            designator.iter().collect::<String>()
+        ))
+    })
+}
+
+/// Splits a history entry into words on whitespace, keeping quoted strings whole.
+fn split_history_words(cmd: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quote: Option<char> = None;
+    let mut chars = cmd.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                word.push(c);
+                if let Some(c) = chars.next() {
+                    word.push(c);
+                }
+            }
+            '\'' | '"' if quote.is_none() => {
+                quote = Some(c);
+                word.push(c);
+            }
+            c if quote == Some(c) => {
+                quote = None;
+                word.push(c);
+            }
+            c if c.is_whitespace() && quote.is_none() => {
+                if !word.is_empty() {
+                    words.push(word.clone());
+                    word.clear();
+                }
+            }
+            _ => {
+                word.push(c);
+            }
+        }
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    words
+}
+
+/// Performs `^old^new` substitution on the previous command.
+fn quick_substitute(entries: &[HistEntry], line: &str) -> Result<String, ShellError> {
+    let mut parts = line[1..].splitn(3, '^');
+    let old = parts.next().unwrap_or("");
+    let new = parts.next().unwrap_or("");
+    let last = match entries.last() {
+        Some(entry) => &entry.cmd,
+        None => return Err(ShellError::from("oyster: no previous command")),
+    };
+    if old.is_empty() || !last.contains(old) {
+        return Err(ShellError::from(
+            format!("oyster: {}: substitution failed", line.trim_end())
+        ))
+    }
+    Ok(last.replacen(old, new, 1))
+}
+
+fn event_not_found(designator: &[char]) -> ShellError {
+    ShellError::from(format!(
+        "oyster: !{}: event not found",
+        designator.iter().collect::<String>()
+    ))
+}
+
 // This command is gonna be sooo fucking slow
 pub fn substitute_commands(shell: &mut Shell, string: &str) -> Result<String, CmdSubError> {
     let mut string = string.to_string();
@@ -457,6 +773,14 @@ fn execute_commands_once(shell: &mut Shell, input: &str)
             eprintln!("error: unmatched square bracket");
             return Err(CmdSubError);
         }
+        UnmatchedBrace => {
+            eprintln!("error: unmatched brace");
+            Err(CmdSubError)
+        }
+        UnmatchedHereDoc => {
+            eprintln!("error: unterminated here-document");
+            Err(CmdSubError)
+        }
         EndsOnAnd | EndsOnOr | EndsOnPipe => {
             eprintln!("error: command ends on delimiter");
             return Err(CmdSubError);
@@ -499,13 +823,84 @@ mod tests {
         shell.add_variable("hello", Var::from("wassup"));
         shell.add_variable("what", Var::from("is this"));
         let mut test = String::from("goodbye $hello i know you $what $wontwork");
-        expand_variables(&shell, &mut test);
+        expand_variables(&shell, &mut test).unwrap();
         assert_eq!(
             test,
             String::from("goodbye wassup i know you is this ")
         );
     }
 
+    #[test]
+    fn check_history_expansion() {
+        let mut shell = Shell::new();
+        shell.history.push("ls -l /tmp", 0);
+        shell.history.push("echo 'hello world' again", 0);
+        shell.history.push("cargo build --release", 101);
+        let expand = |line: &str| expand_history(&shell, line).unwrap();
+        assert_eq!(expand("sudo !!"), "sudo cargo build --release");
+        assert_eq!(expand("!1"), "ls -l /tmp");
+        assert_eq!(expand("!-2"), "echo 'hello world' again");
+        assert_eq!(expand("!ls && !ca"), "ls -l /tmp && cargo build --release");
+        assert_eq!(expand("!?world?"), "echo 'hello world' again");
+        assert_eq!(expand("cat !$"), "cat --release");
+        assert_eq!(expand("!echo:1 !1:$"), "'hello world' /tmp");
+        assert_eq!(expand("!-2:*"), "'hello world' again");
+        assert_eq!(expand("!!:0 test"), "cargo test");
+        assert_eq!(expand("^build^check"), "cargo check --release");
+        // these should all be left alone
+        assert_eq!(expand("echo 'hi !!' \\!! \"yo!\" ! $a != 2"), "echo 'hi !!' \\!! \"yo!\" ! $a != 2");
+        assert_eq!(expand("if !true"), "if !true");
+        assert_eq!(expand("if [$a == 1 && !$b]"), "if [$a == 1 && !$b]");
+        assert_eq!(expand("while [!done] && !echo"), "while [!done] && echo 'hello world' again");
+        assert!(expand_history(&shell, "!nothing").is_err());
+        assert!(expand_history(&shell, "!!:5").is_err());
+        assert!(expand_history(&shell, "^foo^bar").is_err());
+    }
+
+    #[test]
+    fn check_range_contains() {
+        let mut shell = Shell::new();
+        for range in &["1..10", "1..=10", "0..10..3", "10..0", "10..=0..4", "5..5", "5..=5"] {
+            let expanded = expand_range(&mut shell, range).unwrap();
+            for value in -2..13 {
+                assert_eq!(
+                    range_contains(&mut shell, range, value).unwrap(),
+                    expanded.contains(&value.to_string()),
+                    "{} in {}", value, range
+                );
+            }
+        }
+        assert!(range_contains(&mut shell, "0..2000000000", 1999999999).unwrap());
+        assert!(!range_contains(&mut shell, "0..2000000000..2", 1999999999).unwrap());
+    }
+
+    #[test]
+    fn check_map_indexing() {
+        use crate::types::Map;
+        let mut shell = Shell::new();
+        let mut map = Map::new();
+        map.insert("b", Var::from("x y"));
+        map.insert("a", Var::Int(1));
+        shell.add_map("m", map);
+        shell.add_variable("k", Var::from("b"));
+        assert_eq!(index_into(&shell, "$m[a]"), Ok(Var::Int(1)));
+        assert_eq!(index_into(&shell, "$m[$k]"), Ok(Var::from("x y")));
+        assert!(index_into(&shell, "$m[c]").is_err());
+        let mut test = String::from("$m[a] and $m[$k]");
+        expand_variables(&shell, &mut test).unwrap();
+        assert_eq!(test, "1 and x y");
+        assert_eq!(
+            expand_map_iter(&shell, "@keys(m)").unwrap().unwrap(),
+            vec!["a", "b"]
+        );
+        assert_eq!(
+            expand_map_iter(&shell, "@values(m)").unwrap().unwrap(),
+            vec!["1", "x y"]
+        );
+        assert!(expand_map_iter(&shell, "@keys(nothing)").unwrap().is_err());
+        assert!(expand_map_iter(&shell, "@m").is_none());
+    }
+
     #[test]
     fn check_path_globbing() { 
         //this fails because i couldn't be bothered to type out everything
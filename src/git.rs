@@ -0,0 +1,955 @@
+use std::collections::{BinaryHeap, HashMap};
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{FileExt, MetadataExt};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use flate2::read::ZlibDecoder;
+use sha1::{Digest, Sha1};
+
+/// How long the prompt waits on git by default, in milliseconds.
+/// Can be changed with `$OYSTER_GIT_TIMEOUT`.
+pub const GIT_TIMEOUT_DFL: u64 = 100;
+
+/// The name of a git object, the SHA-1 hash of its contents.
+type Oid = [u8; 20];
+
+const MODE_SYMLINK: u32 = 0o120000;
+const MODE_GITLINK: u32 = 0o160000;
+
+/// Git status segments of the prompt, for the repository
+/// containing the current directory.
+///
+/// Everything is read straight from the files in `.git`. The slower
+/// segments stop and show nothing once the time budget runs out,
+/// so that a huge repository never holds up the prompt.
+pub struct GitPrompt {
+    repo: Option<Repo>,
+    deadline: Instant,
+}
+
+impl GitPrompt {
+    pub fn new() -> Self {
+        let timeout = env::var("OYSTER_GIT_TIMEOUT").ok()
+            .and_then(|ms| ms.parse().ok())
+            .unwrap_or(GIT_TIMEOUT_DFL);
+        let repo = env::current_dir().ok().and_then(|dir| Repo::discover(&dir));
+        GitPrompt {
+            repo,
+            deadline: Instant::now() + Duration::from_millis(timeout),
+        }
+    }
+
+    /// The current branch, or the abbreviated commit if HEAD is detached.
+    pub fn branch(&self) -> String {
+        match self.repo.as_ref().and_then(|repo| repo.head()) {
+            Some(Head::Branch(branch)) => branch,
+            Some(Head::Detached(oid)) => format!("({})", &to_hex(&oid)[..7]),
+            None => String::new(),
+        }
+    }
+
+    /// `*` if any tracked file has changes, staged or not.
+    pub fn dirty(&mut self) -> String {
+        let deadline = self.deadline;
+        match self.repo.as_mut().and_then(|repo| repo.is_dirty(deadline)) {
+            Some(true) => String::from("*"),
+            _ => String::new(),
+        }
+    }
+
+    /// How many commits the branch is ahead of and behind its upstream,
+    /// as in `↑2↓1`.
+    pub fn ahead_behind(&mut self) -> String {
+        let deadline = self.deadline;
+        let counts = self.repo.as_mut().and_then(|repo| repo.ahead_behind(deadline));
+        let mut segment = String::new();
+        if let Some((ahead, behind)) = counts {
+            if ahead > 0 {
+                segment.push_str(&format!("↑{}", ahead));
+            }
+            if behind > 0 {
+                segment.push_str(&format!("↓{}", behind));
+            }
+        }
+        segment
+    }
+
+    /// The number of stash entries, as in `≡2`.
+    pub fn stash(&self) -> String {
+        match self.repo.as_ref().map(|repo| repo.stash_count()) {
+            Some(count) if count > 0 => format!("≡{}", count),
+            _ => String::new(),
+        }
+    }
+}
+
+/// What HEAD points to.
+#[derive(Debug, Clone, PartialEq)]
+enum Head {
+    Branch(String),
+    Detached(Oid),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Kind {
+    Commit,
+    Tree,
+    Blob,
+    Tag,
+}
+
+impl Kind {
+    fn from_name(name: &[u8]) -> Option<Kind> {
+        match name {
+            b"commit" => Some(Kind::Commit),
+            b"tree" => Some(Kind::Tree),
+            b"blob" => Some(Kind::Blob),
+            b"tag" => Some(Kind::Tag),
+            _ => None,
+        }
+    }
+
+    fn from_pack(code: u8) -> Option<Kind> {
+        match code {
+            1 => Some(Kind::Commit),
+            2 => Some(Kind::Tree),
+            3 => Some(Kind::Blob),
+            4 => Some(Kind::Tag),
+            _ => None,
+        }
+    }
+}
+
+struct Commit {
+    tree: Oid,
+    parents: Vec<Oid>,
+    time: i64,
+}
+
+/// A single file in the index.
+struct IndexEntry {
+    path: String,
+    mode: u32,
+    mtime: (u32, u32),
+    size: u32,
+    oid: Oid,
+    stage: u16,
+    skip_worktree: bool,
+}
+
+struct Index {
+    entries: Vec<IndexEntry>,
+    /// The tree the index would be written as, if git has it cached.
+    tree: Option<Oid>,
+}
+
+/// A packfile and its index.
+struct Pack {
+    idx: File,
+    pack: File,
+    fanout: Vec<u32>,
+}
+
+impl Pack {
+    fn open(idx_path: &Path) -> io::Result<Pack> {
+        let idx = File::open(idx_path)?;
+        let pack = File::open(idx_path.with_extension("pack"))?;
+        let mut header = [0; 8 + 256 * 4];
+        idx.read_exact_at(&mut header, 0)?;
+        //only version 2 indexes are written by any recent git
+        if header[..8] != [0xff, b't', b'O', b'c', 0, 0, 0, 2] {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "bad pack index"))
+        }
+        let fanout = header[8..].chunks_exact(4).map(be_u32).collect();
+        Ok(Pack { idx, pack, fanout })
+    }
+
+    /// Returns the offset of an object in the packfile, if it is there.
+    fn find(&self, oid: &Oid) -> io::Result<Option<u64>> {
+        let count = self.fanout[255] as u64;
+        let first = oid[0] as usize;
+        let mut lo = if first == 0 {0} else {self.fanout[first - 1] as u64};
+        let mut hi = self.fanout[first] as u64;
+        let mut name = [0; 20];
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            self.idx.read_exact_at(&mut name, 1032 + mid * 20)?;
+            match name.cmp(oid) {
+                std::cmp::Ordering::Less => lo = mid + 1,
+                std::cmp::Ordering::Greater => hi = mid,
+                std::cmp::Ordering::Equal => {
+                    let mut offset = [0; 4];
+                    self.idx.read_exact_at(&mut offset, 1032 + count * 24 + mid * 4)?;
+                    let offset = be_u32(&offset);
+                    if offset & 0x8000_0000 == 0 {
+                        return Ok(Some(offset as u64))
+                    }
+                    //the offset is in the table of large offsets
+                    let mut large = [0; 8];
+                    let index = (offset & 0x7fff_ffff) as u64;
+                    self.idx.read_exact_at(&mut large, 1032 + count * 28 + index * 8)?;
+                    return Ok(Some(u64::from_be_bytes(large)))
+                }
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// A repository, as found from a directory inside its working tree.
+struct Repo {
+    workdir: PathBuf,
+    /// The git directory of this working tree, with HEAD and the index.
+    gitdir: PathBuf,
+    /// The git directory shared by every worktree, with refs and objects.
+    commondir: PathBuf,
+    packs: Option<Vec<Pack>>,
+}
+
+impl Repo {
+    /// Finds the repository that `dir` is in, looking in each parent
+    /// directory in turn.
+    ///
+    /// `.git` can be a file pointing to the real git directory,
+    /// as it is in linked worktrees and submodules.
+    pub fn discover(dir: &Path) -> Option<Repo> {
+        for dir in dir.ancestors() {
+            let dotgit = dir.join(".git");
+            let gitdir = if dotgit.is_dir() {
+                dotgit
+            } else if dotgit.is_file() {
+                let contents = fs::read_to_string(&dotgit).ok()?;
+                let path = contents.trim().strip_prefix("gitdir:")?.trim();
+                dir.join(path)
+            } else {
+                continue
+            };
+            let commondir = match fs::read_to_string(gitdir.join("commondir")) {
+                Ok(path) => gitdir.join(path.trim()),
+                Err(_) => gitdir.clone(),
+            };
+            return Some(Repo {
+                workdir: dir.to_path_buf(),
+                gitdir,
+                commondir,
+                packs: None,
+            })
+        }
+        None
+    }
+
+    pub fn head(&self) -> Option<Head> {
+        let head = fs::read_to_string(self.gitdir.join("HEAD")).ok()?;
+        match head.trim().strip_prefix("ref:") {
+            Some(target) => {
+                let target = target.trim();
+                let branch = target.strip_prefix("refs/heads/").unwrap_or(target);
+                Some(Head::Branch(branch.to_string()))
+            }
+            None => from_hex(head.trim()).map(Head::Detached),
+        }
+    }
+
+    /// Resolves a ref such as `HEAD` or `refs/heads/master` to the
+    /// object it points to, following symbolic refs.
+    fn resolve(&self, name: &str) -> Option<Oid> {
+        let mut name = name.to_string();
+        //symbolic refs can point to each other, but not forever
+        for _ in 0..5 {
+            //only refs under refs/ are shared between worktrees
+            let dir = if name.starts_with("refs/") {&self.commondir} else {&self.gitdir};
+            let contents = match fs::read_to_string(dir.join(&name)) {
+                Ok(contents) => contents,
+                Err(_) => return self.packed_ref(&name),
+            };
+            match contents.trim().strip_prefix("ref:") {
+                Some(target) => name = target.trim().to_string(),
+                None => return from_hex(contents.trim()),
+            }
+        }
+        None
+    }
+
+    /// Looks a ref up in `packed-refs`, where git moves refs
+    /// when it packs the repository.
+    fn packed_ref(&self, name: &str) -> Option<Oid> {
+        let packed = fs::read_to_string(self.commondir.join("packed-refs")).ok()?;
+        packed.lines()
+            .filter(|line| !line.starts_with('#') && !line.starts_with('^'))
+            .find_map(|line| {
+                let (oid, refname) = line.split_at(line.find(' ')?);
+                if refname.trim() == name {from_hex(oid)} else {None}
+            })
+    }
+
+    /// Returns the ref a branch is set to track with `git branch -u`.
+    fn upstream(&self, branch: &str) -> Option<String> {
+        let config = fs::read_to_string(self.commondir.join("config")).ok()?;
+        let section = format!("[branch \"{}\"]", branch);
+        let mut in_section = false;
+        let mut remote = None;
+        let mut merge = None;
+        for line in config.lines().map(str::trim) {
+            if line.starts_with('[') {
+                in_section = line == section;
+                continue
+            }
+            if !in_section {
+                continue
+            }
+            let mut parts = line.splitn(2, '=').map(str::trim);
+            match (parts.next(), parts.next()) {
+                (Some(key), Some(value)) if key.eq_ignore_ascii_case("remote") => {
+                    remote = Some(value.to_string())
+                }
+                (Some(key), Some(value)) if key.eq_ignore_ascii_case("merge") => {
+                    merge = Some(value.to_string())
+                }
+                _ => {}
+            }
+        }
+        let (remote, merge) = (remote?, merge?);
+        //a remote of `.` means the upstream is another local branch
+        if remote == "." {
+            return Some(merge)
+        }
+        let merge = merge.strip_prefix("refs/heads/").unwrap_or(&merge);
+        Some(format!("refs/remotes/{}/{}", remote, merge))
+    }
+
+    /// Counts the commits on the current branch that are not on its
+    /// upstream, and the commits on the upstream that are not on the
+    /// branch. Returns `None` if there is no upstream, or the walk
+    /// takes too long.
+    pub fn ahead_behind(&mut self, deadline: Instant) -> Option<(usize, usize)> {
+        let branch = match self.head()? {
+            Head::Branch(branch) => branch,
+            Head::Detached(_) => return None,
+        };
+        let upstream = self.upstream(&branch)?;
+        let local = self.resolve(&format!("refs/heads/{}", branch))?;
+        let upstream = self.resolve(&upstream)?;
+        if local == upstream {
+            return Some((0, 0))
+        }
+
+        //walks back from both tips, newest commits first, marking each
+        //commit with the tips it is reachable from, until every commit
+        //left to visit is reachable from both
+        const LOCAL: u8 = 1;
+        const UPSTREAM: u8 = 2;
+        const BOTH: u8 = LOCAL | UPSTREAM;
+        let mut flags: HashMap<Oid, u8> = HashMap::new();
+        let mut queue = BinaryHeap::new();
+        for (oid, flag) in &[(local, LOCAL), (upstream, UPSTREAM)] {
+            let commit = self.read_commit(oid)?;
+            flags.insert(*oid, *flag);
+            queue.push((commit.time, *oid));
+        }
+        while let Some((_, oid)) = queue.pop() {
+            if Instant::now() > deadline {
+                return None
+            }
+            let flag = flags[&oid];
+            for parent in self.read_commit(&oid)?.parents {
+                let old = flags.get(&parent).copied().unwrap_or(0);
+                if old | flag != old {
+                    flags.insert(parent, old | flag);
+                    queue.push((self.read_commit(&parent)?.time, parent));
+                }
+            }
+            if queue.iter().all(|(_, oid)| flags[oid] == BOTH) {
+                break
+            }
+        }
+        let ahead = flags.values().filter(|flag| **flag == LOCAL).count();
+        let behind = flags.values().filter(|flag| **flag == UPSTREAM).count();
+        Some((ahead, behind))
+    }
+
+    pub fn stash_count(&self) -> usize {
+        //every stash entry is a line in the stash reflog
+        match fs::read_to_string(self.commondir.join("logs/refs/stash")) {
+            Ok(log) => log.lines().count(),
+            Err(_) => if self.resolve("refs/stash").is_some() {1} else {0},
+        }
+    }
+
+    /// Checks whether any tracked file differs from HEAD, either in the
+    /// index or in the working tree. Untracked files are not counted.
+    ///
+    /// Returns `None` if the check takes too long.
+    pub fn is_dirty(&mut self, deadline: Instant) -> Option<bool> {
+        let index = match fs::read(self.gitdir.join("index")) {
+            Ok(data) => parse_index(&data)?,
+            //a repository with nothing added yet has no index
+            Err(_) => Index { entries: Vec::new(), tree: None },
+        };
+        Some(self.has_staged_changes(&index, deadline)?
+            || self.has_unstaged_changes(&index, deadline)?)
+    }
+
+    fn has_staged_changes(&mut self, index: &Index, deadline: Instant) -> Option<bool> {
+        let head_tree = match self.resolve("HEAD") {
+            Some(head) => self.read_commit(&head)?.tree,
+            None => return Some(!index.entries.is_empty()),
+        };
+        //git keeps the tree of the index around while it is unchanged
+        if index.tree == Some(head_tree) {
+            return Some(false)
+        }
+        let mut files = HashMap::new();
+        self.read_tree_files(&head_tree, "", &mut files, deadline)?;
+        if files.len() != index.entries.len() {
+            return Some(true)
+        }
+        Some(index.entries.iter().any(|entry| {
+            files.get(&entry.path) != Some(&(entry.mode, entry.oid))
+        }))
+    }
+
+    fn has_unstaged_changes(&mut self, index: &Index, deadline: Instant) -> Option<bool> {
+        for entry in &index.entries {
+            if Instant::now() > deadline {
+                return None
+            }
+            //an unresolved merge conflict
+            if entry.stage != 0 {
+                return Some(true)
+            }
+            if entry.skip_worktree {
+                continue
+            }
+            let path = self.workdir.join(&entry.path);
+            if entry.mode == MODE_GITLINK {
+                //a submodule has changed if a different commit is checked out
+                let checked_out = Repo::discover(&path)
+                    .filter(|repo| repo.workdir == path)
+                    .and_then(|repo| repo.resolve("HEAD"));
+                if matches!(checked_out, Some(oid) if oid != entry.oid) {
+                    return Some(true)
+                }
+                continue
+            }
+            let meta = match fs::symlink_metadata(&path) {
+                Ok(meta) => meta,
+                Err(_) => return Some(true),
+            };
+            let mode = if meta.file_type().is_symlink() {
+                MODE_SYMLINK
+            } else if meta.is_file() {
+                if meta.mode() & 0o111 != 0 {0o100755} else {0o100644}
+            } else {
+                return Some(true)
+            };
+            if mode != entry.mode || meta.size() as u32 != entry.size {
+                return Some(true)
+            }
+            let mtime = (meta.mtime() as u32, meta.mtime_nsec() as u32);
+            if mtime == entry.mtime {
+                continue
+            }
+            //the file was touched, so its contents have to be compared
+            let oid = if mode == MODE_SYMLINK {
+                hash_object(Kind::Blob, fs::read_link(&path).ok()?.as_os_str().as_bytes())
+            } else {
+                hash_file(&path, meta.size(), deadline)?
+            };
+            if oid != entry.oid {
+                return Some(true)
+            }
+        }
+        Some(false)
+    }
+
+    /// Lists every file under a tree, with its mode and object.
+    fn read_tree_files(
+        &mut self,
+        tree: &Oid,
+        prefix: &str,
+        files: &mut HashMap<String, (u32, Oid)>,
+        deadline: Instant,
+    ) -> Option<()> {
+        if Instant::now() > deadline {
+            return None
+        }
+        let (kind, data) = self.read_object(tree)?;
+        if kind != Kind::Tree {
+            return None
+        }
+        let mut rest = &data[..];
+        //each entry is `<octal mode> <name>\0<20 byte object name>`
+        while !rest.is_empty() {
+            let space = rest.iter().position(|b| *b == b' ')?;
+            let nul = rest.iter().position(|b| *b == 0)?;
+            let mode = u32::from_str_radix(std::str::from_utf8(&rest[..space]).ok()?, 8).ok()?;
+            let name = String::from_utf8_lossy(&rest[space + 1..nul]);
+            let mut oid = [0; 20];
+            oid.copy_from_slice(rest.get(nul + 1..nul + 21)?);
+            rest = &rest[nul + 21..];
+
+            let path = format!("{}{}", prefix, name);
+            if mode == 0o40000 {
+                self.read_tree_files(&oid, &format!("{}/", path), files, deadline)?;
+            } else {
+                files.insert(path, (mode, oid));
+            }
+        }
+        Some(())
+    }
+
+    fn read_commit(&mut self, oid: &Oid) -> Option<Commit> {
+        let (kind, data) = self.read_object(oid)?;
+        if kind != Kind::Commit {
+            return None
+        }
+        let mut commit = Commit { tree: [0; 20], parents: Vec::new(), time: 0 };
+        for line in data.split(|b| *b == b'\n') {
+            //the headers end at the first blank line
+            if line.is_empty() {
+                break
+            }
+            let line = String::from_utf8_lossy(line);
+            if let Some(tree) = line.strip_prefix("tree ") {
+                commit.tree = from_hex(tree)?;
+            } else if let Some(parent) = line.strip_prefix("parent ") {
+                commit.parents.push(from_hex(parent)?);
+            } else if let Some(committer) = line.strip_prefix("committer ") {
+                //`committer <name> <email> <timestamp> <timezone>`
+                commit.time = committer.rsplit(' ').nth(1)?.parse().ok()?;
+            }
+        }
+        Some(commit)
+    }
+
+    /// Reads an object, either from its own file or from a packfile.
+    fn read_object(&mut self, oid: &Oid) -> Option<(Kind, Vec<u8>)> {
+        let hex = to_hex(oid);
+        let path = self.commondir.join("objects").join(&hex[..2]).join(&hex[2..]);
+        if let Ok(file) = File::open(path) {
+            let mut data = Vec::new();
+            ZlibDecoder::new(BufReader::new(file)).read_to_end(&mut data).ok()?;
+            //loose objects start with `<kind> <size>\0`
+            let nul = data.iter().position(|b| *b == 0)?;
+            let space = data.iter().position(|b| *b == b' ')?;
+            let kind = Kind::from_name(&data[..space])?;
+            return Some((kind, data.split_off(nul + 1)))
+        }
+        if self.packs.is_none() {
+            self.packs = Some(self.open_packs());
+        }
+        let packs = self.packs.as_ref()?;
+        for (i, pack) in packs.iter().enumerate() {
+            if let Ok(Some(offset)) = pack.find(oid) {
+                return self.read_packed(i, offset, 0)
+            }
+        }
+        None
+    }
+
+    fn open_packs(&self) -> Vec<Pack> {
+        let dir = self.commondir.join("objects/pack");
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(_) => return Vec::new(),
+        };
+        entries.filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != "idx" {
+                return None
+            }
+            Pack::open(&path).ok()
+        }).collect()
+    }
+
+    /// Reads the object at `offset` in a packfile,
+    /// applying it to its base object if it is a delta.
+    fn read_packed(&mut self, pack: usize, offset: u64, depth: usize) -> Option<(Kind, Vec<u8>)> {
+        //git itself stops making deltas of deltas at 50 deep
+        if depth > 100 {
+            return None
+        }
+        let mut header = [0; 32];
+        let file = &self.packs.as_ref()?[pack].pack;
+        let read = file.read_at(&mut header, offset).ok()?;
+        let header = &header[..read];
+
+        //the read comes up short at the end of the file
+        let first = *header.first()?;
+        let code = (first >> 4) & 7;
+        let mut size = (first & 15) as u64;
+        let mut pos = 1;
+        let mut shift = 4;
+        while header[pos - 1] & 0x80 != 0 {
+            size |= ((*header.get(pos)? & 0x7f) as u64) << shift;
+            shift += 7;
+            pos += 1;
+        }
+        let base = match code {
+            6 => {
+                let (distance, used) = read_offset(&header[pos..])?;
+                pos += used;
+                Some(self.read_packed(pack, offset.checked_sub(distance)?, depth + 1)?)
+            }
+            7 => {
+                let mut base = [0; 20];
+                base.copy_from_slice(header.get(pos..pos + 20)?);
+                pos += 20;
+                Some(self.read_object(&base)?)
+            }
+            _ => None,
+        };
+
+        let mut file = self.packs.as_ref()?[pack].pack.try_clone().ok()?;
+        file.seek(SeekFrom::Start(offset + pos as u64)).ok()?;
+        let mut data = Vec::new();
+        ZlibDecoder::new(BufReader::new(file)).take(size).read_to_end(&mut data).ok()?;
+        match base {
+            Some((kind, base)) => Some((kind, apply_delta(&base, &data)?)),
+            None => Some((Kind::from_pack(code)?, data)),
+        }
+    }
+}
+
+fn parse_index(data: &[u8]) -> Option<Index> {
+    if data.get(..4)? != b"DIRC" {
+        return None
+    }
+    let version = be_u32(data.get(4..8)?);
+    let count = be_u32(data.get(8..12)?);
+    let mut entries = Vec::new();
+    let mut pos = 12;
+    let mut last_path: Vec<u8> = Vec::new();
+    for _ in 0..count {
+        let entry = data.get(pos..pos + 62)?;
+        let flags = be_u16(&entry[60..62]);
+        let mut name_start = pos + 62;
+        let mut skip_worktree = false;
+        //version 3 adds a second set of flags
+        if version >= 3 && flags & 0x4000 != 0 {
+            let extended = be_u16(data.get(name_start..name_start + 2)?);
+            skip_worktree = extended & 0x4000 != 0;
+            name_start += 2;
+        }
+        let path = if version >= 4 {
+            //each path only stores what differs from the one before it
+            let (strip, used) = read_offset(data.get(name_start..)?)?;
+            let suffix_start = name_start + used;
+            let len = data.get(suffix_start..)?.iter().position(|b| *b == 0)?;
+            last_path.truncate(last_path.len().checked_sub(strip as usize)?);
+            last_path.extend_from_slice(&data[suffix_start..suffix_start + len]);
+            pos = suffix_start + len + 1;
+            last_path.clone()
+        } else {
+            let len = match (flags & 0xfff) as usize {
+                0xfff => data.get(name_start..)?.iter().position(|b| *b == 0)?,
+                len => len,
+            };
+            //entries are padded with nuls to a multiple of 8 bytes
+            pos += (name_start - pos + len + 8) & !7;
+            data.get(name_start..name_start + len)?.to_vec()
+        };
+        let mut oid = [0; 20];
+        oid.copy_from_slice(&entry[40..60]);
+        entries.push(IndexEntry {
+            path: String::from_utf8_lossy(&path).into_owned(),
+            mode: be_u32(&entry[24..28]),
+            mtime: (be_u32(&entry[8..12]), be_u32(&entry[12..16])),
+            size: be_u32(&entry[36..40]),
+            oid,
+            stage: (flags >> 12) & 3,
+            skip_worktree,
+        });
+    }
+
+    //extensions follow, up to the checksum at the end
+    let mut tree = None;
+    while pos + 8 <= data.len().saturating_sub(20) {
+        let signature = &data[pos..pos + 4];
+        let size = be_u32(&data[pos + 4..pos + 8]) as usize;
+        let ext = data.get(pos + 8..pos + 8 + size)?;
+        match signature {
+            //the cached tree, starting with the root: `\0<entries> <subtrees>\n<oid>`
+            b"TREE" if ext.first() == Some(&0) => {
+                let newline = ext.iter().position(|b| *b == b'\n')?;
+                let counts = String::from_utf8_lossy(&ext[1..newline]);
+                let valid = !counts.starts_with('-');
+                if valid {
+                    let mut oid = [0; 20];
+                    oid.copy_from_slice(ext.get(newline + 1..newline + 21)?);
+                    tree = Some(oid);
+                }
+            }
+            //a split index keeps most of its entries in another file
+            b"link" => return None,
+            _ => {}
+        }
+        pos += 8 + size;
+    }
+    Some(Index { entries, tree })
+}
+
+/// Applies a delta from a packfile to its base object.
+fn apply_delta(base: &[u8], delta: &[u8]) -> Option<Vec<u8>> {
+    let (base_size, mut pos) = read_size(delta)?;
+    if base_size != base.len() {
+        return None
+    }
+    let (result_size, used) = read_size(&delta[pos..])?;
+    pos += used;
+    let mut result = Vec::new();
+    while pos < delta.len() {
+        let op = delta[pos];
+        pos += 1;
+        if op & 0x80 != 0 {
+            //copy a range of the base, with each byte of the offset
+            //and size only present if its bit is set
+            let mut offset = 0;
+            let mut size = 0;
+            for i in 0..7 {
+                if op & (1 << i) != 0 {
+                    let byte = *delta.get(pos)? as usize;
+                    pos += 1;
+                    if i < 4 {
+                        offset |= byte << (8 * i);
+                    } else {
+                        size |= byte << (8 * (i - 4));
+                    }
+                }
+            }
+            if size == 0 {
+                size = 0x10000;
+            }
+            result.extend_from_slice(base.get(offset..offset + size)?);
+        } else if op != 0 {
+            //insert the next bytes of the delta
+            let len = op as usize;
+            result.extend_from_slice(delta.get(pos..pos + len)?);
+            pos += len;
+        } else {
+            return None
+        }
+    }
+    if result.len() != result_size {
+        return None
+    }
+    Some(result)
+}
+
+/// Reads a little-endian base 128 size, as used in deltas.
+fn read_size(data: &[u8]) -> Option<(usize, usize)> {
+    let mut size = 0;
+    let mut shift = 0;
+    for (i, byte) in data.iter().enumerate() {
+        size |= ((byte & 0x7f) as usize) << shift;
+        shift += 7;
+        if byte & 0x80 == 0 {
+            return Some((size, i + 1))
+        }
+    }
+    None
+}
+
+/// Reads the big-endian base 128 number git uses for delta offsets
+/// and index paths, where each continuation also adds one.
+fn read_offset(data: &[u8]) -> Option<(u64, usize)> {
+    let mut bytes = data.iter();
+    let mut byte = *bytes.next()?;
+    let mut value = (byte & 0x7f) as u64;
+    let mut used = 1;
+    while byte & 0x80 != 0 {
+        byte = *bytes.next()?;
+        value = ((value + 1) << 7) | (byte & 0x7f) as u64;
+        used += 1;
+    }
+    Some((value, used))
+}
+
+fn object_hasher(kind: Kind, size: u64) -> Sha1 {
+    let name = match kind {
+        Kind::Commit => "commit",
+        Kind::Tree => "tree",
+        Kind::Blob => "blob",
+        Kind::Tag => "tag",
+    };
+    let mut hasher = Sha1::new();
+    hasher.update(format!("{} {}\0", name, size));
+    hasher
+}
+
+fn hash_object(kind: Kind, contents: &[u8]) -> Oid {
+    let mut hasher = object_hasher(kind, contents.len() as u64);
+    hasher.update(contents);
+    hasher.finalize().into()
+}
+
+/// Hashes the file at `path` as a blob of `size` bytes, a chunk at a time,
+/// giving up once `deadline` has passed.
+fn hash_file(path: &Path, size: u64, deadline: Instant) -> Option<Oid> {
+    let mut file = File::open(path).ok()?;
+    let mut hasher = object_hasher(Kind::Blob, size);
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        if Instant::now() > deadline {
+            return None
+        }
+        match file.read(&mut buf) {
+            Ok(0) => break,
+            Ok(read) => hasher.update(&buf[..read]),
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(_) => return None,
+        }
+    }
+    Some(hasher.finalize().into())
+}
+
+fn be_u32(bytes: &[u8]) -> u32 {
+    u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}
+
+fn be_u16(bytes: &[u8]) -> u16 {
+    u16::from_be_bytes([bytes[0], bytes[1]])
+}
+
+fn from_hex(hex: &str) -> Option<Oid> {
+    if hex.len() != 40 {
+        return None
+    }
+    let mut oid = [0; 20];
+    for (i, byte) in oid.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(hex.get(2 * i..2 * i + 2)?, 16).ok()?;
+    }
+    Some(oid)
+}
+
+fn to_hex(oid: &Oid) -> String {
+    oid.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    fn git(dir: &Path, args: &[&str]) {
+        let status = Command::new("git")
+            .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+            .args(args)
+            .current_dir(dir)
+            .output()
+            .unwrap()
+            .status;
+        assert!(status.success(), "git {:?} failed", args);
+    }
+
+    fn temp_repo(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(
+            format!("oyster-gittest-{}-{}", name, std::process::id())
+        );
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        git(&dir, &["init", "-q", "-b", "main"]);
+        dir
+    }
+
+    fn commit(dir: &Path, file: &str, contents: &str) {
+        fs::write(dir.join(file), contents).unwrap();
+        git(dir, &["add", file]);
+        git(dir, &["commit", "-q", "-m", file]);
+    }
+
+    fn far_future() -> Instant {
+        Instant::now() + Duration::from_secs(60)
+    }
+
+    #[test]
+    fn check_object_hashes() {
+        assert_eq!(
+            to_hex(&hash_object(Kind::Blob, b"hello\n")),
+            "ce013625030ba8dba906f756967f9e9ca394464a"
+        );
+        let dir = temp_repo("hash");
+        let path = dir.join("big");
+        let contents = vec![b'x'; 200 * 1024];
+        let started = Instant::now();
+        fs::write(&path, &contents).unwrap();
+        assert_eq!(
+            hash_file(&path, contents.len() as u64, far_future()),
+            Some(hash_object(Kind::Blob, &contents))
+        );
+        assert_eq!(hash_file(&path, contents.len() as u64, started), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn check_git_status() {
+        let dir = temp_repo("status");
+        let mut repo = Repo::discover(&dir).unwrap();
+        assert_eq!(repo.head(), Some(Head::Branch(String::from("main"))));
+        assert_eq!(repo.is_dirty(far_future()), Some(false));
+
+        commit(&dir, "a.txt", "one\n");
+        fs::create_dir(dir.join("sub")).unwrap();
+        commit(&dir, "sub/b.txt", "two\n");
+        let mut repo = Repo::discover(&dir.join("sub")).unwrap();
+        assert_eq!(repo.workdir, dir);
+        assert_eq!(repo.is_dirty(far_future()), Some(false));
+
+        // unstaged, then staged changes
+        fs::write(dir.join("a.txt"), "three\n").unwrap();
+        assert_eq!(repo.is_dirty(far_future()), Some(true));
+        git(&dir, &["add", "a.txt"]);
+        assert_eq!(repo.is_dirty(far_future()), Some(true));
+        git(&dir, &["stash", "-q"]);
+        assert_eq!(repo.is_dirty(far_future()), Some(false));
+        assert_eq!(repo.stash_count(), 1);
+
+        // runs out of time
+        fs::write(dir.join("a.txt"), "four\n").unwrap();
+        assert_eq!(repo.is_dirty(Instant::now() - Duration::from_secs(1)), None);
+
+        git(&dir, &["checkout", "-q", "--detach"]);
+        let head = repo.resolve("HEAD").unwrap();
+        assert_eq!(repo.head(), Some(Head::Detached(head)));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn check_ahead_behind() {
+        let dir = temp_repo("upstream");
+        commit(&dir, "a.txt", "one\n");
+        git(&dir, &["branch", "feature"]);
+        git(&dir, &["branch", "-u", "main", "feature"]);
+        git(&dir, &["checkout", "-q", "feature"]);
+        let mut repo = Repo::discover(&dir).unwrap();
+        assert_eq!(repo.ahead_behind(far_future()), Some((0, 0)));
+
+        commit(&dir, "b.txt", "two\n");
+        commit(&dir, "c.txt", "three\n");
+        git(&dir, &["checkout", "-q", "main"]);
+        commit(&dir, "d.txt", "four\n");
+        git(&dir, &["checkout", "-q", "feature"]);
+        assert_eq!(repo.ahead_behind(far_future()), Some((2, 1)));
+
+        // packed objects and refs are read the same
+        git(&dir, &["gc", "-q", "--aggressive"]);
+        let mut repo = Repo::discover(&dir).unwrap();
+        assert_eq!(repo.ahead_behind(far_future()), Some((2, 1)));
+        assert_eq!(repo.is_dirty(far_future()), Some(false));
+
+        // a linked worktree shares refs but has its own HEAD
+        let worktree = dir.with_extension("worktree");
+        let _ = fs::remove_dir_all(&worktree);
+        git(&dir, &["worktree", "add", "-q", worktree.to_str().unwrap(), "main"]);
+        let mut repo = Repo::discover(&worktree).unwrap();
+        assert_eq!(repo.head(), Some(Head::Branch(String::from("main"))));
+        assert_eq!(repo.is_dirty(far_future()), Some(false));
+        assert_eq!(repo.ahead_behind(far_future()), None);
+        fs::remove_dir_all(&worktree).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
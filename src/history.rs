@@ -0,0 +1,313 @@
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use regex::Regex;
+
+pub const HISTSIZE_DFL: usize = 1000;
+
+/// A single line of history, as recorded after it was executed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistEntry {
+    pub timestamp: u64,
+    pub status: i32,
+    pub cmd: String,
+}
+
+impl HistEntry {
+    /// Encodes the entry as a single line of the history file.
+    ///
+    /// The format is `: <timestamp>:<status>;<command>`, with newlines
+    /// and backslashes in the command escaped.
+    fn to_line(&self) -> String {
+        let cmd = self.cmd.replace('\\', "\\\\").replace('\n', "\\n");
+        format!(": {}:{};{}", self.timestamp, self.status, cmd)
+    }
+
+    /// Decodes a line from the history file.
+    /// Lines without the metadata header are read in as plain commands.
+    fn from_line(line: &str) -> Option<Self> {
+        if line.is_empty() {
+            return None
+        }
+        if let Some(rest) = line.strip_prefix(": ") {
+            if let Some((meta, cmd)) = rest.split_once(';') {
+                if let Some((ts, status)) = meta.split_once(':') {
+                    if let (Ok(ts), Ok(status)) = (ts.parse(), status.parse()) {
+                        return Some(HistEntry {
+                            timestamp: ts,
+                            status,
+                            cmd: unescape(cmd),
+                        })
+                    }
+                }
+            }
+        }
+        Some(HistEntry {
+            timestamp: 0,
+            status: 0,
+            cmd: line.to_string(),
+        })
+    }
+}
+
+fn unescape(string: &str) -> String {
+    let mut unescaped = String::new();
+    let mut chars = string.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' {
+            match chars.next() {
+                Some('n') => unescaped.push('\n'),
+                Some(c) => unescaped.push(c),
+                None => unescaped.push('\\'),
+            }
+        } else {
+            unescaped.push(c);
+        }
+    }
+    unescaped
+}
+
+/// The shell's command history.
+///
+/// Entries are deduplicated (re-running a command moves it to the end)
+/// and capped at `max_size`. If a history file is set, each new entry
+/// is appended to it as it is added, and the file is compacted on load.
+#[derive(Debug, Clone)]
+pub struct History {
+    entries: Vec<HistEntry>,
+    file: Option<PathBuf>,
+    max_size: usize,
+    /// Set when entries are changed other than by appending,
+    /// so the line editor knows to reload its own copy.
+    dirty: bool,
+}
+
+impl History {
+    /// Creates an in-memory history that is not backed by a file.
+    pub fn new() -> Self {
+        History {
+            entries: Vec::new(),
+            file: None,
+            max_size: HISTSIZE_DFL,
+            dirty: false,
+        }
+    }
+
+    /// Creates a history backed by the file named in `$OYSTER_HISTFILE`,
+    /// or `~/.oyster_history` if it is not set.
+    /// The size limit is read from `$OYSTER_HISTSIZE`.
+    pub fn from_env() -> Self {
+        let file = match env::var("OYSTER_HISTFILE") {
+            Ok(path) if !path.is_empty() => Some(PathBuf::from(path)),
+            _ => env::var("HOME").ok().map(|home| {
+                PathBuf::from(home).join(".oyster_history")
+            }),
+        };
+        let max_size = env::var("OYSTER_HISTSIZE").ok()
+            .and_then(|size| size.parse().ok())
+            .unwrap_or(HISTSIZE_DFL);
+        History {
+            entries: Vec::new(),
+            file,
+            max_size,
+            dirty: false,
+        }
+    }
+
+    /// Reads in the history file, deduplicating and truncating it.
+    /// The file is rewritten if anything had to be removed.
+    pub fn load(&mut self) -> io::Result<()> {
+        let path = match &self.file {
+            Some(path) => path.clone(),
+            None => return Ok(())
+        };
+        if !path.exists() {
+            return Ok(())
+        }
+        let mut read = 0;
+        for line in BufReader::new(File::open(&path)?).lines() {
+            if let Some(entry) = HistEntry::from_line(&line?) {
+                self.entries.retain(|e| e.cmd != entry.cmd);
+                self.entries.push(entry);
+                read += 1;
+            }
+        }
+        if self.entries.len() > self.max_size {
+            let excess = self.entries.len() - self.max_size;
+            self.entries.drain(..excess);
+        }
+        if read != self.entries.len() {
+            self.rewrite()?;
+        }
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Records a command and its exit status.
+    pub fn push(&mut self, cmd: &str, status: i32) {
+        let cmd = cmd.trim_end();
+        if cmd.trim().is_empty() || self.max_size == 0 {
+            return
+        }
+        let len = self.entries.len();
+        self.entries.retain(|e| e.cmd != cmd);
+        if self.entries.len() != len {
+            self.dirty = true;
+        }
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)
+            .map(|time| time.as_secs())
+            .unwrap_or(0);
+        let entry = HistEntry {
+            timestamp,
+            status,
+            cmd: cmd.to_string(),
+        };
+        if let Err(e) = self.append_to_file(&entry) {
+            eprintln!("oyster: could not write history: {}", e);
+        }
+        self.entries.push(entry);
+        if self.entries.len() > self.max_size {
+            self.entries.remove(0);
+            self.dirty = true;
+        }
+    }
+
+    /// Removes every entry, truncating the history file as well.
+    pub fn clear(&mut self) -> io::Result<()> {
+        self.entries.clear();
+        self.dirty = true;
+        self.rewrite()
+    }
+
+    /// Removes the entry with the given (1-indexed) history number.
+    pub fn remove(&mut self, idx: usize) -> io::Result<Option<HistEntry>> {
+        if idx == 0 || idx > self.entries.len() {
+            return Ok(None)
+        }
+        let entry = self.entries.remove(idx - 1);
+        self.dirty = true;
+        self.rewrite()?;
+        Ok(Some(entry))
+    }
+
+    pub fn entries(&self) -> &[HistEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn max_size(&self) -> usize {
+        self.max_size
+    }
+
+    /// Returns all entries matching the regex, with their history numbers.
+    pub fn search(&self, pattern: &Regex) -> Vec<(usize, &HistEntry)> {
+        self.entries.iter().enumerate()
+            .filter(|(_, entry)| pattern.is_match(&entry.cmd))
+            .map(|(i, entry)| (i + 1, entry))
+            .collect()
+    }
+
+    /// Returns whether entries were removed or reordered since the last call.
+    pub fn take_dirty(&mut self) -> bool {
+        let dirty = self.dirty;
+        self.dirty = false;
+        dirty
+    }
+
+    fn append_to_file(&self, entry: &HistEntry) -> io::Result<()> {
+        if let Some(path) = &self.file {
+            let mut file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)?;
+            writeln!(file, "{}", entry.to_line())?;
+        }
+        Ok(())
+    }
+
+    fn rewrite(&self) -> io::Result<()> {
+        if let Some(path) = &self.file {
+            let mut file = File::create(path)?;
+            for entry in &self.entries {
+                writeln!(file, "{}", entry.to_line())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Formats a unix timestamp as local time, e.g. `2020-11-08 14:10:46`.
+pub fn format_timestamp(timestamp: u64) -> String {
+    let time = timestamp as libc::time_t;
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&time, &mut tm) }.is_null() {
+        return timestamp.to_string()
+    }
+    format!("{:04}-{:02}-{:02} {:02}:{:02}:{:02}",
+        tm.tm_year + 1900, tm.tm_mon + 1, tm.tm_mday,
+        tm.tm_hour, tm.tm_min, tm.tm_sec,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_histfile(name: &str) -> PathBuf {
+        let path = env::temp_dir().join(
+            format!("oyster-histtest-{}-{}", name, std::process::id())
+        );
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn check_history_dedup_and_size() {
+        let mut history = History::new();
+        history.max_size = 3;
+        history.push("ls", 0);
+        history.push("cd ..", 0);
+        history.push("ls", 1);
+        assert_eq!(
+            history.entries().iter().map(|e| e.cmd.as_str()).collect::<Vec<_>>(),
+            vec!["cd ..", "ls"]
+        );
+        assert_eq!(history.entries()[1].status, 1);
+        history.push("pwd", 0);
+        history.push("whoami", 0);
+        assert_eq!(history.len(), 3);
+        assert_eq!(history.entries()[0].cmd, "ls");
+    }
+
+    #[test]
+    fn check_history_file_roundtrip() {
+        let path = temp_histfile("roundtrip");
+        let mut history = History::new();
+        history.file = Some(path.clone());
+        history.push("echo hello", 0);
+        history.push("for i in [1..3]\necho \\$i\ndone", 2);
+        history.push("echo hello", 0);
+
+        let mut loaded = History::new();
+        loaded.file = Some(path.clone());
+        loaded.load().unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded.entries()[0].cmd, "for i in [1..3]\necho \\$i\ndone");
+        assert_eq!(loaded.entries()[0].status, 2);
+        assert_eq!(loaded.entries()[1].cmd, "echo hello");
+        // the duplicate should have been compacted out of the file
+        let lines = std::fs::read_to_string(&path).unwrap().lines().count();
+        assert_eq!(lines, 2);
+
+        loaded.remove(1).unwrap();
+        loaded.clear().unwrap();
+        assert!(std::fs::read_to_string(&path).unwrap().is_empty());
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -1,7 +1,11 @@
-use nix::unistd::{Pid, write};
+use std::os::unix::io::RawFd;
+use std::sync::atomic::{AtomicI32, Ordering};
+
+use nix::unistd::{Pid, isatty, pipe2, read, write};
+use nix::fcntl::OFlag;
 use nix::sys::{
     wait::{waitpid, WaitPidFlag, WaitStatus},
-    signal::Signal,
+    signal::{sigaction, SaFlags, SigAction, SigHandler, SigSet, Signal},
 };
 use nix::Error;
 use nix::errno::Errno;
@@ -10,12 +14,117 @@ use crate::types::{
     JobStatus,
     JobTrack,
     STOPPED,
+    ShellError,
+    pipeline_status,
 };
 use crate::shell::Shell;
 
+/// The read and write ends of the pipe that `sigchld_handler` writes to,
+/// so that the shell can tell when its children have changed state.
+static SIGCHLD_PIPE: [AtomicI32; 2] = [AtomicI32::new(-1), AtomicI32::new(-1)];
+
+/// Creates the SIGCHLD self-pipe and installs `sigchld_handler`.
+pub fn init_sigchld() -> nix::Result<()> {
+    let (read_end, write_end) = pipe2(OFlag::O_CLOEXEC | OFlag::O_NONBLOCK)?;
+    SIGCHLD_PIPE[0].store(read_end, Ordering::SeqCst);
+    SIGCHLD_PIPE[1].store(write_end, Ordering::SeqCst);
+    //restart interrupted system calls, so that the shell's own blocking
+    //reads and waits are not cut short by its background jobs
+    let action = SigAction::new(
+        SigHandler::Handler(sigchld_handler),
+        SaFlags::SA_RESTART,
+        SigSet::empty(),
+    );
+    unsafe {
+        sigaction(Signal::SIGCHLD, &action)?;
+    }
+    Ok(())
+}
+
+extern "C" fn sigchld_handler(_: libc::c_int) {
+    //if the pipe is full, there is already a wakeup pending
+    let _ = write(SIGCHLD_PIPE[1].load(Ordering::SeqCst), &[0]);
+}
+
+/// Returns the end of the SIGCHLD self-pipe that becomes readable
+/// when a child changes state.
+pub fn sigchld_fd() -> RawFd {
+    SIGCHLD_PIPE[0].load(Ordering::SeqCst)
+}
+
+/// Returns true if SIGCHLD was received since the last call,
+/// emptying the self-pipe.
+pub fn sigchld_received() -> bool {
+    let mut buf = [0; 64];
+    let mut received = false;
+    while let Ok(count) = read(sigchld_fd(), &mut buf) {
+        if count == 0 {
+            break
+        }
+        received = true;
+    }
+    received
+}
+
+/// Collects every change in state of the shell's children without blocking,
+/// and updates the job table to match. Returns the jobs that finished,
+/// were stopped or were continued, to be reported to the user.
+///
+/// Only called when no job is running in the foreground,
+/// whose processes are waited on individually.
+pub fn update_jobs(shell: &mut Shell) -> Vec<JobTrack> {
+    let mut changed = Vec::new();
+    let flags = WaitPidFlag::WNOHANG 
+        | WaitPidFlag::WUNTRACED 
+        | WaitPidFlag::WCONTINUED;
+    loop {
+        let (pid, status) = match waitpid(Pid::from_raw(-1), Some(flags)) {
+            Ok(WaitStatus::Exited(pid, code)) => (pid, JobStatus::Completed(code)),
+            Ok(WaitStatus::Signaled(pid, signal, _)) => (pid, JobStatus::Signaled(signal)),
+            Ok(WaitStatus::Stopped(pid, _)) => (pid, JobStatus::Stopped),
+            Ok(WaitStatus::Continued(pid)) => (pid, JobStatus::Running),
+            //no more children have changed state, or there are no children
+            _ => break,
+        };
+        //disowned jobs are reaped here too, but not tracked
+        let (id, old_status) = match shell.jobs.values()
+            .find(|job| job.pids.contains(&pid)) {
+            Some(job) => (job.id, job.status.clone()),
+            None => continue,
+        };
+        match status {
+            JobStatus::Stopped if old_status != JobStatus::Stopped => {
+                shell.mark_job_as_stopped(id);
+            }
+            JobStatus::Running if old_status == JobStatus::Stopped => {
+                shell.mark_job_as_running(id, true);
+            }
+            JobStatus::Completed(_) | JobStatus::Signaled(_) => {
+                if let Some(job) = finish_process(shell, pid, status) {
+                    changed.push(job);
+                }
+                continue
+            }
+            _ => continue,
+        }
+        if let Some(job) = shell.get_job_by_id(id) {
+            changed.push(job.clone());
+        }
+    }
+    changed
+}
+
+/// Formats a job as `[id] pgid command status`.
+pub fn format_job(job: &JobTrack) -> String {
+    format!("[{}] {} {} {}", job.id, job.pgid, job.firstcmd, job.status)
+}
+
 pub fn print_job(job: &JobTrack) {
-    let to_print = format!("\n[{}] {} {} {}\n",
-    job.id, job.pgid, job.firstcmd, job.status);
+    //scripts run without job notifications
+    if !isatty(1).unwrap_or(false) {
+        return;
+    }
+    let to_print = format!("\n{}\n", format_job(job));
     if job.background {
         write(1, to_print.as_bytes()).unwrap();
     } else if job.status == JobStatus::Signaled(Signal::SIGSEGV) {
@@ -25,6 +134,21 @@ pub fn print_job(job: &JobTrack) {
     }
 }
 
+/// Finds the job given to a job control builtin, as either a job spec
+/// or a job id. With no argument, this is the current job.
+pub fn resolve_job(shell: &Shell, arg: Option<&str>) -> Result<i32, ShellError> {
+    match arg {
+        None => shell.current_job()
+            .ok_or_else(|| ShellError::from("no current job")),
+        Some(spec) if spec.starts_with('%') => shell.find_job(spec),
+        Some(id) => match id.parse::<i32>() {
+            Ok(num) if shell.jobs.contains_key(&num) => Ok(num),
+            Ok(_) => Err(ShellError::from(format!("{}: no such job", id))),
+            Err(_) => Err(ShellError::from(format!("{}: not a job id", id))),
+        }
+    }
+}
+
 pub fn mark_job_as_stopped(shell: &mut Shell, id: i32) {
     shell.mark_job_as_stopped(id);
     if let Some(job) = shell.get_job_by_id(id) {
@@ -39,32 +163,55 @@ pub fn mark_job_as_running(shell: &mut Shell, id: i32, bg: bool) {
     }
 }
 
+/// Records a process as finished. If it was the last process
+/// in its job, returns the job with its final status.
+fn finish_process(shell: &mut Shell, pid: Pid, status: JobStatus) -> Option<JobTrack> {
+    let code = match status {
+        JobStatus::Completed(code) => code,
+        JobStatus::Signaled(signal) => 128 + signal as i32,
+        _ => 0,
+    };
+    let mut job = shell.finish_process(pid, code)?;
+    job.status = match status {
+        JobStatus::Completed(_) => {
+            JobStatus::Completed(job.exit_status(shell.options.pipefail))
+        }
+        status => status,
+    };
+    Some(job)
+}
+
 pub fn cleanup_process(
     shell: &mut Shell, 
     pid: Pid, 
-    pgid: Pid,
     status: JobStatus) {
-    if let Some(mut job) = shell.remove_pid_from_job(pid, pgid) {
-        job.status = status;
+    if let Some(job) = finish_process(shell, pid, status) {
         print_job(&job);
     }
 }
 
-pub fn try_wait_bg_jobs(shell: &mut Shell) {
-    if shell.jobs.is_empty() {
-        return;
-    }
-    let jobs = shell.jobs.clone();
-    for (_i, job) in jobs.iter() {
-        for pid in job.pids.iter() {
-            wait_on_job(shell, job.pgid, *pid, false);
+/// Waits for every unfinished process in a job, returning the exit
+/// status of the job. If the job is stopped, returns `STOPPED`.
+pub fn wait_for_job(shell: &mut Shell, job: &JobTrack) -> i32 {
+    let mut statuses = Vec::new();
+    for (pid, status) in job.pids.iter().zip(&job.statuses) {
+        let status = match status {
+            Some(status) => *status,
+            None => wait_on_job(shell, *pid, true),
+        };
+        if status == STOPPED {
+            mark_job_as_stopped(shell, job.id);
+            return STOPPED;
         }
+        statuses.push(status);
     }
+    pipeline_status(&statuses, shell.options.pipefail)
 }
 
+/// Waits for a process, returning its exit status,
+/// or 128 plus the signal number if it was killed by a signal.
 pub fn wait_on_job(
     shell: &mut Shell, 
-    pgid: Pid, 
     pid: Pid, 
     stop: bool
 ) -> i32 {
@@ -79,15 +226,15 @@ pub fn wait_on_job(
         Ok(result) => {
             match result {
                 WaitStatus::Exited(pid, exitstat) => {
-                    cleanup_process(shell, pid, pgid, JobStatus::Completed(exitstat));
+                    cleanup_process(shell, pid, JobStatus::Completed(exitstat));
                     status = exitstat;
                 }
                 WaitStatus::Stopped(_pid, _signal) => {
                     status = STOPPED;
                 }
                 WaitStatus::Signaled(pid, signal, _cd) => {
-                    cleanup_process(shell, pid, pgid, JobStatus::Signaled(signal));
-                    status = signal as i32;
+                    cleanup_process(shell, pid, JobStatus::Signaled(signal));
+                    status = 128 + signal as i32;
                 }
                 _ => {
 
@@ -98,7 +245,7 @@ pub fn wait_on_job(
             match error {
                 Error::Sys(errno) => {
                     if errno == Errno::ECHILD {
-                        cleanup_process(shell, pid, pgid, JobStatus::Completed(status));
+                        cleanup_process(shell, pid, JobStatus::Completed(status));
                     }
                 }
                 _ => {
@@ -108,4 +255,4 @@ pub fn wait_on_job(
         }
     }
     status
-}
\ No newline at end of file
+}
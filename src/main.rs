@@ -9,16 +9,27 @@ mod builtins;
 mod scripting;
 mod expansion;
 mod completion;
+mod history;
+mod namespace;
+mod arithmetic;
+mod cli;
+mod git;
+mod style;
+mod terminal;
 
 #[macro_use]
 extern crate lazy_static;
 
 use std::error::Error;
 use std::env;
+use std::io;
+use std::path::Path;
 use std::process;
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use nix::sys::signal::{signal, Signal, SigHandler,};
+use nix::unistd::isatty;
 use linefeed::{
     Interface, ReadResult,
     terminal::Signal as TSignal,
@@ -33,71 +44,150 @@ use types::{
 use completion::OshComplete;
 use execute::*;
 use shell::Shell;
-use scripting::execute_scriptfile;
+use scripting::{execute_script, execute_scriptfile, StdinScript};
+use history::History;
+use cli::{Args, Input};
+use prompt::{PromptContext, PromptLine};
+use terminal::JobTerminal;
 
 fn main() -> Result<(), Box<dyn Error>> {
     unsafe {
         signal(Signal::SIGINT, SigHandler::SigIgn)?;
         signal(Signal::SIGQUIT, SigHandler::SigIgn)?;
         signal(Signal::SIGTSTP, SigHandler::SigDfl)?;
-        signal(Signal::SIGCHLD, SigHandler::Handler(sigchld_handler))?;
     }
+    jobc::init_sigchld()?;
 
-    let mut shell = Shell::with_config("testconfig");
+    let args = match Args::parse(env::args().collect(), isatty(0).unwrap_or(false)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("oyster: {}", e);
+            eprintln!("{}", cli::USAGE);
+            process::exit(2);
+        }
+    };
+    if args.version {
+        println!("oyster {}", env!("CARGO_PKG_VERSION"));
+        return Ok(());
+    }
+
+    //a nested shell shows up as a higher level
+    let shlvl = env::var("SHLVL").ok()
+        .and_then(|level| level.parse::<u32>().ok())
+        .unwrap_or(0);
+    env::set_var("SHLVL", (shlvl + 1).to_string());
+
+    let mut shell = Shell::from_env();
     let mut last_status: i32 = 0;
+    let mut last_duration: Option<Duration> = None;
+    shell.is_login = args.login;
+    shell.options = args.options;
+    shell.add_variable("0", Variable::from(&args.name));
 
-    let args: Vec<String> = env::args().collect();
-    if args.len() > 0 && args[0].starts_with('-') {
-        shell.is_login = true;
+    if shell.is_login {
+        for profile in cli::profile_files() {
+            if profile.is_file() {
+                run_startup_file(&mut shell, &profile);
+            }
+        }
     }
-    if args.len() >= 3 {
-        let mut counter = 0;
-        for arg in &args[2..] {
-            let varname = format!("{}", counter);
-            shell.add_variable(&varname, Variable::from(arg));
-            counter += 1;
+    if args.input == Input::Interactive && !args.norc {
+        match args.rcfile {
+            Some(rcfile) if !rcfile.is_file() => {
+                eprintln!("oyster: {}: no such file", rcfile.display());
+            }
+            Some(rcfile) => run_startup_file(&mut shell, &rcfile),
+            None => {
+                if let Some(rcfile) = cli::rc_file().filter(|rc| rc.is_file()) {
+                    run_startup_file(&mut shell, &rcfile);
+                }
+            }
         }
     }
-    if args.len() > 1 {
-        if args[1] == "-i" {
 
-        } else {
-            let status = match execute_scriptfile(&mut shell, &args[1]) {
-                Ok(result) => result,
-                Err(e) => {
-                    eprintln!("{}", e);
-                    process::exit(10);
-                }
-            };
-            process::exit(status)
+    let result = match args.input {
+        Input::Interactive => None,
+        Input::Command(cmd) => {
+            Some(execute_script(&mut shell, cmd.as_bytes(), args.params))
+        }
+        Input::Stdin => {
+            Some(execute_script(&mut shell, StdinScript::new(), args.params))
         }
+        Input::File(file) => {
+            if !Path::new(&file).is_file() {
+                eprintln!("oyster: {}: no such file", file);
+                process::exit(127);
+            }
+            Some(execute_scriptfile(&mut shell, &file, args.params))
+        }
+    };
+    match result {
+        Some(Ok(status)) => process::exit(status),
+        Some(Err(e)) => {
+            eprintln!("{}", e);
+            process::exit(1);
+        }
+        None => {}
     }
-    let lr = Interface::new("oyster")?;
+    shell.history = History::from_env();
+    if let Err(e) = shell.history.load() {
+        eprintln!("oyster: could not read history file: {}", e);
+    }
+    let lr = Interface::with_term("oyster", JobTerminal::new()?)?;
     lr.set_completer(Arc::new(OshComplete{}));
+    lr.set_history_size(shell.history.max_size());
     
     // main: main loop (get command, execute, repeat)
     'main: loop {
-        jobc::try_wait_bg_jobs(&mut shell);
-        let prompt = prompt::render_prompt(last_status);
-        match lr.set_prompt(&prompt) {
-            Ok(()) => {},
-            Err(_) => {
-                eprintln!("oyster: could not set prompt")
+        jobc::sigchld_received();
+        for job in jobc::update_jobs(&mut shell) {
+            println!("{}", jobc::format_job(&job));
+        }
+        if shell.history.take_dirty() {
+            lr.clear_history();
+            for entry in shell.history.entries() {
+                lr.add_history(entry.cmd.clone());
             }
         }
+        let mut ctx = PromptContext::new(&mut shell, last_status);
+        ctx.duration = last_duration.take();
+        let mut prompt = PromptLine::new(
+            prompt::render_prompt(&mut ctx),
+            prompt::render_rprompt(&mut ctx),
+        );
+        let transient = prompt::render_transient(&mut ctx);
         let mut buffer = String::new();
 
-        match lr.read_line() {
+        match read_line(&mut shell, &lr, &mut prompt) {
             Ok(ReadResult::Input(line)) => {
+                if let Some(transient) = &transient {
+                    if let Err(e) = prompt.redraw_transient(transient, &line) {
+                        eprintln!("oyster: could not redraw prompt: {}", e);
+                    }
+                }
+                let line = match expansion::expand_history(&shell, &line) {
+                    Ok(expanded) => {
+                        if expanded != line {
+                            println!("{}", expanded);
+                        }
+                        expanded
+                    }
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        lr.add_history_unique(line);
+                        last_status = 1;
+                        continue 'main;
+                    }
+                };
                 buffer.push_str(&line);
                 buffer.push('\n');
             }
             Ok(ReadResult::Eof) => {
-                process::exit(100);
+                exit_on_eof(last_status)
             }
             Ok(ReadResult::Signal(signal)) => {
                 if let TSignal::Interrupt = signal {
-                    last_status = 20;
+                    last_status = 130;
                     continue 'main;
                 }
             }
@@ -119,43 +209,53 @@ fn main() -> Result<(), Box<dyn Error>> {
                         Ok(result) => result,
                         Err(e) => {
                             eprintln!("{}", e);
+                            if let Some(underline) = e.span()
+                                .and_then(|span| span.underline(&buffer)) {
+                                eprintln!("{}", underline);
+                            }
                             last_status = 2;
+                            record_history(&mut shell, &lr, &buffer, last_status);
                             buffer.clear();
                             break 'complete
                         }
                     };
                     match jobs {
                         ParseResult::Good(jobs) => {
+                            let start = Instant::now();
                             match execute_jobs(&mut shell, jobs, false) {
                                 Ok(result) => {
                                     last_status = result.0;
                                 }
                                 Err(e) => {
                                     eprintln!("{}", e.to_string());
-                                    last_status = 10;
+                                    last_status = 1;
                                 }
                             }
+                            last_duration = Some(start.elapsed());
+                            record_history(&mut shell, &lr, &buffer, last_status);
                             buffer.clear();
                             break 'complete
                         }
                         n@ _ => {
-                            match lr.set_prompt(&n.to_string()) {
-                                Ok(_) => {}
-                                Err(_) => {
-                                    eprintln!("oyster: could not set prompt")
-                                }
-                            } 
-                            match lr.read_line() {
+                            let depth = shell.parse_depth;
+                            let mut ctx = PromptContext::new(&mut shell, last_status);
+                            ctx.construct = Some(&n);
+                            ctx.depth = depth;
+                            let mut prompt = PromptLine::new(
+                                prompt::render_continuation(&mut ctx, &n.to_string()),
+                                None,
+                            );
+                            match read_line(&mut shell, &lr, &mut prompt) {
                                 Ok(ReadResult::Input(line)) => {
                                     buffer.push_str(&line);
                                     buffer.push('\n');
                                 }
                                 Ok(ReadResult::Eof) => {
-                                    process::exit(100);
+                                    exit_on_eof(last_status)
                                 }
                                 Ok(ReadResult::Signal(signal)) => {
                                     if let TSignal::Interrupt = signal {
-                                        last_status = 20;
+                                        last_status = 130;
                                         break 'complete
                                     }
                                 }
@@ -168,23 +268,24 @@ fn main() -> Result<(), Box<dyn Error>> {
                     }
                 }
                 n@ _ => {
-                    match lr.set_prompt(&n.to_string()) {
-                        Ok(_) => {}
-                        Err(_) => {
-                            eprintln!("oyster: could not set prompt")
-                        }
-                    } 
-                    match lr.read_line() {
+                    let mut prompt = PromptLine::new(
+                        prompt::render_continuation(
+                            &mut PromptContext::new(&mut shell, last_status),
+                            &n.to_string(),
+                        ),
+                        None,
+                    );
+                    match read_line(&mut shell, &lr, &mut prompt) {
                         Ok(ReadResult::Input(line)) => {
                             buffer.push_str(&line);
                             buffer.push('\n');
                         }
                         Ok(ReadResult::Eof) => {
-                            process::exit(100)
+                            exit_on_eof(last_status)
                         }
                         Ok(ReadResult::Signal(signal)) => {
                             if let TSignal::Interrupt = signal {
-                                last_status = 20;
+                                last_status = 130;
                                 break 'complete
                             }
                         }
@@ -199,6 +300,65 @@ fn main() -> Result<(), Box<dyn Error>> {
     }
 }
 
-extern "C" fn sigchld_handler(_: libc::c_int) {
-    //do something with this? idk
+/// Leaves the shell at the end of its input, with the status
+/// of the last command, as sh does.
+fn exit_on_eof(last_status: i32) -> ! {
+    process::exit(last_status)
+}
+
+/// Runs a startup file such as the rc file, reporting any errors.
+fn run_startup_file(shell: &mut Shell, path: &Path) {
+    match execute_scriptfile(shell, &path.to_string_lossy(), Vec::new()) {
+        Ok(0) => {}
+        Ok(_) => {
+            eprintln!("oyster: error occurred while running {}", path.display());
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            eprintln!("oyster: could not run {}", path.display());
+        }
+    }
+}
+
+/// Saves a completed command to the shell history and the line editor.
+fn record_history(
+    shell: &mut Shell,
+    lr: &Interface<JobTerminal>,
+    line: &str,
+    status: i32,
+) {
+    shell.history.push(line, status);
+    lr.add_history_unique(line.trim_end().to_string());
+}
+
+/// Reads a line of input, printing jobs that change state
+/// above the prompt while the user is typing.
+fn read_line(
+    shell: &mut Shell,
+    lr: &Interface<JobTerminal>,
+    prompt: &mut PromptLine,
+) -> io::Result<ReadResult> {
+    prompt.update("", prompt::term_width());
+    if lr.set_prompt(&prompt.text()).is_err() {
+        eprintln!("oyster: could not set prompt")
+    }
+    loop {
+        if let Some(result) = lr.read_line_step(None)? {
+            return Ok(result);
+        }
+        //the right prompt is hidden once the input reaches it
+        if prompt.update(&lr.buffer(), prompt::term_width()) {
+            lr.set_prompt(&prompt.text())?;
+        }
+        if !jobc::sigchld_received() {
+            continue
+        }
+        let jobs = jobc::update_jobs(shell);
+        if !jobs.is_empty() {
+            let mut writer = lr.lock_writer_erase()?;
+            for job in jobs {
+                writeln!(writer, "{}", jobc::format_job(&job))?;
+            }
+        }
+    }
 }
@@ -0,0 +1,251 @@
+use std::collections::HashMap;
+use std::env;
+
+use crate::shell::Shell;
+use crate::core;
+use crate::expansion::index_into;
+use crate::types::{ShellError, Variable};
+
+/// A builtin namespace takes the arguments after its name and returns a value.
+pub type NamespaceFn = fn(&mut Shell, &[Variable]) -> Result<Variable, ShellError>;
+
+/// A namespace that can be used in a namespaced expansion
+/// such as `@env:HOME` or `${color:red}`.
+#[derive(Clone)]
+pub enum Namespace {
+    /// A namespace implemented by the shell itself.
+    Builtin(NamespaceFn),
+    /// A user-defined namespace that calls a shell function with its
+    /// arguments, and expands to the function's output.
+    Func(String),
+}
+
+impl std::fmt::Debug for Namespace {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Namespace::Builtin(_) => write!(f, "Builtin"),
+            Namespace::Func(func) => write!(f, "Func({})", func),
+        }
+    }
+}
+
+/// The registry of all namespaces known to the shell.
+#[derive(Clone, Debug)]
+pub struct Namespaces {
+    inner: HashMap<String, Namespace>,
+}
+
+impl Namespaces {
+    /// Creates a registry containing the builtin namespaces.
+    pub fn new() -> Self {
+        let mut namespaces = Namespaces {
+            inner: HashMap::new(),
+        };
+        namespaces.register_builtin("env", env_ns);
+        namespaces.register_builtin("color", color_ns);
+        namespaces.register_builtin("c", color_ns);
+        namespaces.register_builtin("fn", fn_ns);
+        namespaces.register_builtin("arr", arr_ns);
+        namespaces
+    }
+
+    pub fn register_builtin(&mut self, name: &str, func: NamespaceFn) {
+        self.inner.insert(name.to_string(), Namespace::Builtin(func));
+    }
+
+    /// Registers a namespace backed by a shell function.
+    /// Builtin namespaces cannot be overridden.
+    pub fn register_func(&mut self, name: &str, func: &str) -> Result<(), ShellError> {
+        if let Some(Namespace::Builtin(_)) = self.inner.get(name) {
+            return Err(
+                ShellError::from(format!("cannot override builtin namespace {}", name))
+            )
+        }
+        self.inner.insert(name.to_string(), Namespace::Func(func.to_string()));
+        Ok(())
+    }
+
+    /// Removes a user-defined namespace.
+    pub fn remove(&mut self, name: &str) -> Result<Namespace, ShellError> {
+        match self.inner.get(name) {
+            Some(Namespace::Builtin(_)) => Err(
+                ShellError::from(format!("cannot remove builtin namespace {}", name))
+            ),
+            Some(_) => Ok(self.inner.remove(name).unwrap()),
+            None => Err(ShellError::from(format!("no namespace {} found", name))),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Namespace> {
+        self.inner.get(name)
+    }
+
+    /// Returns all namespaces, sorted by name.
+    pub fn list(&self) -> Vec<(&String, &Namespace)> {
+        let mut namespaces: Vec<(&String, &Namespace)> = self.inner.iter().collect();
+        namespaces.sort_by(|a, b| a.0.cmp(b.0));
+        namespaces
+    }
+}
+
+/// Performs a namespaced expansion.
+///
+/// `@ns:args` expands to a list of words, and `$ns:args`
+/// (written `${ns:args}`) expands to a single word.
+pub fn expand(shell: &mut Shell, string: &str) -> Result<Vec<String>, ShellError> {
+    let splat = string.starts_with('@');
+    let value = evaluate(shell, &string[1..])?;
+    if !splat {
+        return Ok(vec![value.to_string()])
+    }
+    match value {
+        Variable::Arr(arr) => Ok(arr.into_iter().map(|var| var.to_string()).collect()),
+        var => Ok(var.to_string().split_whitespace().map(|s| s.to_string()).collect()),
+    }
+}
+
+/// Looks up the namespace and calls it with the `:`-separated arguments.
+///
+/// Arguments starting with `$` are looked up as variables,
+/// so that arrays are passed in whole.
+pub fn evaluate(shell: &mut Shell, string: &str) -> Result<Variable, ShellError> {
+    let mut parts = string.split(':');
+    // split always returns at least one item
+    let name = parts.next().unwrap();
+    let mut args = Vec::new();
+    for part in parts {
+        if part.starts_with('$') && part.ends_with(']') {
+            args.push(index_into(shell, part)?);
+        } else if let Some(name) = part.strip_prefix('$') {
+            args.push(shell.get_variable(name).unwrap_or_else(|| Variable::from("")));
+        } else {
+            args.push(Variable::Str(part.to_string()));
+        }
+    }
+    match shell.namespaces.get(name).cloned() {
+        Some(Namespace::Builtin(func)) => func(shell, &args),
+        Some(Namespace::Func(func)) => call_func(shell, &func, &args),
+        None => Err(ShellError::from(format!("oyster: no namespace {} found", name))),
+    }
+}
+
+fn call_func(shell: &mut Shell, func: &str, args: &[Variable]) -> Result<Variable, ShellError> {
+    let args = args.iter().map(|arg| arg.to_string()).collect();
+    let (_, mut output) = core::capture_func(shell, func, args, None)?;
+    if output.ends_with('\n') {
+        output.pop();
+    }
+    Ok(Variable::Str(output))
+}
+
+/// `@env:NAME` - The value of an environment variable.
+fn env_ns(_shell: &mut Shell, args: &[Variable]) -> Result<Variable, ShellError> {
+    match args {
+        [name] => Ok(Variable::Str(env::var(name.to_string()).unwrap_or_default())),
+        _ => Err(ShellError::from("oyster: usage: @env:<name>")),
+    }
+}
+
+/// `@color:<style>:...` - ANSI escape codes for the given colours and styles.
+fn color_ns(_shell: &mut Shell, args: &[Variable]) -> Result<Variable, ShellError> {
+    if args.is_empty() {
+        return Err(ShellError::from("oyster: usage: @color:<style>[:<style>...]"))
+    }
+    let mut codes = String::new();
+    for arg in args {
+        let code = match arg.to_string().as_str() {
+            "reset" => "0",
+            "bold" => "1",
+            "dim" => "2",
+            "italic" => "3",
+            "underline" => "4",
+            "black" => "30",
+            "red" => "31",
+            "green" => "32",
+            "yellow" => "33",
+            "blue" => "34",
+            "magenta" => "35",
+            "cyan" => "36",
+            "white" => "97",
+            "default" => "39",
+            n => return Err(ShellError::from(format!("oyster: unknown color `{}`", n))),
+        };
+        codes.push_str(&format!("\x1b[{}m", code));
+    }
+    Ok(Variable::Str(codes))
+}
+
+/// `@fn:<name>:<args>...` - Calls a shell function and expands to its output.
+fn fn_ns(shell: &mut Shell, args: &[Variable]) -> Result<Variable, ShellError> {
+    match args.split_first() {
+        Some((name, args)) => call_func(shell, &name.to_string(), args),
+        None => Err(ShellError::from("oyster: usage: @fn:<name>[:<args>...]")),
+    }
+}
+
+/// `@arr:<method>:<array>` - Operations on arrays.
+fn arr_ns(shell: &mut Shell, args: &[Variable]) -> Result<Variable, ShellError> {
+    const USAGE: &str = "oyster: usage: @arr:<len|rev|sort|join|contains>:<array>[:<arg>]";
+    let (method, array, rest) = match args {
+        [method, array, rest @ ..] => (method.to_string(), array, rest),
+        _ => return Err(ShellError::from(USAGE)),
+    };
+    let mut array = match array {
+        Variable::Arr(arr) => arr.clone(),
+        // a bare name is looked up as a variable
+        Variable::Str(name) => match shell.get_variable(name) {
+            Some(Variable::Arr(arr)) => arr,
+            _ => return Err(ShellError::from(format!("oyster: {} is not an array", name))),
+        },
+        var => vec![var.clone()],
+    };
+    match (method.as_str(), rest) {
+        ("len", []) => Ok(Variable::Int(array.len() as i64)),
+        ("rev", []) => {
+            array.reverse();
+            Ok(Variable::Arr(array))
+        }
+        ("sort", []) => {
+            array.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+            Ok(Variable::Arr(array))
+        }
+        ("join", []) => Ok(Variable::Str(array_to_strings(&array).join(" "))),
+        ("join", [sep]) => Ok(Variable::Str(array_to_strings(&array).join(&sep.to_string()))),
+        ("contains", [item]) => {
+            let found = array.iter().any(|elem| elem.to_string() == item.to_string());
+            Ok(Variable::from(found.to_string()))
+        }
+        _ => Err(ShellError::from(USAGE)),
+    }
+}
+
+fn array_to_strings(array: &[Variable]) -> Vec<String> {
+    array.iter().map(|elem| elem.to_string()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_namespace_expansion() {
+        let mut shell = Shell::new();
+        shell.add_variable("x", Variable::Arr(vec![
+            Variable::Int(3), Variable::Int(1), Variable::Int(2),
+        ]));
+        env::set_var("OYSTER_NS_TEST", "hello world");
+        assert_eq!(expand(&mut shell, "@arr:len:$x").unwrap(), vec!["3"]);
+        assert_eq!(expand(&mut shell, "@arr:sort:x").unwrap(), vec!["1", "2", "3"]);
+        assert_eq!(expand(&mut shell, "$arr:join:$x:,").unwrap(), vec!["3,1,2"]);
+        assert_eq!(expand(&mut shell, "@env:OYSTER_NS_TEST").unwrap(), vec!["hello", "world"]);
+        assert_eq!(expand(&mut shell, "$env:OYSTER_NS_TEST").unwrap(), vec!["hello world"]);
+        assert_eq!(expand(&mut shell, "$c:bold:red").unwrap(), vec!["\x1b[1m\x1b[31m"]);
+        assert!(expand(&mut shell, "@color:mauve").is_err());
+        assert!(expand(&mut shell, "@nothing:here").is_err());
+
+        assert!(shell.namespaces.register_func("env", "myfunc").is_err());
+        shell.namespaces.register_func("mine", "myfunc").unwrap();
+        assert!(shell.namespaces.remove("mine").is_ok());
+        assert!(shell.namespaces.remove("arr").is_err());
+    }
+}
@@ -1,8 +1,13 @@
 use std::iter::Peekable;
+use std::mem;
+use std::os::unix::io::RawFd;
 use std::str::Chars;
 
 use crate::types::{
     Redirect,
+    Redirection,
+    RedirectOp,
+    Span,
     Exec,
     Token,
     TokenCmd,
@@ -42,6 +47,9 @@ impl Lexer {
 
         let mut tokens = Vec::<Token>::new();
         let mut buffer = String::new();
+        // here-documents waiting for their bodies to be read in,
+        // as the index of their token and whether to strip tabs
+        let mut heredocs = Vec::<(usize, bool)>::new();
 
         while let Some(c) = chars.next() {
             match c {
@@ -71,35 +79,94 @@ impl Lexer {
                 '&' if chars.peek() == Some(&'>') => {
                     tokens.push(Token::Word(buffer.clone()));
                     buffer.clear();
-                    tokens.push(Token::RDStdOutErr);
+                    let start = offset(cmd, &chars) - 1;
                     chars.next();
+                    let op = if chars.peek() == Some(&'>') {
+                        chars.next();
+                        RedirectOp::AppendAll
+                    } else {
+                        RedirectOp::OutputAll
+                    };
+                    tokens.push(Token::Redirect {
+                        fd: None, op, span: Span::new(start, offset(cmd, &chars))
+                    });
                 }
                 '&' => {
                     tokens.push(Token::Word(buffer.clone()));
                     buffer.clear();
                     tokens.push(Token::Background);
                 }
-                '>' if chars.peek() == Some(&'>') => {
-                    tokens.push(Token::Word(buffer.clone()));
-                    buffer.clear();
-                    tokens.push(Token::RDAppend);
-                    chars.next();
-                }
-                '>' if chars.peek() == Some(&'&') => {
-                    tokens.push(Token::Word(buffer.clone()));
-                    buffer.clear();
-                    tokens.push(Token::RDFileDesc);
-                    chars.next();
-                }
                 '>' => {
-                    tokens.push(Token::Word(buffer.clone()));
-                    buffer.clear();
-                    tokens.push(Token::Redirect);
+                    let start = offset(cmd, &chars) - 1;
+                    let op = match chars.peek() {
+                        Some(&'>') => {
+                            chars.next();
+                            if chars.peek() == Some(&'&') {
+                                chars.next();
+                                RedirectOp::AppendAll
+                            } else {
+                                RedirectOp::Append
+                            }
+                        }
+                        Some(&'&') => {
+                            chars.next();
+                            RedirectOp::DupOutput
+                        }
+                        Some(&'|') => {
+                            chars.next();
+                            RedirectOp::Clobber
+                        }
+                        _ => RedirectOp::Output,
+                    };
+                    let span = Span::new(start, offset(cmd, &chars));
+                    push_redirect(&mut tokens, &mut buffer, op, span);
                 }
                 '<' => {
+                    let start = offset(cmd, &chars) - 1;
+                    let mut strip_tabs = false;
+                    let op = match chars.peek() {
+                        Some(&'<') => {
+                            chars.next();
+                            match chars.peek() {
+                                Some(&'<') => {
+                                    chars.next();
+                                    RedirectOp::HereStr
+                                }
+                                Some(&'-') => {
+                                    chars.next();
+                                    strip_tabs = true;
+                                    RedirectOp::HereDoc
+                                }
+                                _ => RedirectOp::HereDoc,
+                            }
+                        }
+                        Some(&'&') => {
+                            chars.next();
+                            RedirectOp::DupInput
+                        }
+                        Some(&'>') => {
+                            chars.next();
+                            RedirectOp::ReadWrite
+                        }
+                        _ => RedirectOp::Input,
+                    };
+                    let span = Span::new(start, offset(cmd, &chars));
+                    push_redirect(&mut tokens, &mut buffer, op, span);
+                    if op == RedirectOp::HereDoc {
+                        heredocs.push((tokens.len() - 1, strip_tabs));
+                    }
+                }
+                '\n' if !heredocs.is_empty() => {
                     tokens.push(Token::Word(buffer.clone()));
                     buffer.clear();
-                    tokens.push(Token::RDStdin);
+                    for (idx, strip_tabs) in heredocs.drain(..) {
+                        if let Err(e) = lexer.consume_heredoc(
+                            &mut tokens[idx + 1..], strip_tabs, &mut chars
+                        ) {
+                            return e
+                        }
+                    }
+                    tokens.push(Token::Consec);
                 }
                 ';' | '\n' => {
                     tokens.push(Token::Word(buffer.clone()));
@@ -138,6 +205,12 @@ impl Lexer {
                         Err(e) => return e
                     }
                 }
+                '{' if buffer.is_empty() && follows_equals(&tokens) => {
+                    match lexer.consume_maplit(&mut chars) {
+                        Ok(tk) => tokens.push(tk),
+                        Err(e) => return e
+                    }
+                }
                 '{' => {
                     buffer.push(c);
                     lexer.consume_brace(&mut buffer, &mut chars);
@@ -161,6 +234,12 @@ impl Lexer {
                         Err(e) => return e
                     }
                 }
+                '@' if buffer.is_empty() && starts_nmspce(&chars) => {
+                    match lexer.consume_nmspce_word(&mut chars) {
+                        Ok(tk) => tokens.push(tk),
+                        Err(e) => return e
+                    }
+                }
                 '$' if chars.peek() == Some(&'{') => {
                     tokens.push(Token::Word(buffer.clone()));
                     buffer.clear();
@@ -185,6 +264,9 @@ impl Lexer {
         if !buffer.is_empty() {
             tokens.push(Token::Word(buffer))
         }
+        if !heredocs.is_empty() {
+            return TokenizeResult::UnmatchedHereDoc
+        }
 
         //filtering empty words
         let mut tokens: Vec<Token> = tokens.into_iter()
@@ -317,6 +399,15 @@ impl Lexer {
                 break
             }
         }
+        // indexing into an array or map
+        if chars.peek() == Some(&'[') {
+            for c in chars.by_ref() {
+                buf.push(c);
+                if c == ']' {
+                    break
+                }
+            }
+        }
         Ok(Token::Variable(buf))
     }
 
@@ -335,6 +426,27 @@ impl Lexer {
         }
     }
 
+    /// Consumes a map literal, e.g. `{a: 1, b: "x"}`.
+    /// The literal is returned whole as a double-quoted string.
+    fn consume_maplit(&mut self, chars: &mut CharsIter) -> Result<Token, TokenizeResult> {
+        let mut buf = String::from("{");
+        let mut quote: Option<char> = None;
+        loop {
+            if let Some(c) = chars.next() {
+                buf.push(c);
+                match c {
+                    '"' | '\'' if quote.is_none() => quote = Some(c),
+                    c if quote == Some(c) => quote = None,
+                    '}' if quote.is_none() => break,
+                    _ => {}
+                }
+            } else {
+                return Err(TokenizeResult::UnmatchedBrace)
+            }
+        }
+        Ok(Token::DQuote(buf))
+    }
+
     fn consume_sqbrkt(&mut self, chars: &mut CharsIter) -> Result<Token, TokenizeResult> {
         let mut buf = String::new();
         let mut nesting_level = 0;
@@ -360,12 +472,60 @@ impl Lexer {
         return Ok(Token::SqBrkt(buf))
     }
 
+    /// Reads in the body of a here-document, up to the line containing
+    /// only the delimiter. The delimiter is the first word in `tokens`,
+    /// and is replaced with the body.
+    fn consume_heredoc(&mut self, 
+        tokens: &mut [Token],
+        strip_tabs: bool,
+        chars: &mut CharsIter
+    ) -> Result<(), TokenizeResult> {
+        let pos = match tokens.iter().position(|token| {
+            !matches!(token, Token::Word(word) if word.is_empty())
+        }) {
+            Some(pos) => pos,
+            None => return Ok(())
+        };
+        let (delim, expand) = match &tokens[pos] {
+            Token::Word(word) => (word.clone(), true),
+            Token::SQuote(word) | Token::DQuote(word) => (word.clone(), false),
+            //* the parser will error out on this
+            _ => return Ok(())
+        };
+        let mut body = String::new();
+        loop {
+            if chars.peek().is_none() {
+                return Err(TokenizeResult::UnmatchedHereDoc)
+            }
+            let mut line = String::new();
+            for c in chars.by_ref() {
+                if c == '\n' {
+                    break
+                }
+                line.push(c);
+            }
+            if strip_tabs {
+                line = line.trim_start_matches('\t').to_string();
+            }
+            if line == delim {
+                break
+            }
+            body.push_str(&line);
+            body.push('\n');
+        }
+        tokens[pos] = Token::HereDoc(body, expand);
+        Ok(())
+    }
+
+    /// Consumes a namespaced expansion of the form `${ns:args}`.
+    /// The braces are stripped, leaving `$ns:args`.
     fn consume_nmspce(&mut self, chars: &mut CharsIter) -> Result<Token, TokenizeResult> {
-        let mut buf = String::new();
+        let mut buf = String::from("$");
+        // skipping the opening brace
+        chars.next();
         loop {
             if let Some(c) = chars.next() {
                 if c == '}' {
-                    buf.push(c);
                     break
                 } else {
                     buf.push(c)
@@ -377,6 +537,20 @@ impl Lexer {
         Ok(Token::NmSpce(buf))
     }
 
+    /// Consumes a namespaced expansion of the form `@ns:args`,
+    /// up to the end of the word.
+    fn consume_nmspce_word(&mut self, chars: &mut CharsIter) -> Result<Token, TokenizeResult> {
+        let mut buf = String::from("@");
+        while let Some(&c) = chars.peek() {
+            if c.is_whitespace() || matches!(c, '|' | '&' | ';' | '<' | '>' | '"' | '\'') {
+                break
+            }
+            buf.push(c);
+            chars.next();
+        }
+        Ok(Token::NmSpce(buf))
+    }
+
     /// Splits command and parses special characters
     pub fn parse_tokens(shell: &mut Shell, tokens: Vec<Token>) 
     -> Result<ParseResult, ParseError> {
@@ -423,17 +597,16 @@ impl Lexer {
         for mut tokengrp in commandmap {
 
             //* trackers
-            let mut all_to_filename = false;
-            let mut rd_to_filename = false;
-            let mut rd_to_filedesc = false;
-            let mut rd_from_stdin = false;
+            // a redirect operator waiting for its target
+            let mut pending_rd: Option<(Option<RawFd>, RedirectOp, Span)> = None;
 
             let mut cmd_idx = 0;
+            // patterns in a match arm are not glob expanded
+            let mut in_case = false;
 
             //* accumulators
             let mut buffer = Vec::<(Quote, String)>::new();
-            let mut redirect = [String::new(), String::new(), String::new()];
-            let mut redirects = Vec::<[String; 3]>::new();
+            let mut redirects = Vec::<Redirection>::new();
 
             //* building job from these
             let mut cmds = Vec::<TokenCmd>::new();
@@ -460,164 +633,17 @@ impl Lexer {
             for token in tokengrp {
 
                 // println!("=========================");
-                // println!("{}, {}, {}", all_to_filename, rd_to_filename, rd_to_filedesc);
                 // println!("Token: {:?}", token);
                 // println!("Buffer: {:?}", buffer);
                 // println!("Cmds: {:?}", cmds);
-                // println!("Redirect: {:?}", redirect);
                 // println!("Redirects: {:?}", redirects);
 
-                if rd_to_filename {
-                    match token {
-                        Token::Word(mut dest) => {
-                            expand_variables(shell, &mut dest);
-                            expand_tilde(shell, &mut dest);
-                            redirect[2] = dest;
-                            redirects.push(redirect.clone());
-                            rd_to_filename = false;
-                            continue;
-                        }
-                        Token::DQuote(mut dest) => {
-                            expand_variables(shell, &mut dest);
-                            let dest = substitute_commands(shell, &dest)?;
-                            redirect[2] = dest;
-                            redirects.push(redirect.clone());
-                            rd_to_filename = false;
-                            continue;
-                        }
-                        Token::SQuote(dest) => {
-                            redirect[2] = dest;
-                            redirects.push(redirect.clone());
-                            rd_to_filename = false;
-                            continue;
-                        }
-                        Token::CmdSub(cmd) => {
-                            let dest = substitute_commands(shell, &cmd)?;
-                            redirect[2] = dest;
-                            redirects.push(redirect.clone());
-                            rd_to_filename = false;
-                            continue;
-                        }
-                        Token::BQuote(mut cmd) => {
-                            expand_variables(shell, &mut cmd);
-                            let dest = substitute_commands(shell, &cmd)?;
-                            redirect[2] = dest;
-                            redirects.push(redirect.clone());
-                            rd_to_filename = false;
-                            continue;
-                        }
-                        _ => { //* FIXME: Does not account for cases like:
-                               //* 2>>&1 (In zsh this appends stderr to a file called 1)
-                            return Err(ParseError::InvalidFileRD);
-                        }
-                    }
-                } else if rd_to_filedesc {
-                    match token {
-                        Token::Word(dest) | 
-                        Token::DQuote(dest) | 
-                        Token::SQuote(dest) => {
-                            redirect[2] = if dest == "1" {
-                                String::from("&1")
-                            } else if dest == "2" {
-                                String::from("&2")
-                            } else {
-                                return Err(ParseError::InvalidFileDesc)
-                            };
-                            redirects.push(redirect.clone());
-                            rd_to_filedesc = false;
-                            continue;
-                        }
-                        _ => {
-                            return Err(ParseError::InvalidFileRD);
-                        }
-                    }
-                //TODO: Appending not yet implemented; only supports truncation
-                } else if all_to_filename {
-                    match token {
-                        Token::Word(mut dest) => {
-                            expand_variables(shell, &mut dest);
-                            expand_tilde(shell, &mut dest);
-                            redirects.push([String::from("1"), 
-                                            String::from(">"), 
-                                            String::from(dest.clone())]);
-                            redirects.push([String::from("2"),
-                                            String::from(">"),
-                                            String::from(dest)]);
-                            all_to_filename = false;
-                            continue;
-                        }
-                        Token::DQuote(mut dest) => {
-                            expand_variables(shell, &mut dest);
-                            let dest = substitute_commands(shell, &dest)?;
-                            redirects.push([String::from("1"), 
-                                            String::from(">"), 
-                                            String::from(dest.clone())]);
-                            redirects.push([String::from("2"),
-                                            String::from(">"),
-                                            String::from(dest)]);
-                            all_to_filename = false;
-                            continue;
-                        }
-                        Token::SQuote(dest) => {
-                            redirects.push([String::from("1"), 
-                                            String::from(">"), 
-                                            String::from(dest.clone())]);
-                            redirects.push([String::from("2"),
-                                            String::from(">"),
-                                            String::from(dest)]);
-                            all_to_filename = false;
-                            continue;
-                        }
-                        _ => {
-                            return Err(ParseError::InvalidFileRD);
-                        }
-                    }
-                } else if rd_from_stdin {
-                    match token {
-                        Token::Word(mut dest) => {
-                            expand_variables(shell, &mut dest);
-                            expand_tilde(shell, &mut dest);
-                            redirects.push([String::from(dest), 
-                                            String::from("<"), 
-                                            String::from("0")]);
-                            rd_from_stdin = false;
-                            continue;
-                        }
-                        Token::DQuote(mut dest) => {
-                            expand_variables(shell, &mut dest);
-                            let dest = substitute_commands(shell, &dest)?;
-                            redirects.push([String::from(dest), 
-                                            String::from("<"), 
-                                            String::from("0")]);
-                            rd_from_stdin = false;
-                            continue;
-                        }
-                        Token::SQuote(dest) => {
-                            redirects.push([String::from(dest), 
-                                            String::from("<"), 
-                                            String::from("0")]);
-                            rd_from_stdin = false;
-                            continue;
-                        }
-                        _ => {
-                            return Err(ParseError::InvalidFileRD);
-                        }
-                    }
+                if let Some((fd, op, span)) = pending_rd.take() {
+                    redirects.extend(parse_redirect(shell, fd, op, span, token)?);
+                    continue;
                 }
                 match token {
                     pipe @ Token::Pipe | pipe @ Token::Pipe2 => {
-                        let mut final_redirects = 
-                            Vec::<(String, Redirect, String)>::new();
-                        for redirect in &redirects {
-                            let redirecttype = 
-                                if redirect[1] == ">>" {Redirect::Append}
-                                else if redirect[1] == ">" {Redirect::Override}
-                                else {Redirect::FromStdin};
-                            final_redirects.push((redirect[0].clone(), 
-                                                  redirecttype, 
-                                                  redirect[2].clone()));
-                        }
-                        redirects.clear();
                         if buffer.len() < 1 {
                             return Err(ParseError::EmptyCommand);
                         } else {
@@ -625,7 +651,7 @@ impl Lexer {
                                 TokenCmd {
                                     cmd: buffer[0].clone(),
                                     args: buffer.clone(),
-                                    redirects: final_redirects,
+                                    redirects: mem::take(&mut redirects),
                                     pipe_stderr: if pipe == Token::Pipe {false} else {true},
                                 }
                             );
@@ -651,6 +677,16 @@ impl Lexer {
                                 "if" => {
                                     stack.push(ParseResult::If);
                                 }
+                                "match" => {
+                                    stack.push(ParseResult::Match);
+                                }
+                                n@ "case" => {
+                                    if let Some(&ParseResult::Match) = stack.last() {
+                                        in_case = true;
+                                    } else {
+                                        return Err(ParseError::GenericError(n.to_string()))
+                                    }
+                                }
                                 n@ "elif" | n@ "else" => {
                                     if let Some(&ParseResult::If) = stack.last() {
 
@@ -670,6 +706,8 @@ impl Lexer {
                                 n@ "end" => {
                                     if let Some(&ParseResult::If) = stack.last() {
                                         stack.pop();
+                                    } else if let Some(&ParseResult::Match) = stack.last() {
+                                        stack.pop();
                                     } else {
                                         return Err(ParseError::GenericError(n.to_string()))
                                     }
@@ -684,7 +722,7 @@ impl Lexer {
                                 _ => {}
                             }
                         }
-                        if string.contains("*") {
+                        if string.contains("*") && !in_case && !shell.options.noglob {
                             expand_tilde(shell, &mut string);
                             buffer.extend(expand_glob(&string)?.into_iter().map(
                                 |string| {
@@ -713,56 +751,17 @@ impl Lexer {
                     Token::SqBrkt(string) => {
                         buffer.push((Quote::SqBrkt, string));
                     }
-                    Token::NmSpce(mut string) => {
-                        string.pop();
+                    Token::NmSpce(string) => {
                         buffer.push((Quote::NmSpce, string));
                     }
                     Token::Brace(string) => {
                         buffer.push((Quote::CBrace, string));
                     }
-                    rd @ Token::Redirect |
-                    rd @ Token::RDAppend |
-                    rd @ Token::RDFileDesc => {
-                        if rd == Token::RDFileDesc {
-                            rd_to_filedesc = true;
-                            rd_to_filename = false;
-                        } else {
-                            rd_to_filedesc = false;
-                            rd_to_filename = true;
-                        }
-                        if let Some(fd) = buffer.pop() {
-                            if fd.1 == "2" || fd.1 == "1" {
-                                //origin is file descriptor
-                                redirect[0] = fd.1;
-                                redirect[1] = if rd == Token::RDAppend {
-                                    String::from(">>")
-                                } else {
-                                    String::from(">")
-                                }
-                            } else {
-                                //is part of the command
-                                buffer.push(fd);
-                                redirect[0] = String::from("1");
-                                redirect[1] = if rd == Token::RDAppend {
-                                    String::from(">>")
-                                } else {
-                                    String::from(">")
-                                }
-                            }
-                        } else {
-                            return Err(ParseError::InvalidRDSyntax);
-                        }
-                    }
-                    Token::RDStdOutErr => {
-                        rd_to_filedesc = false;
-                        rd_to_filename = false;
-                        all_to_filename = true;
+                    Token::Redirect { fd, op, span } => {
+                        pending_rd = Some((fd, op, span));
                     }
-                    Token::RDStdin => {
-                        rd_to_filedesc = false;
-                        rd_to_filename = false;
-                        all_to_filename = false;
-                        rd_from_stdin = true;
+                    Token::HereDoc(..) => {
+                        return Err(ParseError::InvalidRDSyntax);
                     }
                     //* if matching on these, they must be the last item
                     Token::And => {
@@ -780,18 +779,9 @@ impl Lexer {
                 }
                 cmd_idx += 1;
             }
-            let mut final_redirects = 
-                Vec::<(String, Redirect, String)>::new();
-            for redirect in &redirects {
-                let redirecttype = 
-                if redirect[1] == ">>" {Redirect::Append}
-                else if redirect[1] == ">" {Redirect::Override}
-                else {Redirect::FromStdin};
-                final_redirects.push((redirect[0].clone(), 
-                                        redirecttype, 
-                                        redirect[2].clone()));
-            }
-            redirects.clear();
+            if let Some((_, _, span)) = pending_rd {
+                return Err(ParseError::InvalidFileRD(span));
+            }
             if buffer.len() < 1 {
                 return Err(ParseError::EmptyCommand);
             } else {
@@ -799,7 +789,7 @@ impl Lexer {
                     TokenCmd {
                         cmd: buffer[0].clone(),
                         args: buffer.clone(),
-                        redirects: final_redirects,
+                        redirects,
                         pipe_stderr: false,
                     }
                 );
@@ -816,6 +806,7 @@ impl Lexer {
         }
 
         //println!("{:?}", jobs);
+        shell.parse_depth = stack.len();
         if stack.is_empty() {
             return Ok(ParseResult::Good(jobs))
         } else {
@@ -825,10 +816,182 @@ impl Lexer {
     }
 }
 
+/// Checks whether the characters following an `@` are a namespace
+/// name followed by a colon, as in `@env:HOME`.
+fn starts_nmspce(chars: &CharsIter) -> bool {
+    let mut name_len = 0;
+    for c in chars.clone() {
+        if c == ':' {
+            return name_len > 0
+        } else if c.is_alphanumeric() || c == '_' {
+            name_len += 1;
+        } else {
+            return false
+        }
+    }
+    false
+}
+
+/// Pushes a redirect token, taking the buffer in front of it as the
+/// file descriptor being redirected if it is a number, as in `2>`.
+fn push_redirect(tokens: &mut Vec<Token>, buffer: &mut String, op: RedirectOp, span: Span) {
+    let fd = if buffer.chars().all(|c| c.is_ascii_digit()) {
+        buffer.parse::<RawFd>().ok()
+    } else {
+        None
+    };
+    if fd.is_some() {
+        let span = Span::new(span.start - buffer.len(), span.end);
+        tokens.push(Token::Redirect { fd, op, span });
+    } else {
+        tokens.push(Token::Word(buffer.clone()));
+        tokens.push(Token::Redirect { fd, op, span });
+    }
+    buffer.clear();
+}
+
+/// Returns the byte offset in `cmd` of the next character to be read.
+fn offset(cmd: &str, chars: &CharsIter) -> usize {
+    cmd.len() - chars.clone().map(char::len_utf8).sum::<usize>()
+}
+
+/// Checks whether the last word tokenized was `=`, as in `let m = {...}`.
+fn follows_equals(tokens: &[Token]) -> bool {
+    let last = tokens.iter().rev().find(|token| {
+        !matches!(token, Token::Word(word) if word.is_empty())
+    });
+    matches!(last, Some(Token::Word(word)) if word == "=")
+}
+
+/// Builds the redirections for a redirect operator,
+/// using the token after the operator as its target.
+fn parse_redirect(
+    shell: &mut Shell,
+    fd: Option<RawFd>,
+    op: RedirectOp,
+    span: Span,
+    token: Token,
+) -> Result<Vec<Redirection>, ParseError> {
+    let target = match op {
+        RedirectOp::Output => Redirect::ToFile {
+            path: redirect_path(shell, token, span)?, append: false, clobber: false,
+        },
+        RedirectOp::Append => Redirect::ToFile {
+            path: redirect_path(shell, token, span)?, append: true, clobber: false,
+        },
+        RedirectOp::Clobber => Redirect::ToFile {
+            path: redirect_path(shell, token, span)?, append: false, clobber: true,
+        },
+        RedirectOp::Input => {
+            Redirect::FromFile(redirect_path(shell, token, span)?)
+        }
+        RedirectOp::ReadWrite => {
+            Redirect::ReadWrite(redirect_path(shell, token, span)?)
+        }
+        RedirectOp::OutputAll | RedirectOp::AppendAll => {
+            let file = Redirect::ToFile {
+                path: redirect_path(shell, token, span)?,
+                append: op == RedirectOp::AppendAll,
+                clobber: false,
+            };
+            // as in zsh, `2>>&1` appends stderr to a file called 1
+            if let Some(fd) = fd {
+                return Ok(vec![Redirection::new(fd, file, span)])
+            }
+            return Ok(vec![
+                Redirection::new(1, file, span),
+                Redirection::new(2, Redirect::Duplicate(1), span),
+            ])
+        }
+        RedirectOp::DupOutput | RedirectOp::DupInput => {
+            match token {
+                Token::Word(dest) | 
+                Token::DQuote(dest) | 
+                Token::SQuote(dest) => {
+                    if dest == "-" {
+                        Redirect::Close
+                    } else if let Ok(target) = dest.parse::<RawFd>() {
+                        Redirect::Duplicate(target)
+                    } else {
+                        return Err(ParseError::InvalidFileDesc(span))
+                    }
+                }
+                _ => {
+                    return Err(ParseError::InvalidFileDesc(span));
+                }
+            }
+        }
+        RedirectOp::HereDoc => {
+            match token {
+                Token::HereDoc(body, expand) => {
+                    Redirect::HereDoc { body, expand }
+                }
+                _ => {
+                    return Err(ParseError::InvalidRDSyntax);
+                }
+            }
+        }
+        RedirectOp::HereStr => {
+            let (mut body, expand) = match token {
+                Token::Word(mut word) => {
+                    expand_tilde(shell, &mut word);
+                    (word, true)
+                }
+                Token::DQuote(word) |
+                Token::Variable(word) |
+                Token::CmdSub(word) => (word, true),
+                Token::SQuote(word) => (word, false),
+                _ => {
+                    return Err(ParseError::InvalidFileRD(span));
+                }
+            };
+            body.push('\n');
+            Redirect::HereDoc { body, expand }
+        }
+    };
+    let default_fd = match op {
+        RedirectOp::Input |
+        RedirectOp::ReadWrite |
+        RedirectOp::DupInput |
+        RedirectOp::HereDoc |
+        RedirectOp::HereStr => 0,
+        _ => 1,
+    };
+    Ok(vec![Redirection::new(fd.unwrap_or(default_fd), target, span)])
+}
+
+/// Expands the token after a redirect operator into the path of a file.
+fn redirect_path(shell: &mut Shell, token: Token, span: Span) -> Result<String, ParseError> {
+    match token {
+        Token::Word(mut dest) => {
+            expand_variables(shell, &mut dest)?;
+            expand_tilde(shell, &mut dest);
+            Ok(dest)
+        }
+        Token::DQuote(mut dest) => {
+            expand_variables(shell, &mut dest)?;
+            Ok(substitute_commands(shell, &dest)?)
+        }
+        Token::SQuote(dest) => Ok(dest),
+        Token::CmdSub(cmd) => {
+            Ok(substitute_commands(shell, &cmd)?)
+        }
+        Token::BQuote(mut cmd) => {
+            expand_variables(shell, &mut cmd)?;
+            Ok(substitute_commands(shell, &cmd)?)
+        }
+        _ => Err(ParseError::InvalidFileRD(span)),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn redirect(fd: Option<RawFd>, op: RedirectOp, start: usize, end: usize) -> Token {
+        Token::Redirect { fd, op, span: Span::new(start, end) }
+    }
+
     #[test]
     fn test_lexing() {
         let test_string1 = "git add src/{core,shell}.rs && git $commit -m \"hello\" >> hello.txt";
@@ -846,7 +1009,7 @@ mod tests {
                     Token::Variable(String::from("$commit")),
                     Token::Word(String::from("-m")),
                     Token::DQuote(String::from("hello")),
-                    Token::RDAppend,
+                    redirect(None, RedirectOp::Append, 54, 56),
                     Token::Word(String::from("hello.txt")),
                 ];
                 assert_eq!(tokens, proper)
@@ -864,7 +1027,7 @@ mod tests {
                     Token::Word(String::from("tux")),
                     Token::Word(String::from("-W")),
                     Token::Word(String::from("80")),
-                    Token::RDStdin,
+                    redirect(None, RedirectOp::Input, 20, 21),
                     Token::CmdSub(String::from("$(cat ~/Documents/stallman)")),
                     Token::Pipe,
                     Token::Word(String::from("lolcat")),
@@ -892,4 +1055,191 @@ mod tests {
             }
         }
     }
+
+    #[test]
+    fn test_heredoc_lexing() {
+        let test_string1 = "cat <<EOF | wc -l\nhello $name\n  world\nEOF\necho done\n";
+        let test_string2 = "cat <<-'END'\n\t\traw $name\n\tEND\n";
+        let test_string3 = "tr a-z A-Z <<< \"$name\"";
+
+        match Lexer::tokenize(test_string1) {
+            TokenizeResult::Good(tokens) => {
+                let proper = vec![
+                    Token::Word(String::from("cat")),
+                    redirect(None, RedirectOp::HereDoc, 4, 6),
+                    Token::HereDoc(String::from("hello $name\n  world\n"), true),
+                    Token::Pipe,
+                    Token::Word(String::from("wc")),
+                    Token::Word(String::from("-l")),
+                    Token::Consec,
+                    Token::Word(String::from("echo")),
+                    Token::Word(String::from("done")),
+                ];
+                assert_eq!(tokens, proper)
+            }
+            n => {
+                panic!("{:?}", n)
+            }
+        }
+
+        match Lexer::tokenize(test_string2) {
+            TokenizeResult::Good(tokens) => {
+                let proper = vec![
+                    Token::Word(String::from("cat")),
+                    redirect(None, RedirectOp::HereDoc, 4, 7),
+                    Token::HereDoc(String::from("raw $name\n"), false),
+                ];
+                assert_eq!(tokens, proper)
+            }
+            n => {
+                panic!("{:?}", n)
+            }
+        }
+
+        match Lexer::tokenize(test_string3) {
+            TokenizeResult::Good(tokens) => {
+                let proper = vec![
+                    Token::Word(String::from("tr")),
+                    Token::Word(String::from("a-z")),
+                    Token::Word(String::from("A-Z")),
+                    redirect(None, RedirectOp::HereStr, 11, 14),
+                    Token::DQuote(String::from("$name")),
+                ];
+                assert_eq!(tokens, proper)
+            }
+            n => {
+                panic!("{:?}", n)
+            }
+        }
+
+        assert_eq!(
+            Lexer::tokenize("cat <<EOF\nunfinished\n"),
+            TokenizeResult::UnmatchedHereDoc
+        );
+    }
+
+    #[test]
+    fn test_redirect_parsing() {
+        let tokens = match Lexer::tokenize("cmd 2>&1 3>|out 4<&- 5<>rw 10<in echo 2 >>log") {
+            TokenizeResult::Good(tokens) => tokens,
+            n => panic!("{:?}", n)
+        };
+        let proper = vec![
+            Token::Word(String::from("cmd")),
+            redirect(Some(2), RedirectOp::DupOutput, 4, 7),
+            Token::Word(String::from("1")),
+            redirect(Some(3), RedirectOp::Clobber, 9, 12),
+            Token::Word(String::from("out")),
+            redirect(Some(4), RedirectOp::DupInput, 16, 19),
+            Token::Word(String::from("-")),
+            redirect(Some(5), RedirectOp::ReadWrite, 21, 24),
+            Token::Word(String::from("rw")),
+            redirect(Some(10), RedirectOp::Input, 27, 30),
+            Token::Word(String::from("in")),
+            Token::Word(String::from("echo")),
+            Token::Word(String::from("2")),
+            redirect(None, RedirectOp::Append, 40, 42),
+            Token::Word(String::from("log")),
+        ];
+        assert_eq!(tokens, proper);
+
+        let mut shell = Shell::new();
+        let jobs = match Lexer::parse_tokens(&mut shell, tokens) {
+            Ok(ParseResult::Good(jobs)) => jobs,
+            n => panic!("{:?}", n)
+        };
+        let cmd = &jobs[0].cmds[0];
+        assert_eq!(cmd.redirects, vec![
+            Redirection::new(2, Redirect::Duplicate(1), Span::new(4, 7)),
+            Redirection::new(3, Redirect::ToFile {
+                path: String::from("out"), append: false, clobber: true
+            }, Span::new(9, 12)),
+            Redirection::new(4, Redirect::Close, Span::new(16, 19)),
+            Redirection::new(5, Redirect::ReadWrite(String::from("rw")), Span::new(21, 24)),
+            Redirection::new(10, Redirect::FromFile(String::from("in")), Span::new(27, 30)),
+            Redirection::new(1, Redirect::ToFile {
+                path: String::from("log"), append: true, clobber: false
+            }, Span::new(40, 42)),
+        ]);
+        assert_eq!(cmd.args.len(), 3);
+    }
+
+    #[test]
+    fn test_redirect_combinations() {
+        let mut shell = Shell::new();
+        let mut parse = |line: &str| -> Result<Vec<Redirection>, ParseError> {
+            let tokens = match Lexer::tokenize(line) {
+                TokenizeResult::Good(tokens) => tokens,
+                n => panic!("{:?}", n)
+            };
+            match Lexer::parse_tokens(&mut shell, tokens)? {
+                ParseResult::Good(mut jobs) => Ok(jobs.remove(0).cmds.remove(0).redirects),
+                n => panic!("{:?}", n)
+            }
+        };
+        let to_file = |path: &str, append| Redirect::ToFile {
+            path: String::from(path), append, clobber: false
+        };
+
+        // fd to file
+        assert_eq!(parse("ls >out").unwrap(), vec![
+            Redirection::new(1, to_file("out", false), Span::new(3, 4)),
+        ]);
+        assert_eq!(parse("ls 2>'err log'").unwrap(), vec![
+            Redirection::new(2, to_file("err log", false), Span::new(3, 5)),
+        ]);
+        // appending
+        assert_eq!(parse("ls 2>>err").unwrap(), vec![
+            Redirection::new(2, to_file("err", true), Span::new(3, 6)),
+        ]);
+        // both stdout and stderr
+        assert_eq!(parse("ls &>out").unwrap(), vec![
+            Redirection::new(1, to_file("out", false), Span::new(3, 5)),
+            Redirection::new(2, Redirect::Duplicate(1), Span::new(3, 5)),
+        ]);
+        assert_eq!(parse("ls &>>out").unwrap(), vec![
+            Redirection::new(1, to_file("out", true), Span::new(3, 6)),
+            Redirection::new(2, Redirect::Duplicate(1), Span::new(3, 6)),
+        ]);
+        // as in zsh, this appends stderr to a file called 1
+        assert_eq!(parse("ls 2>>&1").unwrap(), vec![
+            Redirection::new(2, to_file("1", true), Span::new(3, 7)),
+        ]);
+        // fd to fd, applied in order
+        assert_eq!(parse("ls 2>&1 >out").unwrap(), vec![
+            Redirection::new(2, Redirect::Duplicate(1), Span::new(3, 6)),
+            Redirection::new(1, to_file("out", false), Span::new(8, 9)),
+        ]);
+        assert_eq!(parse("cat <&3").unwrap(), vec![
+            Redirection::new(0, Redirect::Duplicate(3), Span::new(4, 6)),
+        ]);
+        // input
+        assert_eq!(parse("cat < in").unwrap(), vec![
+            Redirection::new(0, Redirect::FromFile(String::from("in")), Span::new(4, 5)),
+        ]);
+        // closing
+        assert_eq!(parse("ls >&-").unwrap(), vec![
+            Redirection::new(1, Redirect::Close, Span::new(3, 5)),
+        ]);
+        // here-documents and here-strings
+        assert_eq!(parse("cat 3<<'EOF'\n$body\nEOF\n").unwrap(), vec![
+            Redirection::new(3, Redirect::HereDoc {
+                body: String::from("$body\n"), expand: false
+            }, Span::new(4, 7)),
+        ]);
+        assert_eq!(parse("cat <<< hello").unwrap(), vec![
+            Redirection::new(0, Redirect::HereDoc {
+                body: String::from("hello\n"), expand: true
+            }, Span::new(4, 7)),
+        ]);
+
+        // errors point at the redirect operator
+        assert_eq!(parse("ls >&file"), Err(ParseError::InvalidFileDesc(Span::new(3, 5))));
+        assert_eq!(parse("ls 2>"), Err(ParseError::InvalidFileRD(Span::new(3, 5))));
+        assert_eq!(parse("ls > | wc"), Err(ParseError::InvalidFileRD(Span::new(3, 4))));
+        assert_eq!(
+            Span::new(3, 5).underline("ls >&file").unwrap(),
+            "  ls >&file\n     ^^"
+        );
+    }
 }
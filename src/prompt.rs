@@ -1,36 +1,204 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
 use std::env;
 use std::error::Error;
+use std::io::{self, Write};
 use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use nix::sys::signal::Signal;
 use nix::unistd::gethostname;
 
+use crate::core;
+use crate::git::GitPrompt;
+use crate::history::format_timestamp;
+use crate::shell::Shell;
+use crate::style::{ColorDepth, render_style, hide_escapes, display_width};
+use crate::types::{JobStatus, ParseResult, ShellError};
+
 pub const RESET: &str = "\x01\x1B[0m\x02";
 pub const BOLD: &str = "\x01\x1B[1m\x02";
-pub const UNDERLINED: &str = "\x01\x1B[4m\x02";
-
-pub const BLUE: &str = "\x01\x1B[34m\x02";
-pub const YELLOW: &str = "\x01\x1B[33m\x02";
-pub const BLACK: &str = "\x01\x1B[30m\x02";
-pub const WHITE: &str = "\x01\x1B[97m\x02";
-pub const RED: &str = "\x01\x1B[31m\x02";
-pub const GREEN: &str = "\x01\x1B[32m\x02";
-
-pub const BLUE_B: &str = "\x01\x1B[34m\x1B[1m\x02";
-pub const YELLOW_B: &str = "\x01\x1B[33m\x1B[1m\x02";
-pub const BLACK_B: &str = "\x01\x1B[30m\x1B[1m\x02";
-pub const WHITE_B: &str = "\x01\x1B[97m\x1B[1m\x02";
-pub const RED_B: &str = "\x01\x1B[31m\x1B[1m\x02";
-pub const GREEN_B: &str = "\x01\x1B[32m\x1B[1m\x02";
 
 pub const OYSTER_DFL: &str 
     = "{BOLD}{USER}{RESET}: {CWD_TOP} {COLOR_ST}>>{RESET} ";
 
+/// The function that renders the prompt instead of `OYSTER_PROMPT`, if it is defined.
+pub const PROMPT_FUNC: &str = "oyster_prompt";
 
-pub fn render_prompt(status: i32) -> String {
-    let prompt = match env::var("OYSTER_PROMPT") {
-        Ok(string) => string,
-        Err(_) => OYSTER_DFL.to_string()
-    };
+/// How many milliseconds a prompt function may run for.
+pub const PROMPT_TIMEOUT_DFL: u64 = 500;
+
+/// Commands that finish quicker than this many seconds
+/// do not show their duration.
+pub const DURATION_MIN_DFL: f64 = 2.0;
+
+/// What the main loop knows about the last command,
+/// for the prompt to display.
+pub struct PromptContext<'a> {
+    pub shell: &'a mut Shell,
+    /// The exit status of the last command.
+    pub status: i32,
+    /// How long the last command took to run, if one was run.
+    pub duration: Option<Duration>,
+    /// The innermost unfinished construct, when continuing a line.
+    pub construct: Option<&'a ParseResult>,
+    /// How many constructs are left unfinished.
+    pub depth: usize,
+    /// Only looked up if the prompt shows git status, and then
+    /// shared by every template, along with its time budget.
+    git: Option<GitPrompt>,
+    /// The text of the slow tokens, `{FN:...}` and the git segments,
+    /// so that each is worked out once however many templates use it.
+    rendered: HashMap<String, String>,
+}
+
+impl<'a> PromptContext<'a> {
+    pub fn new(shell: &'a mut Shell, status: i32) -> Self {
+        PromptContext {
+            shell,
+            status,
+            duration: None,
+            construct: None,
+            depth: 0,
+            git: None,
+            rendered: HashMap::new(),
+        }
+    }
+}
+
+/// Renders the prompt from the output of the `oyster_prompt` function
+/// if it is defined, or else from `OYSTER_PROMPT` or the default prompt.
+pub fn render_prompt(ctx: &mut PromptContext) -> String {
+    let template = env::var("OYSTER_PROMPT").ok();
+    render_left(ctx, template.as_deref(), prompt_timeout())
+}
+
+/// Renders the prompt from the `oyster_prompt` function, run for
+/// at most `timeout`, or else from `template` or the default prompt.
+fn render_left(ctx: &mut PromptContext, template: Option<&str>, timeout: Duration) -> String {
+    if ctx.shell.funcs().contains_key(PROMPT_FUNC) {
+        let args = vec![ctx.status.to_string()];
+        match call_func(ctx.shell, PROMPT_FUNC, args, timeout) {
+            Ok(prompt) => return hide_escapes(&prompt),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+    render(template.unwrap_or(OYSTER_DFL), ctx)
+}
+
+/// Renders the prompt shown while waiting for the rest of an unfinished
+/// command, from `OYSTER_PROMPT2` if it is set, or else `dfl`.
+pub fn render_continuation(ctx: &mut PromptContext, dfl: &str) -> String {
+    match env::var("OYSTER_PROMPT2") {
+        Ok(string) => render(&string, ctx),
+        Err(_) => dfl.to_string(),
+    }
+}
+
+/// Renders the right prompt from `OYSTER_RPROMPT`, if it is set.
+pub fn render_rprompt(ctx: &mut PromptContext) -> Option<String> {
+    env::var("OYSTER_RPROMPT").ok().map(|string| render(&string, ctx))
+}
+
+/// Renders the prompt that replaces the prompt of an accepted command
+/// in scrollback, from `OYSTER_TRANSIENT_PROMPT`, if it is set.
+pub fn render_transient(ctx: &mut PromptContext) -> Option<String> {
+    env::var("OYSTER_TRANSIENT_PROMPT").ok().map(|string| render(&string, ctx))
+}
+
+/// A rendered prompt, along with a right prompt that is only shown
+/// while the input leaves room for it.
+pub struct PromptLine {
+    left: String,
+    right: Option<String>,
+    /// The width of the last line of `left`.
+    left_width: usize,
+    shown: bool,
+    width: Option<usize>,
+    /// The input when the prompt was last drawn.
+    input: String,
+}
+
+impl PromptLine {
+    pub fn new(left: String, right: Option<String>) -> Self {
+        let left_width = display_width(left.rsplit('\n').next().unwrap_or_default());
+        PromptLine {
+            left,
+            right,
+            left_width,
+            shown: false,
+            width: None,
+            input: String::new(),
+        }
+    }
+
+    /// Works out whether the right prompt fits beside `input` in a terminal
+    /// `width` columns wide. Returns true if the prompt has to be redrawn.
+    pub fn update(&mut self, input: &str, width: Option<usize>) -> bool {
+        let shown = match (&self.right, width) {
+            (Some(right), Some(width)) => {
+                //leaves a space after the input and in the last column
+                self.left_width + display_width(input) + display_width(right) + 2 <= width
+            }
+            _ => false,
+        };
+        //editing anywhere but the end of the input can clear the rest of the line
+        let redraw = self.right.is_some() && (shown != self.shown || width != self.width
+            || (shown && !input.starts_with(&self.input)));
+        self.shown = shown;
+        self.width = width;
+        self.input = input.to_string();
+        redraw
+    }
+
+    /// The prompt to give to the line editor. The right prompt is drawn
+    /// by saving the cursor, moving to the right edge and restoring it,
+    /// all hidden from the line editor between `\x01` and `\x02`.
+    pub fn text(&self) -> String {
+        match (&self.right, self.width) {
+            (Some(right), Some(width)) if self.shown => {
+                let right_width = display_width(right);
+                format!("{}\x01\x1B7\x1B[{}G{}\x1B8\x02",
+                    self.left, width - right_width, strip_markers(right))
+            }
+            _ => self.left.clone(),
+        }
+    }
+
+    /// Replaces the prompt and the accepted `input` on screen with
+    /// `transient` followed by the input, leaving the cursor below them.
+    pub fn redraw_transient(&self, transient: &str, input: &str) -> io::Result<()> {
+        let width = match term_width() {
+            Some(width) => width,
+            None => return Ok(()),
+        };
+        let rows = |line_width: usize| line_width.saturating_sub(1) / width + 1;
+        let mut lines = self.left.split('\n').collect::<Vec<&str>>();
+        let last = lines.pop().unwrap_or_default();
+        let height = lines.iter().map(|line| rows(display_width(line))).sum::<usize>()
+            + rows(display_width(last) + display_width(input));
+        let mut stdout = io::stdout();
+        write!(stdout, "\x1B[{}A\r\x1B[J{}{}\n", height, strip_markers(transient), input)?;
+        stdout.flush()
+    }
+}
+
+/// Returns the width of the terminal, or `None` if stdout is not a terminal.
+pub fn term_width() -> Option<usize> {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    if unsafe { libc::ioctl(1, libc::TIOCGWINSZ, &mut size) } == 0 && size.ws_col > 0 {
+        Some(size.ws_col as usize)
+    } else {
+        None
+    }
+}
+
+/// Removes the `\x01` and `\x02` markers, for text written straight to the terminal.
+fn strip_markers(text: &str) -> String {
+    text.chars().filter(|c| *c != '\x01' && *c != '\x02').collect()
+}
+
+fn render(prompt: &str, ctx: &mut PromptContext) -> String {
     let mut text = Vec::new();
     let mut tokens = Vec::new();
     let mut word = String::new();
@@ -61,12 +229,16 @@ pub fn render_prompt(status: i32) -> String {
     text.push(word);
 
     let mut final_prompt = String::new();
+    let depth = ColorDepth::from_env();
     let mut text_iter = text.iter();
     if let Some(text) = text_iter.next() {
         final_prompt.push_str(text);
     }
     for token in tokens {
         match token.as_str() {
+            token if ctx.rendered.contains_key(token) => {
+                final_prompt.push_str(&ctx.rendered[token]);
+            }
             "CWD_FULL" => {
                 match render_cwd(false) {
                     Ok(dir) => {final_prompt.push_str(&dir);}
@@ -113,73 +285,197 @@ pub fn render_prompt(status: i32) -> String {
                     }
                 }
             }
-            "GIT_REPO" => {
-                //TODO: get git status
+            segment @ "GIT_BRANCH" | segment @ "GIT_REPO" | segment @ "GIT_DIRTY"
+            | segment @ "GIT_AHEAD_BEHIND" | segment @ "GIT_STASH" => {
+                let git = ctx.git.get_or_insert_with(GitPrompt::new);
+                let rendered = match segment {
+                    "GIT_DIRTY" => git.dirty(),
+                    "GIT_AHEAD_BEHIND" => git.ahead_behind(),
+                    "GIT_STASH" => git.stash(),
+                    _ => git.branch(),
+                };
+                final_prompt.push_str(&rendered);
+                ctx.rendered.insert(segment.to_string(), rendered);
             }
             "NEWLINE" => {
                 final_prompt.push('\n');
             }
-            "COLOR_ST" => {
-                if status == 0 {
-                    final_prompt.push_str(GREEN);
-                } else {
-                    final_prompt.push_str(RED);
-                }
-            }
-            "BLUE" => {
-                final_prompt.push_str(BLUE);
+            "STATUS" => {
+                final_prompt.push_str(&render_status(ctx.status));
             }
-            "YELLOW" => {
-                final_prompt.push_str(YELLOW);
+            "DURATION" => {
+                final_prompt.push_str(&render_duration(ctx.duration));
             }
-            "BLACK" => {
-                final_prompt.push_str(BLACK);
+            "JOBS" => {
+                final_prompt.push_str(&count_jobs(ctx.shell, None));
             }
-            "WHITE" => {
-                final_prompt.push_str(WHITE);
+            "JOBS_RUNNING" => {
+                final_prompt.push_str(&count_jobs(ctx.shell, Some(JobStatus::Running)));
             }
-            "RED" => {
-                final_prompt.push_str(RED);
+            "JOBS_STOPPED" => {
+                final_prompt.push_str(&count_jobs(ctx.shell, Some(JobStatus::Stopped)));
             }
-            "GREEN" => {
-                final_prompt.push_str(GREEN);
+            "TIME" => {
+                final_prompt.push_str(&render_time());
             }
-            "BLUE_B" => {
-                final_prompt.push_str(BLUE_B);
+            "SHLVL" => {
+                final_prompt.push_str(&env::var("SHLVL").unwrap_or_default());
             }
-            "YELLOW_B" => {
-                final_prompt.push_str(YELLOW_B);
-            }
-            "BLACK_B" => {
-                final_prompt.push_str(BLACK_B);
-            }
-            "WHITE_B" => {
-                final_prompt.push_str(WHITE_B);
-            }
-            "RED_B" => {
-                final_prompt.push_str(RED_B);
-            }
-            "GREEN_B" => {
-                final_prompt.push_str(GREEN_B);
+            "DEPTH" => {
+                if ctx.depth > 0 {
+                    final_prompt.push_str(&ctx.depth.to_string());
+                }
             }
-            "BOLD" => {
-                final_prompt.push_str(BOLD);
+            "CONSTRUCT" => {
+                if let Some(construct) = ctx.construct {
+                    final_prompt.push_str(construct.name());
+                }
             }
-            "ULINED" => {
-                final_prompt.push_str(UNDERLINED);
+            token if token.starts_with("FN:") => {
+                let name = &token[3..];
+                if !ctx.shell.funcs().contains_key(name) {
+                    final_prompt.push_str(&format!("{{{}}}", token));
+                } else {
+                    //a function that fails shows nothing, and is not run again
+                    let rendered = call_func(ctx.shell, name, Vec::new(), prompt_timeout())
+                        .unwrap_or_else(|e| {
+                            eprintln!("{}", e);
+                            String::new()
+                        });
+                    final_prompt.push_str(&rendered);
+                    ctx.rendered.insert(token.to_string(), rendered);
+                }
             }
-            "RESET" => {
-                final_prompt.push_str(RESET);
+            "COLOR_ST" => {
+                let style = if ctx.status == 0 {"fg:green"} else {"fg:red"};
+                final_prompt.push_str(&render_style(style, depth).unwrap_or_default());
             }
-            n @ _ => {
-                eprintln!("oyster: error parsing prompt - unknown token {}", n);
+            token => {
+                let styles = match legacy_style(token) {
+                    Some(styles) => styles.iter()
+                        .map(|style| render_style(style, depth))
+                        .collect::<Option<String>>(),
+                    None => render_style(token, depth),
+                };
+                match styles {
+                    Some(styles) => final_prompt.push_str(&styles),
+                    //shown as is, so that a typo is noticed without an error every prompt
+                    None => {
+                        final_prompt.push('{');
+                        final_prompt.push_str(token);
+                        final_prompt.push('}');
+                    }
+                }
             }
         }
         if let Some(text) = text_iter.next() {
             final_prompt.push_str(text);
         }
     }
-    final_prompt
+    hide_escapes(&final_prompt)
+}
+
+/// How long a prompt function may run for, from `OYSTER_PROMPT_TIMEOUT`.
+fn prompt_timeout() -> Duration {
+    let timeout = env::var("OYSTER_PROMPT_TIMEOUT").ok()
+        .and_then(|timeout| timeout.parse::<u64>().ok())
+        .unwrap_or(PROMPT_TIMEOUT_DFL);
+    Duration::from_millis(timeout)
+}
+
+/// Runs a prompt function, returning its output without the final newline.
+/// The function is killed if it takes longer than `timeout`.
+fn call_func(shell: &mut Shell, name: &str, args: Vec<String>, timeout: Duration)
+-> Result<String, ShellError> {
+    let (_, mut output) = core::capture_func(shell, name, args, Some(timeout))?;
+    if output.ends_with('\n') {
+        output.pop();
+    }
+    Ok(output)
+}
+
+/// Translates the upper case colour keywords into style tokens.
+fn legacy_style(token: &str) -> Option<&'static [&'static str]> {
+    let styles: &[&str] = match token {
+        "BLUE" => &["fg:blue"],
+        "YELLOW" => &["fg:yellow"],
+        "BLACK" => &["fg:black"],
+        "WHITE" => &["fg:bright_white"],
+        "RED" => &["fg:red"],
+        "GREEN" => &["fg:green"],
+        "BLUE_B" => &["fg:blue", "bold"],
+        "YELLOW_B" => &["fg:yellow", "bold"],
+        "BLACK_B" => &["fg:black", "bold"],
+        "WHITE_B" => &["fg:bright_white", "bold"],
+        "RED_B" => &["fg:red", "bold"],
+        "GREEN_B" => &["fg:green", "bold"],
+        "BOLD" => &["bold"],
+        "ULINED" => &["underline"],
+        "RESET" => &["reset"],
+        _ => return None,
+    };
+    Some(styles)
+}
+
+/// Renders a non-zero exit status, or the name of the signal
+/// that killed the command for statuses above 128.
+fn render_status(status: i32) -> String {
+    if status == 0 {
+        return String::new();
+    }
+    match Signal::try_from(status - 128) {
+        Ok(signal) if status > 128 => signal.as_str().to_string(),
+        _ => status.to_string(),
+    }
+}
+
+/// Renders how long the last command took, if it took longer
+/// than `OYSTER_DURATION_MIN` seconds.
+fn render_duration(duration: Option<Duration>) -> String {
+    let min = env::var("OYSTER_DURATION_MIN").ok()
+        .and_then(|min| min.parse::<f64>().ok())
+        .unwrap_or(DURATION_MIN_DFL);
+    match duration {
+        Some(duration) if duration.as_secs_f64() >= min => {
+            format_duration(duration)
+        }
+        _ => String::new(),
+    }
+}
+
+/// Formats a duration as `4.2s`, `1m 23s` or `2h 5m 0s`.
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        format!("{:.1}s", duration.as_secs_f64())
+    } else if secs < 3600 {
+        format!("{}m {}s", secs / 60, secs % 60)
+    } else {
+        format!("{}h {}m {}s", secs / 3600, secs / 60 % 60, secs % 60)
+    }
+}
+
+/// Counts the shell's jobs, or only those with the given status.
+/// Renders nothing if there are none.
+fn count_jobs(shell: &Shell, status: Option<JobStatus>) -> String {
+    let count = shell.jobs.values()
+        .filter(|job| status.is_none() || status.as_ref() == Some(&job.status))
+        .count();
+    if count == 0 {
+        String::new()
+    } else {
+        count.to_string()
+    }
+}
+
+/// Renders the local time as `HH:MM:SS`.
+fn render_time() -> String {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|time| time.as_secs())
+        .unwrap_or(0);
+    //the timestamp ends with the time of day
+    let timestamp = format_timestamp(now);
+    timestamp[timestamp.len() - 8..].to_string()
 }
 
 pub fn render_cwd(last: bool) -> Result<String, Box<dyn Error>> {
@@ -204,4 +500,67 @@ pub fn render_cwd(last: bool) -> Result<String, Box<dyn Error>> {
         to_display = current_dir.to_str().unwrap().to_string();
     }
     return Ok(to_display)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::scripting::execute_script;
+
+    #[test]
+    fn check_prompt_segments() {
+        let mut shell = Shell::new();
+        let construct = ParseResult::For;
+        let mut ctx = PromptContext::new(&mut shell, 1);
+        ctx.duration = Some(Duration::from_millis(83_400));
+        assert_eq!(render("[{STATUS}] {DURATION} {JOBS}", &mut ctx), "[1] 1m 23s ");
+        ctx.status = 130;
+        ctx.duration = Some(Duration::from_millis(4_200));
+        assert_eq!(render("{STATUS} {DURATION}", &mut ctx), "SIGINT 4.2s");
+        ctx.status = 0;
+        ctx.duration = Some(Duration::from_millis(300));
+        assert_eq!(render("{STATUS}{DURATION}", &mut ctx), "");
+        ctx.construct = Some(&construct);
+        ctx.depth = 2;
+        assert_eq!(render("{CONSTRUCT} {DEPTH}> ", &mut ctx), "for 2> ");
+
+        assert_eq!(format_duration(Duration::from_secs(7500)), "2h 5m 0s");
+        assert_eq!(render_time().len(), 8);
+    }
+
+    #[test]
+    fn check_right_prompt() {
+        let left = format!("first line\n{}>>{} ", BOLD, RESET);
+        let mut prompt = PromptLine::new(left.clone(), Some(format!("{}12:00{}", BOLD, RESET)));
+        assert!(prompt.update("", Some(20)));
+        assert_eq!(prompt.text(), format!("{}\x01\x1B7\x1B[15G\x1B[1m12:00\x1B[0m\x1B8\x02", left));
+        assert!(!prompt.update("ls -la", Some(20)));
+        assert!(prompt.update("ls -la /tmp", Some(20)));
+        assert_eq!(prompt.text(), left);
+        assert!(prompt.update("ls", Some(20)));
+        assert!(prompt.update("ls", Some(10)));
+        assert_eq!(prompt.text(), left);
+        assert!(!prompt.update("ls", Some(10)));
+    }
+
+    #[test]
+    fn check_prompt_functions() {
+        let mut shell = Shell::new();
+        let script = "func venv\necho (env)\nendfn\nfunc oyster_prompt\necho \"<$1> \"\nendfn\n";
+        execute_script(&mut shell, script.as_bytes(), Vec::new()).unwrap();
+        let mut ctx = PromptContext::new(&mut shell, 2);
+        assert_eq!(render("{FN:venv} {FN:missing}> ", &mut ctx), "(env) {FN:missing}> ");
+        assert_eq!(render_prompt(&mut ctx), "<2> ");
+
+        //functions run once per prompt, whichever templates use them
+        let script = "func venv\necho (other)\nendfn\n";
+        execute_script(ctx.shell, script.as_bytes(), Vec::new()).unwrap();
+        assert_eq!(render("{FN:venv}", &mut ctx), "(env)");
+        assert_eq!(render("{FN:venv}", &mut PromptContext::new(ctx.shell, 2)), "(other)");
+
+        let script = "func oyster_prompt\nsleep 5\nendfn\n";
+        execute_script(ctx.shell, script.as_bytes(), Vec::new()).unwrap();
+        let timeout = Duration::from_millis(100);
+        assert_eq!(render_left(&mut ctx, Some("fallback> "), timeout), "fallback> ");
+    }
+}
@@ -1,8 +1,13 @@
 //use std::collections::HashMap;
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader, Read};
+
+use nix::unistd;
+
+use regex::Regex;
 
 use crate::parser::Lexer;
+use crate::namespace;
 use crate::types::{
     Job, 
     ShellError, 
@@ -12,6 +17,7 @@ use crate::types::{
     Variable,
     TokenizeResult,
     ParseResult,
+    ControlFlow,
 };
 use crate::shell::{
     Shell,
@@ -22,27 +28,108 @@ use crate::expansion::{
     expand_tilde,
     expand_braces,
     expand_range,
+    range_contains,
     index_into,
+    expand_map_iter,
 };
 use crate::execute::{
     execute_jobs,
     execute as exec,
 };
-//TODO: Implement scoping; rn all variables have global scope
 
-pub fn execute_scriptfile(shell: &mut Shell, filename: &str) -> Result<i32, ShellError> {
-    let lines = BufReader::new(File::open(filename)?).lines();
+/// Executes the script at `filename`, with `args` as its positional
+/// parameters. If `args` is empty, the positional parameters are left
+/// as they are.
+pub fn execute_scriptfile(shell: &mut Shell, filename: &str, args: Vec<String>) 
+-> Result<i32, ShellError> {
+    let file = BufReader::new(File::open(filename)?);
+    execute_script(shell, file, args)
+}
+
+/// Executes a script read line by line from `script`, such as
+/// stdin or the string given to `oyster -c`.
+pub fn execute_script<R: BufRead>(shell: &mut Shell, script: R, args: Vec<String>)
+-> Result<i32, ShellError> {
+    if args.is_empty() {
+        return run_lines(shell, script)
+    }
+    shell.push_params(args);
+    let result = run_lines(shell, script);
+    shell.pop_scope();
+    result
+}
+
+/// A script read from stdin.
+///
+/// Stdin is read one byte at a time, so that nothing after the current
+/// line is consumed and commands in the script that read stdin,
+/// such as `read`, get the lines that follow.
+pub struct StdinScript {
+    byte: [u8; 1],
+    filled: bool,
+}
+
+impl StdinScript {
+    pub fn new() -> Self {
+        StdinScript { byte: [0], filled: false }
+    }
+}
+
+impl Read for StdinScript {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0)
+        }
+        let read = self.fill_buf()?.len();
+        if read > 0 {
+            buf[0] = self.byte[0];
+            self.consume(read);
+        }
+        Ok(read)
+    }
+}
+
+impl BufRead for StdinScript {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if !self.filled {
+            let read = unistd::read(0, &mut self.byte)
+                .map_err(|_| io::Error::last_os_error())?;
+            self.filled = read > 0;
+        }
+        Ok(if self.filled { &self.byte } else { &[] })
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if amt > 0 {
+            self.filled = false;
+        }
+    }
+}
+
+fn run_lines<R: BufRead>(shell: &mut Shell, script: R) -> Result<i32, ShellError> {
+    let lines = script.lines();
     let mut status: i32 = 0;
     let mut buffer = String::new();
+    // here-document bodies are read in verbatim
+    let mut in_heredoc = false;
     for line in lines {
         let line = line?;
-        if line.is_empty() || line.starts_with("#") {
-            continue
+        if in_heredoc {
+            buffer.push_str(&line);
+        } else {
+            if line.is_empty() || line.starts_with("#") {
+                continue
+            }
+            buffer.push_str(line.trim());
         }
-        buffer.push_str(line.trim());
         buffer.push('\n');
+        in_heredoc = false;
         let tokens = match Lexer::tokenize(&buffer) {
             TokenizeResult::Good(parsedtokens) => parsedtokens,
+            TokenizeResult::UnmatchedHereDoc => {
+                in_heredoc = true;
+                continue;
+            }
             _ => {continue;}
         };
         let jobs = match Lexer::parse_tokens(shell, tokens)? {
@@ -66,7 +153,7 @@ pub fn execute_scriptfile(shell: &mut Shell, filename: &str) -> Result<i32, Shel
 /// 
 /// A recursive type that forms an abstract syntax tree.
 /// 
-/// The `For` , `While` and `If` variants are branches that store the enum
+/// The `For` , `While`, `If` and `Match` variants are branches that store the enum
 /// itself alongside execution information, and the `Code` variant 
 /// is the leaf (base case) that stores the actual commands to execute.
 /// This allows `Construct` to recursively build itself
@@ -94,6 +181,13 @@ pub enum Construct {
         conditions: Vec<(Job, bool)>,
         code: Vec<Vec<Box<Construct>>>
     },
+    /// Represents a `match` statement.
+    ///
+    /// Each arm is tried in order and only the first matching arm is run.
+    Match {
+        value: (Quote, String),
+        arms: Vec<MatchArm<Construct>>,
+    },
     /// The base case for everything.
     ///
     /// Does not contain itself.
@@ -162,7 +256,7 @@ impl Construct {
                         iterable.push(substitute_commands(shell, &word.1)?);
                     } else if word.0 == Quote::DQuote {
                         let mut string = word.1.clone();
-                        expand_variables(shell, &mut string);
+                        expand_variables(shell, &mut string)?;
                         let string = substitute_commands(shell, &string)?;
                         iterable.push(string);
 
@@ -174,7 +268,9 @@ impl Construct {
                         }
 
                     } else if word.1.starts_with("@") && word.0 == Quote::NQuote {
-                        if let Some(var) = shell.get_variable(&word.1[1..]) {
+                        if let Some(expanded) = expand_map_iter(shell, &word.1) {
+                            iterable.extend(expanded?);
+                        } else if let Some(var) = shell.get_variable(&word.1[1..]) {
                             if let Variable::Arr(arr) = var {
                                 iterable.extend(arr.into_iter()
                                     .map(|var| var.to_string())
@@ -197,10 +293,9 @@ impl Construct {
                         }).collect::<Vec<String>>();
                         iterable.extend(expanded);
                     } else if word.0 == Quote::NmSpce {
-                        //TODO
-                        iterable.push(word.1.clone())
+                        iterable.extend(namespace::expand(shell, &word.1)?);
                     } else {
-                        expand_variables(shell, &mut word.1);
+                        expand_variables(shell, &mut word.1)?;
                         expand_tilde(shell, &mut word.1);
                         iterable.push(word.1.clone())
                     }
@@ -270,7 +365,38 @@ impl Construct {
                     code: final_code,
                 })
             }
-            n@ "done" | n@ "end" | n@ "elif" | n@ "else" => {
+            "match" => {
+                if let Some(last) = raw.iter().last() {
+                    //last.cmds should not be empty
+                    if last.cmds[0].cmd.1 != "end" {
+                        return
+                        Err(
+                            ShellError::from("oyster: could not parse script")
+                        )
+                    }
+                }
+                raw.remove(len - 1);
+                let details = raw.remove(0).cmds.remove(0);
+                if details.args.len() != 2 {
+                    return
+                    Err(
+                        ShellError::from("oyster: match takes exactly one value")
+                    )
+                }
+                let mut arms = Vec::new();
+                for (patterns, jobs) in split_on_arms(raw)? {
+                    let mut code = Vec::new();
+                    for job in split_on_same_scope(jobs) {
+                        code.push(Construct::build(shell, job)?);
+                    }
+                    arms.push((patterns, code));
+                }
+                Ok(Construct::Match {
+                    value: details.args[1].clone(),
+                    arms,
+                })
+            }
+            n@ "done" | n@ "end" | n@ "elif" | n@ "else" | n@ "case" => {
                 return Err(
                     ShellError::from(format!("oyster: parse error near {}", n))
                 )
@@ -285,29 +411,52 @@ impl Construct {
         self,
         shell: &mut Shell,
     ) -> Result<i32, ShellError> {
+        let is_loop = matches!(self, Construct::For{..} | Construct::While{..});
+        if is_loop {
+            shell.loop_depth += 1;
+        }
+        let result = self.run(shell);
+        if is_loop {
+            shell.loop_depth -= 1;
+        }
+        result
+    }
+
+    fn run(
+        self,
+        shell: &mut Shell,
+    ) -> Result<i32, ShellError> {
 
         match self {
             Construct::For {loop_var, iterable, code} => {
-                let mut status: i32 = 0;
+                let mut result = Ok(0);
+                //the loop variable is restored after the loop ends
+                let saved = shell.get_variable(&loop_var);
 
                 for item in iterable {
                     shell.add_variable(&loop_var, Variable::from(item));
                     //* clone here slows things down a lot
                     let code2 = code.clone();
-                    for block in code2 {
-                        status = block.execute(shell)?;
+                    result = execute_block(shell, code2);
+                    if result.is_err() || loop_should_stop(shell) {
+                        break
                     }
                 }
-                shell.remove_variable(&loop_var);
-                Ok(status)
+                if let Some(var) = saved {
+                    shell.add_variable(&loop_var, var);
+                } else {
+                    shell.remove_variable(&loop_var);
+                }
+                result
             }
             Construct::While{condition, code} => {
                 let mut status: i32 = 0;
 
                 while eval_condition(shell, condition.clone())? {
                     let code2 = code.clone();
-                    for block in code2 {
-                        status = block.execute(shell)?;
+                    status = execute_block(shell, code2)?;
+                    if loop_should_stop(shell) {
+                        break
                     }
                 }
 
@@ -319,15 +468,12 @@ impl Construct {
                     return Ok(0)
                 }
                 //note: this isn't particularly efficient
-                for (condition, _execif) in conditions {
+                for (condition, execif) in conditions {
                     //for every if and elif
                     let to_exec = code.remove(0);
-                    //if execif is true, execute the code block
-                    if eval_condition(shell, condition)? {
-                        for job in to_exec {
-                            status = job.execute(shell)?;
-                        }
-                        return Ok(status)
+                    //execif is false if the condition is negated
+                    if eval_condition(shell, condition)? == execif {
+                        return execute_block(shell, to_exec)
                     }
                 }
                 //we have reached the else statement
@@ -335,9 +481,7 @@ impl Construct {
                 //if code is empty, there is no else clause
                 if code.len() == 1 {
                     let to_exec = code.remove(0);
-                    for job in to_exec {
-                        status = job.execute(shell)?;
-                    }
+                    status = execute_block(shell, to_exec)?;
                 } else if code.len() > 1 {
                     return Err(
                         ShellError::from("error: code and condition mismatch")
@@ -345,6 +489,17 @@ impl Construct {
                 }
                 Ok(status) //code is empty
             }
+            Construct::Match {value, arms} => {
+                let value = evaluate_word(shell, &value)?;
+                for (patterns, code) in arms {
+                    for pattern in &patterns {
+                        if pattern.matches(shell, &value)? {
+                            return execute_block(shell, code)
+                        }
+                    }
+                }
+                Ok(0) //no arm matched
+            }
             //Base case
             Construct::Code(code) => {
                 // for (key, value) in vars {
@@ -357,6 +512,172 @@ impl Construct {
     }
 }
 
+/// Executes a block of constructs in order, returning the last exit status.
+///
+/// Stops early if `break`, `continue` or `return` was called.
+fn execute_block<C>(shell: &mut Shell, code: Vec<C>) -> Result<i32, ShellError> 
+where C: Into<Box<Construct>> {
+    let mut status: i32 = 0;
+    for block in code {
+        status = block.into().execute(shell)?;
+        if shell.control_flow.is_some() {
+            break
+        }
+    }
+    Ok(status)
+}
+
+/// Checks whether a loop should stop after running its body,
+/// consuming any `break` or `continue` meant for this loop.
+fn loop_should_stop(shell: &mut Shell) -> bool {
+    match shell.control_flow {
+        Some(ControlFlow::Break(n)) => {
+            shell.control_flow = if n > 1 {Some(ControlFlow::Break(n - 1))} else {None};
+            true
+        }
+        Some(ControlFlow::Continue(n)) => {
+            if n > 1 {
+                shell.control_flow = Some(ControlFlow::Continue(n - 1));
+                true
+            } else {
+                shell.control_flow = None;
+                false
+            }
+        }
+        Some(ControlFlow::Return(_)) => true,
+        None => false,
+    }
+}
+
+/// The patterns of a `case` arm and the code it runs.
+pub type MatchArm<T> = (Vec<Pattern>, Vec<T>);
+
+/// A single pattern in a `case` arm of a match statement.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pattern {
+    /// `_`, matches any value.
+    Default,
+    /// An integer range such as `[1..10]`.
+    Range(String),
+    /// `re <regex>`, matches the value against a regular expression.
+    Regex((Quote, String)),
+    /// A word containing `*`, `?` or `[`.
+    Glob(String),
+    /// Any other word, which must match the value in both type and value.
+    Value((Quote, String)),
+}
+
+impl Pattern {
+    /// Parses the words following `case` into a list of patterns.
+    fn parse(words: &[(Quote, String)]) -> Result<Vec<Self>, ShellError> {
+        if words.is_empty() {
+            return Err(ShellError::from("oyster: syntax error (no pattern)"))
+        }
+        let mut patterns = Vec::new();
+        let mut words = words.iter();
+        while let Some(word) = words.next() {
+            let pattern = match word {
+                (Quote::NQuote, string) if string == "_" => {
+                    Pattern::Default
+                }
+                (Quote::NQuote, string) if string == "re" => {
+                    match words.next() {
+                        Some(regex) => Pattern::Regex(regex.clone()),
+                        None => {
+                            return Err(ShellError::from("oyster: syntax error (no regex)"))
+                        }
+                    }
+                }
+                (Quote::SqBrkt, string) => {
+                    Pattern::Range(string.clone())
+                }
+                (Quote::NQuote, string) if string.contains(&['*', '?', '['][..]) => {
+                    Pattern::Glob(string.clone())
+                }
+                _ => {
+                    Pattern::Value(word.clone())
+                }
+            };
+            patterns.push(pattern);
+        }
+        Ok(patterns)
+    }
+
+    fn matches(&self, shell: &mut Shell, value: &Variable) -> Result<bool, ShellError> {
+        match self {
+            Pattern::Default => Ok(true),
+            Pattern::Range(range) => {
+                if let Variable::Int(int) = value {
+                    range_contains(shell, range, *int)
+                } else {
+                    Ok(false)
+                }
+            }
+            Pattern::Regex(regex) => {
+                let regex = evaluate_word(shell, regex)?.to_string();
+                match Regex::new(&regex) {
+                    Ok(regex) => Ok(regex.is_match(&value.to_string())),
+                    Err(_) => Err(
+                        ShellError::from(format!("oyster: invalid regex `{}`", regex))
+                    ),
+                }
+            }
+            Pattern::Glob(glob) => {
+                let mut glob = glob.clone();
+                expand_variables(shell, &mut glob)?;
+                Ok(glob::Pattern::new(&glob)?.matches(&value.to_string()))
+            }
+            Pattern::Value(word) => {
+                Ok(evaluate_word(shell, word)? == *value)
+            }
+        }
+    }
+}
+
+/// Evaluates a single word into a typed variable.
+///
+/// Variables keep their type, quoted strings are always strings,
+/// and everything else has its type inferred.
+fn evaluate_word(shell: &mut Shell, word: &(Quote, String)) 
+-> Result<Variable, ShellError> {
+    let (quote, string) = word;
+    match quote {
+        Quote::Variable => {
+            if string.ends_with("]") {
+                index_into(shell, string)
+            } else if let Some(var) = shell.get_variable(&string[1..]) {
+                Ok(var)
+            } else {
+                Err(
+                    ShellError::from(
+                        format!("oyster: variable {} not found", &string[1..])
+                    )
+                )
+            }
+        }
+        Quote::DQuote => {
+            let mut string = string.clone();
+            expand_variables(shell, &mut string)?;
+            Ok(Variable::Str(substitute_commands(shell, &string)?))
+        }
+        Quote::SQuote => {
+            Ok(Variable::Str(string.clone()))
+        }
+        Quote::CmdSub | Quote::BQuote => {
+            Ok(Variable::from(substitute_commands(shell, string)?))
+        }
+        Quote::NmSpce => {
+            Ok(Variable::from(namespace::expand(shell, string)?.join(" ")))
+        }
+        _ => {
+            let mut string = string.clone();
+            expand_variables(shell, &mut string)?;
+            expand_tilde(shell, &mut string);
+            Ok(Variable::from(string))
+        }
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 enum EqTest {
     Eq,
@@ -370,6 +691,9 @@ enum EqTest {
 fn eval_condition(shell: &mut Shell, mut condition: Job) 
 -> Result<bool, ShellError> {
     use EqTest::*;
+    if shell.options.xtrace {
+        eprintln!("+ {}", condition);
+    }
     if condition.cmds.len() == 1 &&
        condition.cmds[0].cmd.0 == Quote::SqBrkt {
         if condition.cmds[0].cmd.1.trim() == "true" {
@@ -378,23 +702,7 @@ fn eval_condition(shell: &mut Shell, mut condition: Job)
             return Ok(false)
         }
         let condition = condition.cmds.remove(0).cmd.1;
-        let (lhs, eq, rhs) = tokenize_sqbrkt(shell, condition)?;
-        if Variable::types_match(&lhs, &rhs) {
-            match eq {
-                Eq => {return Ok(lhs == rhs)}
-                Ne => {return Ok(lhs != rhs)}
-                Lt => {return Ok(lhs  < rhs)}
-                Gt => {return Ok(lhs  > rhs)}
-                Le => {return Ok(lhs <= rhs)}
-                Ge => {return Ok(lhs >= rhs)}
-            }
-        } else {
-            return Err(
-                ShellError::from(
-                    "oyster: cannot compare variables of different types"
-                )
-            )
-        }
+        parse_condition(&condition)?.evaluate(shell)
     } else if condition.cmds[0].cmd.0 == Quote::CmdSub {
         let lhs = Variable::Str(substitute_commands(
             shell, 
@@ -421,88 +729,271 @@ fn eval_condition(shell: &mut Shell, mut condition: Job)
     }
 }
 
-fn tokenize_sqbrkt(shell: &mut Shell, condition: String) 
--> Result<(Variable, EqTest, Variable), ShellError> {
-    let mut in_quote = false;
-    let mut parsed: Vec<(bool, String)> = Vec::new();
+/// A token inside a square bracket condition.
+#[derive(Debug, Clone, PartialEq)]
+enum CondToken {
+    /// A value, and whether it was quoted.
+    Word(bool, String),
+    Op(EqTest),
+    And,
+    Or,
+    Not,
+    LParen,
+    RParen,
+}
+
+impl std::fmt::Display for CondToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CondToken::Word(true, word) => write!(f, "\"{}\"", word),
+            CondToken::Word(false, word) => write!(f, "{}", word),
+            CondToken::Op(op) => write!(f, "{}", match op {
+                EqTest::Eq => "==",
+                EqTest::Ne => "!=",
+                EqTest::Lt => "<",
+                EqTest::Gt => ">",
+                EqTest::Le => "<=",
+                EqTest::Ge => ">=",
+            }),
+            CondToken::And => write!(f, "&&"),
+            CondToken::Or => write!(f, "||"),
+            CondToken::Not => write!(f, "!"),
+            CondToken::LParen => write!(f, "("),
+            CondToken::RParen => write!(f, ")"),
+        }
+    }
+}
+
+/// A parsed square bracket condition.
+///
+/// Operands are only looked up when the comparison is evaluated,
+/// so `&&` and `||` short-circuit without touching the other side.
+#[derive(Debug, Clone, PartialEq)]
+enum Condition {
+    Bool(bool),
+    Compare((bool, String), EqTest, (bool, String)),
+    Not(Box<Condition>),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+}
+
+impl Condition {
+    fn evaluate(&self, shell: &mut Shell) -> Result<bool, ShellError> {
+        use EqTest::*;
+        match self {
+            Condition::Bool(value) => Ok(*value),
+            Condition::Not(condition) => Ok(!condition.evaluate(shell)?),
+            Condition::And(lhs, rhs) => {
+                Ok(lhs.evaluate(shell)? && rhs.evaluate(shell)?)
+            }
+            Condition::Or(lhs, rhs) => {
+                Ok(lhs.evaluate(shell)? || rhs.evaluate(shell)?)
+            }
+            Condition::Compare(lhs, eq, rhs) => {
+                let lhs = resolve_operand(shell, lhs)?;
+                let rhs = resolve_operand(shell, rhs)?;
+                if !Variable::types_match(&lhs, &rhs) {
+                    return Err(
+                        ShellError::from(
+                            format!("oyster: cannot compare {} `{}` with {} `{}`",
+                                lhs.type_name(), lhs, rhs.type_name(), rhs)
+                        )
+                    )
+                }
+                match eq {
+                    Eq => Ok(lhs == rhs),
+                    Ne => Ok(lhs != rhs),
+                    Lt => Ok(lhs  < rhs),
+                    Gt => Ok(lhs  > rhs),
+                    Le => Ok(lhs <= rhs),
+                    Ge => Ok(lhs >= rhs),
+                }
+            }
+        }
+    }
+}
+
+/// Quoted operands are always strings, `$` operands are looked up
+/// and anything else has its type inferred.
+fn resolve_operand(shell: &mut Shell, operand: &(bool, String)) 
+-> Result<Variable, ShellError> {
+    let (quoted, word) = operand;
+    if *quoted {
+        Ok(Variable::Str(word.clone()))
+    } else if let Some(name) = word.strip_prefix('$') {
+        if let Some(var) = shell.get_variable(name) {
+            Ok(var)
+        } else {
+            Err(
+                ShellError::from(
+                    format!("oyster: variable {} not found", name)
+                )
+            )
+        }
+    } else {
+        Ok(Variable::from(word))
+    }
+}
+
+fn tokenize_sqbrkt(condition: &str) -> Result<Vec<CondToken>, ShellError> {
+    fn push_word(tokens: &mut Vec<CondToken>, word: &mut String) {
+        if word.is_empty() {
+            return
+        }
+        if let Some(eqtest) = get_valid_operator(word) {
+            tokens.push(CondToken::Op(eqtest));
+        } else {
+            tokens.push(CondToken::Word(false, word.clone()));
+        }
+        word.clear();
+    }
+
+    let mut tokens = Vec::new();
     let mut word = String::new();
-    for c in condition.trim().chars() {
+    let mut chars = condition.trim().chars().peekable();
+    while let Some(c) = chars.next() {
         match c {
             '"' => {
-                if !in_quote {
-                    in_quote = true;
-                } else {
-                    parsed.push((true, word.clone()));
-                    word.clear();
-                    in_quote = false;
+                push_word(&mut tokens, &mut word);
+                let mut quoted = String::new();
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some(c) => quoted.push(c),
+                        None => {
+                            return Err(
+                                ShellError::from("oyster: unmatched quote in square bracket")
+                            )
+                        }
+                    }
                 }
+                tokens.push(CondToken::Word(true, quoted));
             }
-            ' ' => if !in_quote {
-                if !word.is_empty() {
-                    parsed.push((false, word.clone()));
-                }
-                word.clear();
+            ' ' | '\t' => {
+                push_word(&mut tokens, &mut word);
+            }
+            '(' => {
+                push_word(&mut tokens, &mut word);
+                tokens.push(CondToken::LParen);
+            }
+            ')' => {
+                push_word(&mut tokens, &mut word);
+                tokens.push(CondToken::RParen);
+            }
+            '&' if chars.peek() == Some(&'&') => {
+                chars.next();
+                push_word(&mut tokens, &mut word);
+                tokens.push(CondToken::And);
+            }
+            '|' if chars.peek() == Some(&'|') => {
+                chars.next();
+                push_word(&mut tokens, &mut word);
+                tokens.push(CondToken::Or);
+            }
+            '!' if word.is_empty() && chars.peek() != Some(&'=') => {
+                tokens.push(CondToken::Not);
             }
             _ => {
                 word.push(c);
             }
         }
     }
-    if !word.is_empty() {
-        parsed.push((false, word));
+    push_word(&mut tokens, &mut word);
+    Ok(tokens)
+}
+
+/// Parses a square bracket condition.
+///
+/// `!` binds tightest, followed by `&&` and then `||`.
+/// Parentheses can be used to group conditions.
+fn parse_condition(condition: &str) -> Result<Condition, ShellError> {
+    let tokens = tokenize_sqbrkt(condition)?;
+    if tokens.is_empty() {
+        return Err(ShellError::from("oyster: empty square bracket"))
     }
-    if parsed.len() != 3 {
+    let mut tokens = tokens.into_iter().peekable();
+    let condition = parse_or(&mut tokens)?;
+    if let Some(token) = tokens.next() {
         return Err(
             ShellError::from(
-                "oyster: cannot parse square bracket"
+                format!("oyster: unexpected `{}` in square bracket", token)
             )
         )
     }
-    if parsed[1].0 {
-        return Err(
-            ShellError::from(
-                "oyster: middle operator is quoted"
-            )
-        )
+    Ok(condition)
+}
+
+type CondTokens = std::iter::Peekable<std::vec::IntoIter<CondToken>>;
+
+fn parse_or(tokens: &mut CondTokens) -> Result<Condition, ShellError> {
+    let mut lhs = parse_and(tokens)?;
+    while tokens.peek() == Some(&CondToken::Or) {
+        tokens.next();
+        let rhs = parse_and(tokens)?;
+        lhs = Condition::Or(Box::new(lhs), Box::new(rhs));
     }
-    let lhs = if parsed[0].0 {
-        Variable::Str(parsed[0].1.clone())
-    } else if parsed[0].1.starts_with("$") {
-        if let Some(var) = shell.get_variable(&parsed[0].1[1..]) {
-            var
-        } else {
-            return Err(
-                ShellError::from(
-                    format!("oyster: variable {} not found", &parsed[0].1[1..])
+    Ok(lhs)
+}
+
+fn parse_and(tokens: &mut CondTokens) -> Result<Condition, ShellError> {
+    let mut lhs = parse_unary(tokens)?;
+    while tokens.peek() == Some(&CondToken::And) {
+        tokens.next();
+        let rhs = parse_unary(tokens)?;
+        lhs = Condition::And(Box::new(lhs), Box::new(rhs));
+    }
+    Ok(lhs)
+}
+
+fn parse_unary(tokens: &mut CondTokens) -> Result<Condition, ShellError> {
+    match tokens.next() {
+        Some(CondToken::Not) => {
+            Ok(Condition::Not(Box::new(parse_unary(tokens)?)))
+        }
+        Some(CondToken::LParen) => {
+            let condition = parse_or(tokens)?;
+            if tokens.next() != Some(CondToken::RParen) {
+                return Err(
+                    ShellError::from("oyster: unmatched parenthesis in square bracket")
                 )
-            )
+            }
+            Ok(condition)
         }
-    } else {
-        Variable::from(&parsed[0].1)
-    };
-    let rhs = if parsed[2].0 {
-        Variable::Str(parsed[2].1.clone())
-    } else if parsed[2].1.starts_with("$") {
-        if let Some(var) = shell.get_variable(&parsed[2].1[1..]) {
-            var
-        } else {
-            return Err(
-                ShellError::from(
-                    format!("oyster: variable {} not found", &parsed[2].1[1..])
+        Some(CondToken::Word(quoted, lhs)) => {
+            if let Some(&CondToken::Op(eq)) = tokens.peek() {
+                tokens.next();
+                match tokens.next() {
+                    Some(CondToken::Word(rquoted, rhs)) => {
+                        Ok(Condition::Compare((quoted, lhs), eq, (rquoted, rhs)))
+                    }
+                    _ => Err(
+                        ShellError::from(
+                            format!("oyster: missing value after `{}`", CondToken::Op(eq))
+                        )
+                    ),
+                }
+            // a lone true or false can be used like any other condition
+            } else if !quoted && lhs == "true" {
+                Ok(Condition::Bool(true))
+            } else if !quoted && lhs == "false" {
+                Ok(Condition::Bool(false))
+            } else {
+                Err(
+                    ShellError::from(
+                        format!("oyster: expected an operator after `{}`", lhs)
+                    )
                 )
-            )
+            }
         }
-    } else {
-        Variable::from(&parsed[2].1)
-    };
-    let comparator = if let Some(eqtest) = get_valid_operator(&parsed[1].1) {
-        eqtest
-    } else {
-        return Err(
-            ShellError::from("oyster: invalid operator")
-        )
-    };
-    Ok((lhs, comparator, rhs))
+        Some(token) => Err(
+            ShellError::from(
+                format!("oyster: unexpected `{}` in square bracket", token)
+            )
+        ),
+        None => Err(
+            ShellError::from("oyster: unexpected end of square bracket")
+        ),
+    }
 }
 
 fn get_valid_operator(op: &str) -> Option<EqTest> {
@@ -524,7 +1015,7 @@ fn split_on_same_scope(raw: Vec<Job>) -> Vec<Vec<Job>> {
     let mut nesting_level: usize = 0;
     for job in raw {
         match job.cmds[0].cmd.1.as_str() {
-            "for" | "if" | "while" => {
+            "for" | "if" | "while" | "match" => {
                 buffer.push(job);
                 nesting_level += 1;
             }
@@ -547,9 +1038,37 @@ fn split_on_same_scope(raw: Vec<Job>) -> Vec<Vec<Job>> {
     to_return
 }
 
+/// Builds the condition of an `if` or `elif` statement.
+///
+/// A leading `!` negates the condition,
+/// in which case the returned bool is false.
+fn build_condition(job: Job) -> Result<(Job, bool), ShellError> {
+    let mut args = job.cmds[0].args[1..].to_vec();
+    let mut negated = false;
+    if args[0] == (Quote::NQuote, String::from("!")) {
+        args.remove(0);
+        negated = true;
+    } else if args[0].0 == Quote::NQuote && args[0].1.starts_with('!') {
+        args[0].1.remove(0);
+        negated = true;
+    }
+    if args.is_empty() {
+        return Err(ShellError::from("oyster: syntax error (no condition)"))
+    }
+    Ok((Job {
+        cmds: vec![TokenCmd {
+            cmd: args[0].clone(),
+            args,
+            redirects: job.cmds[0].redirects.clone(),
+            pipe_stderr: job.cmds[0].pipe_stderr,
+        }],
+        execnext: job.execnext,
+        id: job.id,
+    }, !negated))
+}
+
 /// Splits an if statement into if/elif/else blocks
 //TODO: optimise this (i.e. remove clones `urgh`)
-//TODO2: Fix ! condition
 fn split_on_branches(raw: Vec<Job>) 
              //condition    codeblock  
 -> Result<Vec<(Option<(Job, bool)>, Vec<Job>)>, ShellError> {
@@ -560,7 +1079,7 @@ fn split_on_branches(raw: Vec<Job>)
     let mut raw = raw.into_iter();
     while let Some(job) = raw.next() {
         match job.cmds[0].cmd.1.as_str() {
-            "for" | "while" => {
+            "for" | "while" | "match" => {
                 buffer.push(job);
                 nesting_level += 1;
             }
@@ -570,26 +1089,7 @@ fn split_on_branches(raw: Vec<Job>)
                     return Err(ShellError::from("oyster: syntax error (no condition)"))
                 }
                 if nesting_level == 0 {
-                    let command = if job.cmds[0].args[1].1.starts_with("!") {
-                        (
-                            job.cmds[0].args[1].0, 
-                            job.cmds[0].args[1].1[1..].to_string()
-                        )
-                    } else {
-                        job.cmds[0].args[1].clone()
-                    };
-                    condition = Some((Job {
-                        cmds: vec![TokenCmd {
-                            cmd: command,
-                            args: job.cmds[0].args[1..].to_vec(),
-                            redirects: job.cmds[0].redirects.clone(),
-                            pipe_stderr: job.cmds[0].pipe_stderr,
-                        }],
-                        execnext: job.execnext,
-                        id: job.id,
-                        },
-                        !job.cmds[0].args[1].1.starts_with("!"))
-                    );
+                    condition = Some(build_condition(job)?);
                 } else {
                     buffer.push(job);
                 }
@@ -610,26 +1110,7 @@ fn split_on_branches(raw: Vec<Job>)
                         (condition.clone(), 
                         buffer.clone()));
                     buffer.clear();
-                    let command = if job.cmds[0].args[1].1.starts_with("!") {
-                        (
-                            job.cmds[0].args[1].0, 
-                            job.cmds[0].args[1].1[1..].to_string()
-                        )
-                    } else {
-                        job.cmds[0].args[1].clone()
-                    };
-                    condition = Some((Job {
-                        cmds: vec![TokenCmd {
-                            cmd: command,
-                            args: job.cmds[0].args[1..].to_vec(),
-                            redirects: job.cmds[0].redirects.clone(),
-                            pipe_stderr: job.cmds[0].pipe_stderr,
-                        }],
-                        execnext: job.execnext,
-                        id: job.id,
-                        },
-                        !job.cmds[0].args[1].1.starts_with("!"))
-                    );
+                    condition = Some(build_condition(job)?);
                 } else {
                     buffer.push(job);
                 }
@@ -653,6 +1134,8 @@ fn split_on_branches(raw: Vec<Job>)
                 if nesting_level == 0 {
                     to_return.push((condition.clone(), buffer.clone()));
                     buffer.clear();
+                } else {
+                    buffer.push(job);
                 }
                 nesting_level -= 1;
             }
@@ -671,6 +1154,36 @@ fn split_on_branches(raw: Vec<Job>)
     Ok(to_return)
 }
 
+/// Splits a match statement into its arms
+fn split_on_arms(raw: Vec<Job>) 
+-> Result<Vec<MatchArm<Job>>, ShellError> {
+    let mut to_return = Vec::<MatchArm<Job>>::new();
+    let mut nesting_level: isize = 0;
+    for job in raw {
+        match job.cmds[0].cmd.1.as_str() {
+            "for" | "while" | "if" | "match" => {
+                nesting_level += 1;
+            }
+            "done" | "end" => {
+                nesting_level -= 1;
+            }
+            "case" if nesting_level == 0 => {
+                let patterns = Pattern::parse(&job.cmds[0].args[1..])?;
+                to_return.push((patterns, Vec::new()));
+                continue
+            }
+            _ => {}
+        }
+        match to_return.last_mut() {
+            Some((_, code)) => code.push(job),
+            None => {
+                return Err(ShellError::from("oyster: syntax error (expected `case`)"))
+            }
+        }
+    }
+    Ok(to_return)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -679,25 +1192,334 @@ mod tests {
     fn test_scriptfile_exec() {
         let mut shell = Shell::new();
         assert_eq!(
-            execute_scriptfile(&mut shell, "testscripts/test1").unwrap(),
+            execute_scriptfile(&mut shell, "testscripts/test1", Vec::new()).unwrap(),
             0
         )
     }
 
     #[test]
     fn test_sqbrkt_tokenizer() {
-        let teststring = String::from("$thing == \"hello\"");
-        let teststring2 = String::from("$number -ne 2");
+        use CondToken::*;
+        assert_eq!(
+            tokenize_sqbrkt("$thing == \"hello\"").unwrap(),
+            vec![
+                Word(false, String::from("$thing")),
+                Op(EqTest::Eq),
+                Word(true, String::from("hello")),
+            ]
+        );
+        assert_eq!(
+            tokenize_sqbrkt("!($number -ne 2)||$a != 1").unwrap(),
+            vec![
+                Not, LParen,
+                Word(false, String::from("$number")),
+                Op(EqTest::Ne),
+                Word(false, String::from("2")),
+                RParen, Or,
+                Word(false, String::from("$a")),
+                Op(EqTest::Ne),
+                Word(false, String::from("1")),
+            ]
+        );
+        assert!(tokenize_sqbrkt("$thing == \"hello").is_err());
+    }
+
+    #[test]
+    fn check_condition_evaluation() {
+        let mut shell = Shell::new();
+        shell.add_variable("a", Variable::from("2"));
+        shell.add_variable("b", Variable::from("x"));
+        let mut eval = |condition: &str| {
+            parse_condition(condition).and_then(|cond| cond.evaluate(&mut shell))
+        };
+        assert!(eval("$a > 1 && $b == \"x\"").unwrap());
+        assert!(!eval("$a > 1 && $b == \"y\"").unwrap());
+        assert!(eval("!($a == 1) || $b == \"y\"").unwrap());
+        // && binds tighter than ||
+        assert!(eval("true || false && false").unwrap());
+        assert!(!eval("(true || false) && false").unwrap());
+        // the right hand side is never evaluated
+        assert!(!eval("false && $missing == 1").unwrap());
+        assert!(eval("true || $missing == 1").unwrap());
+        assert!(eval("$a == \"2\"").is_err());
+        assert!(eval("($a == 2").is_err());
+        assert!(eval("$a == 2 &&").is_err());
+        assert!(eval("$a 2").is_err());
+    }
+
+    fn run_script(shell: &mut Shell, script: &str) -> i32 {
+        let tokens = match Lexer::tokenize(script) {
+            TokenizeResult::Good(tokens) => tokens,
+            result => panic!("could not tokenize script: {:?}", result),
+        };
+        match Lexer::parse_tokens(shell, tokens).unwrap() {
+            ParseResult::Good(jobs) => execute_jobs(shell, jobs, false).unwrap().0,
+            result => panic!("incomplete script: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn check_loop_control_flow() {
+        let mut shell = Shell::new();
+        run_script(&mut shell, "let int count = 0\n\
+            for i in [0..10]\n\
+            for j in [0..10]\n\
+            if [$j == 1]; continue; end\n\
+            if [$j == 3]; continue 2; end\n\
+            if [$i == 4]; break 2; end\n\
+            let count = [$count + 1]\n\
+            done\n\
+            done\n");
+        // j == 0 and j == 2 are counted for i in 0..4
+        assert_eq!(shell.get_variable("count"), Some(Variable::Int(8)));
+        assert_eq!(shell.control_flow, None);
+        assert_eq!(shell.loop_depth, 0);
+
+        run_script(&mut shell, "func early\n\
+            let count = 0\n\
+            while [true]; return 3; done\n\
+            let count = 1\n\
+            endfn\n");
+        assert_eq!(shell.execute_func("early", Vec::new()).unwrap().0, 3);
+        assert_eq!(shell.get_variable("count"), Some(Variable::Int(0)));
+        assert_eq!(shell.control_flow, None);
+
+        assert_eq!(run_script(&mut shell, "break\n"), 1);
+        assert_eq!(run_script(&mut shell, "return\n"), 1);
+    }
+
+    #[test]
+    fn check_builtins_in_pipelines() {
+        let mut shell = Shell::new();
+        run_script(&mut shell, "let first = 1 | cat\n\
+            echo hello | let last = 2\n\
+            alias piped = cat | cat\n");
+        // builtins anywhere in the pipeline run in the shell
+        assert_eq!(shell.get_variable("first"), Some(Variable::Int(1)));
+        assert_eq!(shell.get_variable("last"), Some(Variable::Int(2)));
+        assert_eq!(shell.get_alias("piped"), Some(String::from("cat")));
+
+        // a builtin writing more than a pipe holds to another does not block
+        let script = format!("echo {} | let big = 1\n", "x".repeat(100_000));
+        assert_eq!(run_script(&mut shell, &script), 0);
+        assert_eq!(shell.get_variable("big"), Some(Variable::Int(1)));
+        let script = format!("echo {} | cat | read long\n", "x".repeat(100_000));
+        assert_eq!(run_script(&mut shell, &script), 0);
+        assert_eq!(shell.get_variable("long"), Some(Variable::from("x".repeat(100_000))));
+
+        // but not when the pipeline is in the background
+        run_script(&mut shell, "let background = 1 | cat &\n");
+        run_script(&mut shell, "wait\n");
+        assert_eq!(shell.get_variable("background"), None);
+
+        // the last command in the pipeline sets the status
+        assert_eq!(run_script(&mut shell, "false | let status = 0\n"), 0);
+        assert_eq!(run_script(&mut shell, "let status = 0 | false\n"), 1);
+        assert_eq!(run_script(&mut shell, "true | unset nothing\n"), 2);
+    }
+
+    #[test]
+    fn check_eval_and_source() {
+        let mut shell = Shell::new();
+        assert_eq!(run_script(&mut shell, "eval let evaluated = 3\n"), 0);
+        assert_eq!(shell.get_variable("evaluated"), Some(Variable::Int(3)));
+        assert_eq!(run_script(&mut shell, "eval 'true; false'\n"), 1);
+
+        let path = std::env::temp_dir().join(
+            format!("oyster-sourcetest-{}", std::process::id())
+        );
+        std::fs::write(&path, "let sourced = $1\nlet count = $#\n").unwrap();
+        let script = format!("source {} hello world\n", path.display());
+        assert_eq!(run_script(&mut shell, &script), 0);
+        std::fs::remove_file(&path).unwrap();
+
+        // variables set by the script are kept, but its parameters are not
+        assert_eq!(shell.get_variable("sourced"), Some(Variable::from("hello")));
+        assert_eq!(shell.get_variable("count"), Some(Variable::Int(2)));
+        assert_eq!(shell.get_variable("1"), None);
+        assert_eq!(run_script(&mut shell, "source /nonexistent\n"), 1);
+    }
+
+    #[test]
+    fn check_waiting_on_jobs() {
         let mut shell = Shell::new();
-        shell.add_variable("thing", Variable::from("hello"));
-        shell.add_variable("number", Variable::from("2"));
+        assert_eq!(run_script(&mut shell, "sh -c 'exit 3' & wait %1\n"), 3);
+        assert_eq!(run_script(&mut shell, "sh -c 'exit 4' & wait %sh\n"), 4);
+        run_script(&mut shell, "sleep 0.1 & wait\n");
+        assert!(shell.jobs.is_empty());
+        assert_eq!(run_script(&mut shell, "wait %1\n"), 127);
+    }
+
+    #[test]
+    fn check_job_state_changes() {
+        use std::{panic, process, thread};
+        use std::time::Duration;
+        use nix::sys::signal::{kill, Signal};
+        use nix::sys::wait::{waitpid, WaitStatus};
+        use nix::unistd::ForkResult;
+        use crate::jobc;
+        use crate::types::JobStatus;
+
+        //update_jobs reaps any child, so this runs in a process of its own
+        //where it cannot take the children of the other tests
+        match unistd::fork().unwrap() {
+            ForkResult::Child => {
+                let result = panic::catch_unwind(|| {
+                    let mut shell = Shell::new();
+                    run_script(&mut shell, "sleep 5 &\n");
+                    let pid = shell.jobs.values().next().unwrap().pids[0];
+                    let changed = |shell: &mut Shell| {
+                        for _ in 0..200 {
+                            if let Some(job) = jobc::update_jobs(shell).pop() {
+                                return job.status
+                            }
+                            thread::sleep(Duration::from_millis(10));
+                        }
+                        panic!("job did not change state");
+                    };
+                    kill(pid, Signal::SIGSTOP).unwrap();
+                    assert_eq!(changed(&mut shell), JobStatus::Stopped);
+                    kill(pid, Signal::SIGCONT).unwrap();
+                    assert_eq!(changed(&mut shell), JobStatus::Running);
+                    assert!(shell.jobs.values().all(|job| job.status == JobStatus::Running));
+                    kill(pid, Signal::SIGTERM).unwrap();
+                    assert_eq!(changed(&mut shell), JobStatus::Signaled(Signal::SIGTERM));
+                    assert!(shell.jobs.is_empty());
+                });
+                process::exit(if result.is_ok() {0} else {1});
+            }
+            ForkResult::Parent {child} => {
+                assert_eq!(waitpid(child, None).unwrap(), WaitStatus::Exited(child, 0));
+            }
+        }
+    }
+
+    #[test]
+    fn check_pipeline_statuses() {
+        let mut shell = Shell::new();
+        let pipestatus = |shell: &Shell| match shell.get_variable("pipestatus") {
+            Some(Variable::Arr(statuses)) => statuses,
+            other => panic!("pipestatus not set: {:?}", other),
+        };
+        assert_eq!(run_script(&mut shell, "false | sh -c 'exit 3' | true\n"), 0);
+        assert_eq!(pipestatus(&shell), vec![
+            Variable::Int(1), Variable::Int(3), Variable::Int(0)
+        ]);
+        // killed by a signal
+        assert_eq!(run_script(&mut shell, "true | sh -c 'kill -9 $$'\n"), 137);
+        assert_eq!(pipestatus(&shell), vec![Variable::Int(0), Variable::Int(137)]);
+
+        run_script(&mut shell, "setopt pipefail\n");
+        assert_eq!(run_script(&mut shell, "false | sh -c 'exit 3' | true\n"), 3);
+        assert_eq!(run_script(&mut shell, "false | let x = 1\n"), 1);
+        assert_eq!(run_script(&mut shell, "false | true & wait %1\n"), 1);
+        run_script(&mut shell, "unsetopt pipefail\n");
+        assert_eq!(run_script(&mut shell, "false | true\n"), 0);
+    }
+
+    #[test]
+    fn check_shell_options() {
+        let mut shell = Shell::new();
+        assert_eq!(run_script(&mut shell, "set -eu -o pipefail +e\n"), 0);
+        assert!(shell.options.nounset && shell.options.pipefail);
+        assert!(!shell.options.errexit);
+        assert_eq!(run_script(&mut shell, "set -q\n"), 2);
+        assert_eq!(run_script(&mut shell, "set -o nosuchoption\n"), 2);
+
+        assert_eq!(run_script(&mut shell, "eval 'echo $nosuchvar'\n"), 1);
+        run_script(&mut shell, "set +u\n");
+        assert_eq!(run_script(&mut shell, "eval 'echo $nosuchvar'\n"), 0);
+
+        run_script(&mut shell, "set -f\n");
+        run_script(&mut shell, "eval 'let pattern = /tm*'\n");
+        assert_eq!(shell.get_variable("pattern"), Some(Variable::from("/tm*")));
+        run_script(&mut shell, "set +f\n");
+
+        let path = std::env::temp_dir().join(
+            format!("oyster-clobbertest-{}", std::process::id())
+        );
+        std::fs::write(&path, "kept\n").unwrap();
+        run_script(&mut shell, "setopt noclobber\n");
+        let script = format!("echo lost > {}\n", path.display());
+        assert_eq!(run_script(&mut shell, &script), 1);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "kept\n");
+        let script = format!("echo forced >| {}\n", path.display());
+        assert_eq!(run_script(&mut shell, &script), 0);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "forced\n");
+        std::fs::remove_file(&path).unwrap();
+        let script = format!("echo created > {}\n", path.display());
+        assert_eq!(run_script(&mut shell, &script), 0);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "created\n");
+        assert_eq!(run_script(&mut shell, "echo gone > /dev/null\n"), 0);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn check_read_builtin() {
+        let mut shell = Shell::new();
+        run_script(&mut shell, "echo '  sam 42  1.5 and the rest ' | read name age ratio rest\n\
+            echo '007' | read str code\n\
+            echo '1 2 three' | read -a list\n\
+            echo 'abcdef' | read -n 3 -d c short\n");
+        assert_eq!(shell.get_variable("name"), Some(Variable::from("sam")));
+        assert_eq!(shell.get_variable("age"), Some(Variable::Int(42)));
+        assert_eq!(shell.get_variable("ratio"), Some(Variable::Flt(1.5)));
+        assert_eq!(shell.get_variable("rest"), Some(Variable::from("and the rest")));
+        assert_eq!(shell.get_variable("code"), Some(Variable::Str(String::from("007"))));
+        assert_eq!(shell.get_variable("list"), Some(Variable::Arr(vec![
+            Variable::Int(1), Variable::Int(2), Variable::from("three")
+        ])));
+        assert_eq!(shell.get_variable("short"), Some(Variable::from("ab")));
+
+        shell.add_variable("IFS", Variable::from(":"));
+        run_script(&mut shell, "echo 'a:b::d' | read first second third fourth\n");
+        assert_eq!(shell.get_variable("third"), Some(Variable::from("")));
+        assert_eq!(shell.get_variable("fourth"), Some(Variable::from("d")));
+
+        // end of file before a newline
+        assert_eq!(run_script(&mut shell, "echo -n partial | read\n"), 1);
+        assert_eq!(shell.get_variable("REPLY"), Some(Variable::from("partial")));
+    }
+
+    #[test]
+    fn check_match_patterns() {
+        let mut shell = Shell::new();
+        shell.add_variable("num", Variable::Int(7));
+        let patterns = Pattern::parse(&[
+            (Quote::SqBrkt, String::from("1..=5")),
+            (Quote::NQuote, String::from("*.txt")),
+            (Quote::NQuote, String::from("re")),
+            (Quote::SQuote, String::from("^a+b$")),
+            (Quote::DQuote, String::from("7")),
+            (Quote::Variable, String::from("$num")),
+            (Quote::NQuote, String::from("_")),
+        ]).unwrap();
+        assert_eq!(patterns.len(), 6);
+        let matching = |shell: &mut Shell, value: Variable| -> Vec<bool> {
+            patterns.iter()
+                .map(|pattern| pattern.matches(shell, &value).unwrap())
+                .collect()
+        };
         assert_eq!(
-            tokenize_sqbrkt(&mut shell, teststring).unwrap(),
-            (Variable::from("hello"), EqTest::Eq, Variable::Str(String::from("hello")))
+            matching(&mut shell, Variable::Int(5)),
+            vec![true, false, false, false, false, true]
         );
         assert_eq!(
-            tokenize_sqbrkt(&mut shell, teststring2).unwrap(),
-            (Variable::from("2"), EqTest::Ne, Variable::Int(2))
-        )
+            matching(&mut shell, Variable::from("notes.txt")),
+            vec![false, true, false, false, false, true]
+        );
+        assert_eq!(
+            matching(&mut shell, Variable::from("aaab")),
+            vec![false, false, true, false, false, true]
+        );
+        assert_eq!(
+            matching(&mut shell, Variable::Int(7)),
+            vec![false, false, false, false, true, true]
+        );
+        assert_eq!(
+            matching(&mut shell, Variable::Str(String::from("7"))),
+            vec![false, false, false, true, false, true]
+        );
+        assert!(Pattern::parse(&[(Quote::NQuote, String::from("re"))]).is_err());
     }
-}
\ No newline at end of file
+}
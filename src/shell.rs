@@ -1,7 +1,8 @@
 use std::collections::{HashMap, BTreeMap};
 use std::path::{PathBuf};
-use std::fs::{OpenOptions, File};
-use std::os::unix::io::IntoRawFd;
+use std::fs::{self, OpenOptions, File};
+use std::io::{self, Write, Seek, SeekFrom};
+use std::os::unix::io::{IntoRawFd, FromRawFd, RawFd};
 use std::env;
 
 use regex::Regex;
@@ -9,7 +10,11 @@ use regex::Regex;
 use nix::unistd::{
     Pid,
     tcsetpgrp,
+    pipe,
+    close,
+    mkstemp,
 };
+use nix::fcntl::{fcntl, FcntlArg, FdFlag};
 use nix::sys::signal::{
     Signal,
     SigSet,
@@ -22,32 +27,45 @@ use crate::types::{
     JobTrack,
     Variable as Var,
     Map,
-    Operator,
     Function,
-    UnwrapOr,
+    ControlFlow,
     JobStatus,
     ShellError,
+    ShellOptions,
 };
-use crate::expansion::index_into;
+use crate::arithmetic;
+use crate::history::History;
+use crate::namespace::Namespaces;
 use crate::execute;
-use crate::scripting::execute_scriptfile;
 
 #[derive(Clone, Debug)]
 pub struct Shell {
     pub(crate) jobs: BTreeMap<i32, JobTrack>,
+    /// Ids of stopped and background jobs, the current job last.
+    recent_jobs: Vec<i32>,
     aliases: HashMap<String, String>,
     env: HashMap<String, String>,
     cmds: HashMap<String, PathBuf>,
     vars: HashMap<String, Var>,
+    scopes: Vec<HashMap<String, Var>>,
     maps: HashMap<String, Map>,
     funcs: HashMap<String, Function>,
     max_nesting: usize,
     stack_size: usize,
+    pub(crate) loop_depth: usize,
+    pub(crate) control_flow: Option<ControlFlow>,
+    /// How many constructs the last parse left unfinished.
+    pub(crate) parse_depth: usize,
     pub(crate) dirstack: Vec<PathBuf>,
     pub current_dir: PathBuf,
     pub prev_dir: PathBuf,
-    pgid: i32,
+    /// The process group commands are started in,
+    /// or 0 to start each pipeline in a group of its own.
+    pub(crate) pgid: Pid,
     pub is_login: bool,
+    pub(crate) options: ShellOptions,
+    pub(crate) history: History,
+    pub(crate) namespaces: Namespaces,
 }
 
 #[allow(dead_code)]
@@ -57,19 +75,27 @@ impl Shell {
         let pwd = env::var("PWD").unwrap_or(String::new());
         Shell {
             jobs: BTreeMap::new(),
+            recent_jobs: Vec::new(),
             aliases: HashMap::new(),
             env: HashMap::new(),
             cmds: HashMap::new(),
             vars: HashMap::new(),
+            scopes: Vec::new(),
             maps: HashMap::new(),
             funcs: HashMap::new(),
             max_nesting: 50,
             stack_size: 0,
+            loop_depth: 0,
+            control_flow: None,
+            parse_depth: 0,
             dirstack: Vec::new(),
             current_dir: PathBuf::from(pwd),
             prev_dir: PathBuf::from(home),
-            pgid: 0,
+            pgid: Pid::from_raw(0),
             is_login: false,
+            options: ShellOptions::default(),
+            history: History::new(),
+            namespaces: Namespaces::new(),
         }
     }
     pub fn change_dir<P: Into<PathBuf>>(&mut self, cd_to: P) -> Result<(), ShellError> {
@@ -89,49 +115,56 @@ impl Shell {
             }
         }
     }
-    /// Adds a job to the shell to track.
-    /// Normally only used for background jobs.
+    /// Adds a process to the job with process group `pgid`,
+    /// creating the job if there isn't one yet. Returns the job id.
     pub fn add_cmd_to_job(
         &mut self, 
-        id: i32, 
         pid: Pid, 
         pgid: Pid, 
         cmd: String,
         bg: bool,
-    ) {
-        if let Some(job) = self.jobs.get_mut(&id) {
+    ) -> i32 {
+        if let Some(job) = self.jobs.values_mut().find(|job| job.pgid == pgid) {
             job.pids.push(pid);
-            return;
-        } else {
-            self.jobs.insert(id, JobTrack {
-                firstcmd: cmd,
-                id: id,
-                pgid: pgid,
-                pids: vec![pid],
-                status: JobStatus::Running,
-                background: bg,
-            });
-        }
-    }
-    /// Returns the job. Returns None if it doesn't exist.
-    pub fn remove_pid_from_job(&mut self, pid: Pid, pgid: Pid) 
-    -> Option<JobTrack> {
-        let mut pids_empty = false;
-        let mut jobid = 0;
-        for (id, job) in self.jobs.iter_mut() {
-            if pgid == job.pgid {
-                if let Ok(idx) = job.pids.binary_search(&pid) {
-                    job.pids.remove(idx);
-                }
-                pids_empty = job.pids.is_empty();
-                jobid = *id;
-            }
-        }
-        if pids_empty {
-            return self.jobs.remove(&jobid);
+            job.statuses.push(None);
+            return job.id;
+        }
+        let id = self.jobs.keys().next_back().map_or(1, |id| id + 1);
+        self.jobs.insert(id, JobTrack {
+            firstcmd: cmd,
+            id,
+            pgid,
+            pids: vec![pid],
+            statuses: vec![None],
+            status: JobStatus::Running,
+            background: bg,
+            nohup: false,
+        });
+        if bg {
+            self.make_current_job(id);
+        }
+        id
+    }
+    /// Records the exit status of a process in a job. Once every process
+    /// in the job has finished, the job is removed and returned.
+    pub fn finish_process(&mut self, pid: Pid, status: i32) -> Option<JobTrack> {
+        let job = self.jobs.values_mut().find(|job| job.pids.contains(&pid))?;
+        let idx = job.pids.iter().position(|job_pid| *job_pid == pid)?;
+        //a process can only finish once
+        if job.statuses[idx].is_none() {
+            job.statuses[idx] = Some(status);
+        }
+        if job.statuses.iter().all(Option::is_some) {
+            let id = job.id;
+            return self.remove_job(id);
         }
         None
     }
+    /// Stops tracking a job, returning it if it existed.
+    pub fn remove_job(&mut self, id: i32) -> Option<JobTrack> {
+        self.recent_jobs.retain(|recent| *recent != id);
+        self.jobs.remove(&id)
+    }
 
     pub fn get_job_by_pgid(&mut self, pgid: Pid) -> Option<&JobTrack> {
         for job in &self.jobs {
@@ -145,10 +178,67 @@ impl Shell {
     pub fn get_job_by_id(&mut self, id: i32) -> Option<&JobTrack> {
         self.jobs.get(&id)
     }
+    /// Finds the job referred to by a job spec:
+    /// - `%%`, `%+` or `%` for the current job,
+    /// - `%-` for the previous job,
+    /// - `%<n>` for job `n`,
+    /// - `%<name>` for the job whose command starts with `name`,
+    /// - `%?<substr>` for the job whose command contains `substr`.
+    pub fn find_job(&self, spec: &str) -> Result<i32, ShellError> {
+        let pattern = match spec.strip_prefix('%') {
+            Some(pattern) => pattern,
+            None => return Err(ShellError::from(format!("{}: not a job spec", spec))),
+        };
+        let found = match pattern {
+            "" | "%" | "+" => self.current_job(),
+            "-" => self.previous_job(),
+            _ => if let Ok(id) = pattern.parse::<i32>() {
+                Some(id).filter(|id| self.jobs.contains_key(id))
+            } else {
+                let matches: Vec<i32> = self.jobs.values().filter(|job| {
+                    match pattern.strip_prefix('?') {
+                        Some(substr) => job.firstcmd.contains(substr),
+                        None => job.firstcmd.starts_with(pattern),
+                    }
+                }).map(|job| job.id).collect();
+                if matches.len() > 1 {
+                    return Err(ShellError::from(format!("{}: ambiguous job spec", spec)))
+                }
+                matches.first().copied()
+            }
+        };
+        found.ok_or_else(|| ShellError::from(format!("{}: no such job", spec)))
+    }
+    /// The job that `fg` and `bg` act on by default;
+    /// the last job to be stopped or put in the background.
+    pub fn current_job(&self) -> Option<i32> {
+        self.recent_jobs.last().copied()
+    }
+    /// The job that was current before the current job.
+    pub fn previous_job(&self) -> Option<i32> {
+        self.recent_jobs.iter().rev().nth(1).copied()
+    }
+    /// Returns `+` for the current job, `-` for the previous job
+    /// and a space for any other job, as shown by `jobs`.
+    pub fn job_marker(&self, id: i32) -> char {
+        if self.current_job() == Some(id) {
+            '+'
+        } else if self.previous_job() == Some(id) {
+            '-'
+        } else {
+            ' '
+        }
+    }
+
+    fn make_current_job(&mut self, id: i32) {
+        self.recent_jobs.retain(|recent| *recent != id);
+        self.recent_jobs.push(id);
+    }
 
     pub fn mark_job_as_stopped(&mut self, id: i32) {
         if let Some(job) = self.jobs.get_mut(&id) {
             job.status = JobStatus::Stopped;
+            self.make_current_job(id);
         }
     }
 
@@ -157,6 +247,7 @@ impl Shell {
             job.status = JobStatus::Running;
             if bg {
                 job.background = true;
+                self.make_current_job(id);
             }
         }
     }
@@ -193,26 +284,46 @@ impl Shell {
                     )
                 }
             }
-            let mut counter = 0;
-            for param in params {
-                let varname = format!("{}{}", name, counter);
-                self.add_variable(&varname, Var::from(param));
-                counter += 1;
+            // parameters are local to the function
+            self.push_params(params);
+            // loops outside the function cannot be broken out of
+            let loop_depth = std::mem::replace(&mut self.loop_depth, 0);
+            let mut result = execute::execute_jobs(self, jobs_to_do, false);
+            self.loop_depth = loop_depth;
+            if let Some(ControlFlow::Return(status)) = self.control_flow.take() {
+                result = result.map(|(_, captured)| (status, captured));
             }
-            let result = execute::execute_jobs(self, jobs_to_do, false);
+            self.pop_scope();
             if self.stack_size > 0{
                 self.stack_size -= 1;
             }
-            for i in 0..counter {
-                let varname = format!("{}{}", name, i);
-                self.remove_variable(&varname);
-            }
             result
         } else {
             let msg = format!("oyster: no function `{}` found", name);
             return Err(ShellError::from(msg))
         }
     }
+    /// Pushes a new scope holding `params` as the
+    /// positional parameters $1..$n, $@ and $#.
+    pub fn push_params(&mut self, params: Vec<String>) {
+        let mut scope = HashMap::new();
+        scope.insert(String::from("#"), Var::Int(params.len() as i64));
+        for (i, param) in params.iter().enumerate() {
+            scope.insert((i + 1).to_string(), Var::from(param));
+        }
+        scope.insert(String::from("@"), Var::Arr(
+            params.into_iter().map(Var::Str).collect()
+        ));
+        self.scopes.push(scope);
+    }
+    /// Removes the innermost scope, and the variables in it.
+    pub fn pop_scope(&mut self) {
+        self.scopes.pop();
+    }
+    /// Returns true if the shell is currently executing a function.
+    pub fn in_func(&self) -> bool {
+        self.stack_size > 0
+    }
     /// Gives a reference to the shell environment.
     pub fn env(&self) -> &HashMap<String, String> {
         &self.env
@@ -299,42 +410,72 @@ impl Shell {
         }
     }
     /// Adds a variable to the shell.
+    ///
+    /// If the variable is local to a function being executed,
+    /// the local variable is changed instead of the global one.
     pub fn add_variable(&mut self, key: &str, value: Var) {
-        self.vars.insert(key.to_string(), value);
+        if let Some(scope) = self.scopes.iter_mut().rev()
+            .find(|scope| scope.contains_key(key)) {
+            scope.insert(key.to_string(), value);
+        } else {
+            //a global variable replaces a map of the same name
+            self.maps.remove(key);
+            self.vars.insert(key.to_string(), value);
+        }
     }
-    /// Gets the value of a variable from the shell without removing it.
-    pub fn get_variable(&self, key: &str) -> Option<Var> {
-        if let Some(entry) = self.vars.get(key) {
-            Some(entry.clone())
+    /// Adds a variable to the innermost scope.
+    /// Fails if the shell is not executing a function.
+    pub fn add_local(&mut self, key: &str, value: Var) -> Result<(), ShellError> {
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.insert(key.to_string(), value);
+            Ok(())
         } else {
-            None
+            Err(ShellError::from("oyster: local: can only be used in a function"))
         }
     }
+    /// Gets the value of a variable from the shell without removing it.
+    ///
+    /// Local variables are searched first, from the innermost scope outwards.
+    pub fn get_variable(&self, key: &str) -> Option<Var> {
+        self.scopes.iter().rev()
+            .find_map(|scope| scope.get(key))
+            .or_else(|| self.vars.get(key))
+            .cloned()
+    }
     /// Removes a variable from the shell.
     pub fn remove_variable(&mut self, key: &str) -> Option<String> {
+        if let Some(scope) = self.scopes.iter_mut().rev()
+            .find(|scope| scope.contains_key(key)) {
+            return scope.remove(key).map(|var| var.to_string())
+        }
         self.vars.remove(key).map(|var| {
             var.to_string()
         })
     }
-    /// Loads in a config file and applies it to the shell.
-    /// Internally calls the run_script function in execute.
-    pub fn with_config(filename: &str) -> Self {
+    /// Adds a map to the shell, replacing any variable with the same name.
+    pub fn add_map(&mut self, key: &str, map: Map) {
+        self.vars.remove(key);
+        self.maps.insert(key.to_string(), map);
+    }
+    /// Gets a reference to a map in the shell.
+    pub fn get_map(&self, key: &str) -> Option<&Map> {
+        self.maps.get(key)
+    }
+    /// Gets a mutable reference to a map in the shell.
+    pub fn get_map_mut(&mut self, key: &str) -> Option<&mut Map> {
+        self.maps.get_mut(key)
+    }
+    /// Removes a map from the shell.
+    pub fn remove_map(&mut self, key: &str) -> Option<Map> {
+        self.maps.remove(key)
+    }
+    /// Creates a shell with the variables of the environment it was run in.
+    pub fn from_env() -> Self {
         let mut shell = Shell::new();
         //todo FIXME: variables not registering with shell
         for (var, value) in env::vars() {
             shell.add_variable(&var, Var::Str(value));
         }
-        match execute_scriptfile(&mut shell, filename) {
-            Ok(status) => {
-                if status != 0 {
-                    eprintln!("oyster: error occurred while running rcfile");
-                }
-            },
-            Err(e) => {
-                eprintln!("{}", e);
-                eprintln!("oyster: cannot start shell with config");
-            }
-        }
         shell
     }
 }
@@ -360,22 +501,98 @@ pub fn give_terminal_to(pgid: Pid) -> nix::Result<bool> {
     Ok(true)
 }
 
-pub fn create_fd_from_file(dest: &str, to_append: bool) -> i32 {
+pub fn create_fd_from_file(dest: &str, to_append: bool) -> io::Result<i32> {
     let mut file = OpenOptions::new();
     if to_append {
         file.append(true);
     } else {
         file.write(true).truncate(true);
     }
-    let file = file.create(true).open(dest)
-        .unwrap_or_exit("oyster: could not create file", 3);
-    file.into_raw_fd()
+    let file = file.create(true).open(dest)?;
+    Ok(file.into_raw_fd())
+}
+
+/// Creates `dest` for `>` under `noclobber`, failing with `AlreadyExists`
+/// if it is an existing regular file. Other files that already exist,
+/// such as `/dev/null`, are opened for writing as usual.
+pub fn create_new_fd_from_file(dest: &str) -> io::Result<i32> {
+    match OpenOptions::new().write(true).create_new(true).open(dest) {
+        Ok(file) => Ok(file.into_raw_fd()),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+            //a dangling symlink counts as a file, so nothing is created through it
+            if fs::metadata(dest).map(|meta| meta.is_file()).unwrap_or(true) {
+                return Err(e)
+            }
+            Ok(OpenOptions::new().write(true).open(dest)?.into_raw_fd())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+pub fn open_file_as_fd(dest: &str) -> io::Result<i32> {
+    Ok(File::open(dest)?.into_raw_fd())
+}
+
+/// Opens the file for both reading and writing, creating it if needed.
+pub fn open_file_rw_as_fd(dest: &str) -> io::Result<i32> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(dest)?;
+    Ok(file.into_raw_fd())
+}
+
+/// Here-documents up to this size are fed through a pipe, which
+/// can hold them without blocking; larger ones go in a temporary file.
+const HEREDOC_PIPE_MAX: usize = 4096;
+
+/// Returns a file descriptor to read the body of a here-document from.
+///
+/// Small bodies are written to a pipe. Larger ones are written to a
+/// temporary file created with `mkstemp`, which is unlinked straight away.
+pub fn heredoc_as_fd(body: &str) -> io::Result<i32> {
+    if body.len() <= HEREDOC_PIPE_MAX {
+        let (read_end, write_end) = pipe().map_err(nix_to_io)?;
+        let mut writer = unsafe { File::from_raw_fd(write_end) };
+        if let Err(e) = writer.write_all(body.as_bytes()) {
+            let _ = close(read_end);
+            return Err(e);
+        }
+        return Ok(read_end)
+    }
+    let template = env::temp_dir().join("oyster-heredoc-XXXXXX");
+    let (fd, path) = mkstemp(&template).map_err(nix_to_io)?;
+    let mut file = unsafe { File::from_raw_fd(fd) };
+    fs::remove_file(&path)?;
+    file.write_all(body.as_bytes())?;
+    file.seek(SeekFrom::Start(0))?;
+    Ok(file.into_raw_fd())
 }
 
-pub fn open_file_as_fd(dest: &str) -> i32 {
-    File::open(dest)
-    .unwrap_or_exit("oyster: could not open file", 3)
-    .into_raw_fd()
+/// Creates an unlinked temporary file to stand in for a pipe, returning
+/// a file descriptor that reads it from the start and one that writes it.
+/// Both are closed on exec.
+///
+/// Unlike a pipe, the writer never blocks, however much it writes
+/// before the reader starts.
+pub fn buffer_as_fds() -> io::Result<(RawFd, RawFd)> {
+    let template = env::temp_dir().join("oyster-pipe-XXXXXX");
+    let (fd, path) = mkstemp(&template).map_err(nix_to_io)?;
+    let writer = unsafe { File::from_raw_fd(fd) };
+    let reader = File::open(&path);
+    fs::remove_file(&path)?;
+    let reader = reader?;
+    fcntl(fd, FcntlArg::F_SETFD(FdFlag::FD_CLOEXEC)).map_err(nix_to_io)?;
+    Ok((reader.into_raw_fd(), writer.into_raw_fd()))
+}
+
+pub(crate) fn nix_to_io(e: nix::Error) -> io::Error {
+    match e.as_errno() {
+        Some(errno) => io::Error::from_raw_os_error(errno as i32),
+        None => io::Error::new(io::ErrorKind::InvalidInput, e),
+    }
 }
 
 //steps:
@@ -397,222 +614,18 @@ pub fn assign_variables(shell: &mut Shell, string: &mut String) -> bool {
     false
 }
 
+/// Evaluates an arithmetic expression inside a square bracket.
+///
+/// If the contents are not an operation, the square bracket is not expanded
+/// and its contents are returned with the surrounding brackets.
 pub fn eval_sqbrkt(shell: &mut Shell, string: String)
 -> Result<Var, ShellError> {
-    let string_error: &'static str = 
-    "oyster: operators other than `+` are not supported for strings";
-    let (lhs, op, rhs) = match tokenize_sqbrkt(shell, &string) {
-        Ok(ops) => ops,
-        Err(_) => return Ok(Var::Str(format!("[{}]", string)))
-    };
-    if Var::types_match(&lhs, &rhs) {
-        match lhs {
-            Var::Str(string) => {
-                if let Var::Str(string2) = rhs {
-                    if let Operator::Add = op {
-                        Ok(Var::Str(string + &string2))
-                    } else {
-                        Err(ShellError::from(string_error))
-                    }
-                } else {
-                    unreachable!()
-                }
-            }
-            Var::Int(number) => {
-                if let Var::Int(number2) = rhs {
-                    Ok(Var::Int(perform_ops_int(number, op, number2)?))
-                } else {
-                    unreachable!()
-                }
-            }
-            Var::Flt(float)  => {
-                if let Var::Flt(float2) = rhs {
-                    Ok(Var::Flt(perform_ops_flt(float, op, float2)?))
-                } else {
-                    unreachable!()
-                }
-            }
-            Var::Arr(_) => {
-                Err(ShellError::from("oyster: cannot operate on arrays"))
-            }
-        }
-    } else {
-        Err(ShellError::from("oyster: mismatched variable types (operator evaluation)"))
-    }
-}
-
-//Note: I wanted to do generics here but the kind of trait bounds
-//required to use for the items I wanted to return are not supported by Rust.
-fn perform_ops_int(lhs: i64, op: Operator, rhs: i64)
--> Result<i64, ShellError> {
-    use Operator::*;
-    let unsupported_err: &'static str = 
-    "oyster: assignment operators are currently unsupported";
-    match op {
-        Add => {
-            if lhs == i64::MAX {
-                return Err(ShellError::from("error: variable overflow"))
-            }
-            Ok(lhs + rhs)
-        }
-        Sub => {
-            if lhs == i64::MIN {
-                return Err(ShellError::from("error: variable underflow"))
-            }
-            Ok(lhs - rhs)
-        }
-        Mul => {
-            if lhs as i128 * rhs as i128 >= i64::MAX as i128 {
-                return Err(ShellError::from("error: variable overflow"))
-            }
-            Ok(lhs * rhs)
-        }
-        Div => {
-            Ok(lhs / rhs)
-        }
-        _ => {
-            Err(ShellError::from(unsupported_err))
-        }
+    match arithmetic::parse(&string) {
+        Ok(expr) if expr.is_operation() => expr.evaluate(shell),
+        _ => Ok(Var::Str(format!("[{}]", string))),
     }
 }
 
-fn perform_ops_flt(lhs: f64, op: Operator, rhs: f64)
--> Result<f64, ShellError> {
-    use Operator::*;
-    let unsupported_err: &'static str = 
-    "oyster: assignment operators are currently unsupported";
-    match op {
-        Add => {
-            if lhs == f64::MAX {
-                return Err(ShellError::from("error: variable overflow"))
-            }
-            Ok(lhs + rhs)
-        }
-        Sub => {
-            if lhs == f64::MIN {
-                return Err(ShellError::from("error: variable underflow"))
-            }
-            Ok(lhs - rhs)
-        }
-        Mul => {
-            if lhs as i128 * rhs as i128 >= f64::MAX as i128 {
-                return Err(ShellError::from("error: variable overflow"))
-            }
-            Ok(lhs * rhs)
-        }
-        Div => {
-            Ok(lhs / rhs)
-        }
-        _ => {
-            Err(ShellError::from(unsupported_err))
-        }
-    }
-}
-
-fn tokenize_sqbrkt(shell: &mut Shell, string: &str)
--> Result<(Var, Operator, Var), ShellError> {
-    let mut in_quote = false;
-    let mut parsed: Vec<(bool, String)> = Vec::new();
-    let mut word = String::new();
-    for c in string.chars() {
-        match c {
-            '"' => {
-                if !in_quote {
-                    in_quote = true;
-                } else {
-                    parsed.push((true, word.clone()));
-                    word.clear();
-                    in_quote = false;
-                }
-            }
-            ' ' => if !in_quote {
-                if !word.is_empty() {
-                    parsed.push((false, word.clone()));
-                }
-                word.clear();
-            }
-            _ => {
-                word.push(c);
-            }
-        }
-    }
-    if !word.is_empty() {
-        parsed.push((false, word));
-    }
-    if parsed.len() != 3 {
-        return Err(
-            ShellError::from(
-                "oyster: cannot parse square bracket"
-            )
-        )
-    }
-    if parsed[1].0 {
-        return Err(
-            ShellError::from(
-                "oyster: middle operator is quoted"
-            )
-        )
-    }
-    let lhs = if parsed[0].0 {
-        Var::Str(parsed[0].1.clone())
-    } else if parsed[0].1.starts_with("$") {
-        if let Some(var) = shell.get_variable(&parsed[0].1[1..]) {
-            var
-        } else {
-            return Err(
-                ShellError::from(
-                    format!("oyster: variable {} not found", &parsed[0].1[1..])
-                )
-            )
-        }
-    } else if parsed[0].1.starts_with("@") {
-        if parsed[0].1.contains("[") && parsed[0].1.contains("]") {
-            index_into(shell, &parsed[0].1)?
-        } else {
-            return Err(ShellError::from("oyster: cannot operate on arrays"))
-        }
-    } else {
-        Var::from(&parsed[0].1)
-    };
-    let rhs = if parsed[2].0 {
-        Var::Str(parsed[2].1.clone())
-    } else if parsed[2].1.starts_with("$") {
-        if let Some(var) = shell.get_variable(&parsed[2].1[1..]) {
-            var
-        } else {
-            return Err(
-                ShellError::from(
-                    format!("oyster: variable {} not found", &parsed[2].1[1..])
-                )
-            )
-        }
-    } else if parsed[2].1.starts_with("@") {
-        if parsed[2].1.contains("[") && parsed[2].1.contains("]") {
-            index_into(shell, &parsed[2].1)?
-        } else {
-            return Err(ShellError::from("oyster: cannot operate on arrays"))
-        }
-    } else {
-        Var::from(&parsed[2].1)
-    };
-    let op = match parsed[1].1.as_str() {
-        "+"  => {Operator::Add}
-        "-"  => {Operator::Sub}
-        "*"  => {Operator::Mul}
-        "/"  => {Operator::Div}
-        "+=" => {Operator::AddAssgn}
-        "-=" => {Operator::SubAssgn}
-        "*=" => {Operator::MulAssgn}
-        "/=" => {Operator::DivAssgn}
-        n@ _ => {
-            return Err(ShellError::from(
-                format!("oyster: invalid operator {}", n)
-            ))
-        }
-    };
-    Ok((lhs, op, rhs))
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -637,6 +650,81 @@ mod tests {
             &mut fail,
         ));
     }
+    #[test]
+    fn check_variable_scoping() {
+        use crate::parser::Lexer;
+        use crate::types::{TokenizeResult, ParseResult};
+
+        let mut shell = Shell::new();
+        shell.add_variable("x", Var::from("global"));
+        assert!(shell.add_local("x", Var::from("local")).is_err());
+
+        let tokens = match Lexer::tokenize("local x = inner; let y = $1; let z = $#\n") {
+            TokenizeResult::Good(tokens) => tokens,
+            _ => panic!("could not tokenize function body"),
+        };
+        let jobs = match Lexer::parse_tokens(&mut shell, tokens).unwrap() {
+            ParseResult::Good(jobs) => jobs,
+            _ => panic!("could not parse function body"),
+        };
+        shell.insert_func("scoped", jobs, None);
+        shell.execute_func("scoped", vec![String::from("first"), String::from("2")]).unwrap();
+        assert_eq!(shell.get_variable("x"), Some(Var::from("global")));
+        assert_eq!(shell.get_variable("y"), Some(Var::from("first")));
+        assert_eq!(shell.get_variable("z"), Some(Var::Int(2)));
+        assert_eq!(shell.get_variable("1"), None);
+        assert!(shell.scopes.is_empty());
+
+        shell.scopes.push(HashMap::new());
+        shell.add_local("x", Var::from("local")).unwrap();
+        shell.add_variable("x", Var::from("changed"));
+        assert_eq!(shell.get_variable("x"), Some(Var::from("changed")));
+        shell.scopes.pop();
+        assert_eq!(shell.get_variable("x"), Some(Var::from("global")));
+
+        //a local of the same name leaves a global map alone
+        shell.add_map("m", crate::types::Map::new());
+        shell.scopes.push(HashMap::new());
+        shell.add_local("m", Var::from("local")).unwrap();
+        shell.add_variable("m", Var::from("changed"));
+        shell.scopes.pop();
+        assert!(shell.get_map("m").is_some());
+        shell.add_variable("m", Var::from("global"));
+        assert!(shell.get_map("m").is_none());
+    }
+
+    #[test]
+    fn check_job_specs() {
+        let mut shell = Shell::new();
+        let first = shell.add_cmd_to_job(
+            Pid::from_raw(100), Pid::from_raw(100), String::from("sleep 30"), true
+        );
+        let second = shell.add_cmd_to_job(
+            Pid::from_raw(200), Pid::from_raw(200), String::from("vim notes"), false
+        );
+        assert_eq!(shell.add_cmd_to_job(
+            Pid::from_raw(201), Pid::from_raw(200), String::from("cat"), false
+        ), second);
+        assert_eq!((first, second), (1, 2));
+
+        // only background and stopped jobs become current
+        assert_eq!(shell.find_job("%+").unwrap(), first);
+        shell.mark_job_as_stopped(second);
+        assert_eq!(shell.find_job("%%").unwrap(), second);
+        assert_eq!(shell.find_job("%-").unwrap(), first);
+        assert_eq!(shell.job_marker(first), '-');
+
+        assert_eq!(shell.find_job("%1").unwrap(), first);
+        assert_eq!(shell.find_job("%vim").unwrap(), second);
+        assert_eq!(shell.find_job("%?30").unwrap(), first);
+        assert!(shell.find_job("%3").is_err());
+        assert!(shell.find_job("%?e").is_err());
+
+        shell.remove_job(second);
+        assert_eq!(shell.find_job("%").unwrap(), first);
+        assert!(shell.find_job("%-").is_err());
+    }
+
     #[test]
     fn check_path_searching() {
         let mut shell = Shell::new();
@@ -0,0 +1,283 @@
+use std::env;
+
+use unicode_width::UnicodeWidthChar;
+
+/// How many colours the terminal can display.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum ColorDepth {
+    /// Colours are disabled, by `NO_COLOR` or a dumb terminal.
+    None,
+    /// The 16 standard colours.
+    Basic,
+    /// The xterm 256 colour palette.
+    Ansi256,
+    /// 24-bit colour.
+    TrueColor,
+}
+
+impl ColorDepth {
+    /// Works out the colour depth from `NO_COLOR`, `COLORTERM` and `TERM`.
+    pub fn from_env() -> Self {
+        if matches!(env::var("NO_COLOR"), Ok(val) if !val.is_empty()) {
+            return ColorDepth::None
+        }
+        if let Ok(colorterm) = env::var("COLORTERM") {
+            if colorterm == "truecolor" || colorterm == "24bit" {
+                return ColorDepth::TrueColor
+            }
+        }
+        match env::var("TERM") {
+            Ok(term) if term == "dumb" => ColorDepth::None,
+            Ok(term) if term.contains("256color") => ColorDepth::Ansi256,
+            _ => ColorDepth::Basic,
+        }
+    }
+}
+
+/// A colour as written in a prompt template.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Color {
+    /// One of the 16 standard colours; 8 to 15 are the bright variants.
+    Basic(u8),
+    /// An index into the 256 colour palette.
+    Indexed(u8),
+    Rgb(u8, u8, u8),
+}
+
+const COLOR_NAMES: [&str; 8] = [
+    "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+];
+
+/// The usual xterm values of the 16 standard colours.
+const BASIC_RGB: [(u8, u8, u8); 16] = [
+    (0, 0, 0), (205, 0, 0), (0, 205, 0), (205, 205, 0),
+    (0, 0, 238), (205, 0, 205), (0, 205, 205), (229, 229, 229),
+    (127, 127, 127), (255, 0, 0), (0, 255, 0), (255, 255, 0),
+    (92, 92, 255), (255, 0, 255), (0, 255, 255), (255, 255, 255),
+];
+
+/// The channel values used by the 6x6x6 colour cube.
+const CUBE_STEPS: [u8; 6] = [0, 95, 135, 175, 215, 255];
+
+impl Color {
+    /// Parses a colour name such as `red` or `bright_red`,
+    /// a palette index from 0 to 255, or a hex colour `#ff8800` or `#f80`.
+    pub fn parse(color: &str) -> Option<Self> {
+        if let Some(hex) = color.strip_prefix('#') {
+            let channel = |i: usize, len: usize| {
+                u8::from_str_radix(hex.get(i * len..(i + 1) * len)?, 16).ok()
+            };
+            return match hex.len() {
+                6 => Some(Color::Rgb(channel(0, 2)?, channel(1, 2)?, channel(2, 2)?)),
+                3 => {
+                    let (r, g, b) = (channel(0, 1)?, channel(1, 1)?, channel(2, 1)?);
+                    Some(Color::Rgb(r * 17, g * 17, b * 17))
+                }
+                _ => None,
+            }
+        }
+        if let Ok(index) = color.parse::<u8>() {
+            return Some(Color::Indexed(index))
+        }
+        let (name, bright) = match color.strip_prefix("bright_") {
+            Some(name) => (name, 8),
+            None => (color, 0),
+        };
+        COLOR_NAMES.iter()
+            .position(|known| *known == name)
+            .map(|pos| Color::Basic(pos as u8 + bright))
+    }
+
+    /// Converts the colour to one the terminal can display,
+    /// or `None` if colours are disabled.
+    pub fn downgrade(self, depth: ColorDepth) -> Option<Self> {
+        match (self, depth) {
+            (_, ColorDepth::None) => None,
+            (Color::Basic(_), _) => Some(self),
+            (Color::Indexed(index), ColorDepth::Basic) if index < 16 => {
+                Some(Color::Basic(index))
+            }
+            (Color::Indexed(_), ColorDepth::Basic) | (Color::Rgb(..), ColorDepth::Basic) => {
+                Some(Color::Basic(nearest_basic(self.rgb())))
+            }
+            (Color::Rgb(r, g, b), ColorDepth::Ansi256) => {
+                Some(Color::Indexed(nearest_indexed((r, g, b))))
+            }
+            _ => Some(self),
+        }
+    }
+
+    /// The approximate red, green and blue values of the colour.
+    fn rgb(self) -> (u8, u8, u8) {
+        match self {
+            Color::Basic(index) => BASIC_RGB[index as usize % 16],
+            Color::Indexed(index) if index < 16 => BASIC_RGB[index as usize],
+            Color::Indexed(index) if index < 232 => {
+                let index = index as usize - 16;
+                (CUBE_STEPS[index / 36], CUBE_STEPS[index / 6 % 6], CUBE_STEPS[index % 6])
+            }
+            Color::Indexed(index) => {
+                let gray = 8 + (index - 232) * 10;
+                (gray, gray, gray)
+            }
+            Color::Rgb(r, g, b) => (r, g, b),
+        }
+    }
+
+    /// The SGR parameters that set the colour as the foreground,
+    /// or as the background if `bg` is true.
+    fn sgr(self, bg: bool) -> String {
+        match self {
+            Color::Basic(index) if index < 8 => {
+                format!("{}", if bg {40} else {30} + index)
+            }
+            Color::Basic(index) => {
+                format!("{}", if bg {100} else {90} + index % 8)
+            }
+            Color::Indexed(index) => {
+                format!("{};5;{}", if bg {48} else {38}, index)
+            }
+            Color::Rgb(r, g, b) => {
+                format!("{};2;{};{};{}", if bg {48} else {38}, r, g, b)
+            }
+        }
+    }
+}
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+    let diff = |x: u8, y: u8| (x as i32 - y as i32).pow(2) as u32;
+    diff(a.0, b.0) + diff(a.1, b.1) + diff(a.2, b.2)
+}
+
+fn nearest_basic(rgb: (u8, u8, u8)) -> u8 {
+    (0..16u8).min_by_key(|&index| distance(rgb, BASIC_RGB[index as usize]))
+        .unwrap_or(0)
+}
+
+/// Finds the closest colour in the colour cube or the grayscale ramp.
+fn nearest_indexed(rgb: (u8, u8, u8)) -> u8 {
+    let step = |channel: u8| {
+        (0..6).min_by_key(|&i| (CUBE_STEPS[i] as i32 - channel as i32).abs())
+            .unwrap_or(0)
+    };
+    let (r, g, b) = (step(rgb.0), step(rgb.1), step(rgb.2));
+    let cube = 16 + 36 * r + 6 * g + b;
+    let average = (rgb.0 as usize + rgb.1 as usize + rgb.2 as usize) / 3;
+    let gray = 232 + (average.saturating_sub(3) / 10).min(23);
+    let cube_rgb = Color::Indexed(cube as u8).rgb();
+    let gray_rgb = Color::Indexed(gray as u8).rgb();
+    if distance(rgb, gray_rgb) < distance(rgb, cube_rgb) {
+        gray as u8
+    } else {
+        cube as u8
+    }
+}
+
+/// Renders a style token such as `fg:#ff8800`, `bg:237`, `italic` or `reset`
+/// as an escape sequence, wrapped in `\x01` and `\x02` so that the line editor
+/// does not count it towards the width of the prompt.
+///
+/// Returns an empty string for colours when `depth` is `ColorDepth::None`,
+/// and `None` if the token is not a style.
+pub fn render_style(token: &str, depth: ColorDepth) -> Option<String> {
+    let sgr = if let Some(color) = token.strip_prefix("fg:") {
+        Color::parse(color)?.downgrade(depth).map(|color| color.sgr(false))
+    } else if let Some(color) = token.strip_prefix("bg:") {
+        Color::parse(color)?.downgrade(depth).map(|color| color.sgr(true))
+    } else {
+        let code = match token {
+            "reset" => "0",
+            "bold" => "1",
+            "dim" => "2",
+            "italic" => "3",
+            "underline" => "4",
+            "blink" => "5",
+            "reverse" => "7",
+            "strike" => "9",
+            _ => return None,
+        };
+        Some(code.to_string())
+    };
+    Some(sgr.map_or_else(String::new, |sgr| format!("\x01\x1B[{}m\x02", sgr)))
+}
+
+/// Wraps escape sequences written directly into the prompt, such as
+/// `\x1B[31m`, in `\x01` and `\x02`, so that the line editor
+/// does not count them towards the width of the prompt.
+pub fn hide_escapes(prompt: &str) -> String {
+    let mut hidden = String::with_capacity(prompt.len());
+    let mut chars = prompt.chars().peekable();
+    let mut marked = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '\x01' => marked = true,
+            '\x02' => marked = false,
+            '\x1B' if !marked => {
+                hidden.push('\x01');
+                hidden.push(c);
+                if chars.peek() == Some(&'[') {
+                    //a control sequence ends with a byte in `@` to `~`
+                    for c in chars.by_ref() {
+                        hidden.push(c);
+                        if c != '[' && ('@'..='~').contains(&c) {
+                            break
+                        }
+                    }
+                } else if let Some(c) = chars.next() {
+                    hidden.push(c);
+                }
+                hidden.push('\x02');
+                continue
+            }
+            _ => {}
+        }
+        hidden.push(c);
+    }
+    hidden
+}
+
+/// Returns how many columns the text takes up on screen,
+/// skipping anything between `\x01` and `\x02`.
+/// Wide characters such as CJK and most emoji take up two columns.
+pub fn display_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut hidden = false;
+    for c in text.chars() {
+        match c {
+            '\x01' => hidden = true,
+            '\x02' => hidden = false,
+            _ if hidden => {}
+            _ => width += c.width().unwrap_or(0),
+        }
+    }
+    width
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_style_tokens() {
+        let style = |token| render_style(token, ColorDepth::TrueColor).unwrap();
+        assert_eq!(style("fg:#ff8800"), "\x01\x1B[38;2;255;136;0m\x02");
+        assert_eq!(style("bg:237"), "\x01\x1B[48;5;237m\x02");
+        assert_eq!(style("fg:bright_red"), "\x01\x1B[91m\x02");
+        assert_eq!(style("italic"), "\x01\x1B[3m\x02");
+        assert_eq!(render_style("fg:#ff8800", ColorDepth::Ansi256).unwrap(),
+            "\x01\x1B[38;5;208m\x02");
+        assert_eq!(render_style("bg:245", ColorDepth::Basic).unwrap(),
+            "\x01\x1B[100m\x02");
+        assert_eq!(render_style("fg:#f80", ColorDepth::None).unwrap(), "");
+        assert_eq!(render_style("dim", ColorDepth::None).unwrap(), "\x01\x1B[2m\x02");
+        assert_eq!(render_style("fg:#ff88", ColorDepth::TrueColor), None);
+        assert_eq!(render_style("NOPE", ColorDepth::TrueColor), None);
+
+        assert_eq!(Color::Rgb(128, 128, 128).downgrade(ColorDepth::Ansi256),
+            Some(Color::Indexed(244)));
+        assert_eq!(hide_escapes("\x1B[1;31m>>\x01\x1B[0m\x02 "),
+            "\x01\x1B[1;31m\x02>>\x01\x1B[0m\x02 ");
+        assert_eq!(display_width(&format!("{}~/src >> {}", style("bold"), style("reset"))), 9);
+        assert_eq!(display_width("日本語 🦀>"), 10);
+    }
+}
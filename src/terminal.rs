@@ -0,0 +1,160 @@
+use std::io;
+use std::time::Duration;
+
+use linefeed::terminal::{
+    CursorMode,
+    DefaultTerminal,
+    RawRead,
+    SignalSet,
+    Size,
+    Terminal,
+    TerminalReader,
+    TerminalWriter,
+};
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::Error;
+use nix::errno::Errno;
+
+use crate::jobc;
+use crate::shell::nix_to_io;
+
+/// The terminal used by the line editor, which stops waiting for input
+/// when a child changes state, so that jobs can be reported at the prompt.
+pub struct JobTerminal(DefaultTerminal);
+
+impl JobTerminal {
+    pub fn new() -> io::Result<JobTerminal> {
+        DefaultTerminal::new().map(JobTerminal)
+    }
+}
+
+impl Terminal for JobTerminal {
+    type PrepareState = <DefaultTerminal as Terminal>::PrepareState;
+
+    fn name(&self) -> &str {
+        self.0.name()
+    }
+
+    fn lock_read<'a>(&'a self) -> Box<dyn TerminalReader<Self> + 'a> {
+        Box::new(JobReader(self.0.lock_read()))
+    }
+
+    fn lock_write<'a>(&'a self) -> Box<dyn TerminalWriter<Self> + 'a> {
+        Box::new(JobWriter(self.0.lock_write()))
+    }
+}
+
+pub struct JobReader<'a>(Box<dyn TerminalReader<DefaultTerminal> + 'a>);
+
+pub struct JobWriter<'a>(Box<dyn TerminalWriter<DefaultTerminal> + 'a>);
+
+impl<'a> JobWriter<'a> {
+    /// Recovers the default terminal's writer from one passed back
+    /// by the line editor, which only ever holds writers made by `lock_write`.
+    unsafe fn inner(lock: &mut dyn TerminalWriter<JobTerminal>)
+        -> &mut dyn TerminalWriter<DefaultTerminal> {
+        let writer = &mut *(lock as *mut dyn TerminalWriter<JobTerminal> as *mut JobWriter);
+        &mut *writer.0
+    }
+}
+
+impl<'a> TerminalReader<JobTerminal> for JobReader<'a> {
+    fn prepare(&mut self, block_signals: bool, report_signals: SignalSet)
+        -> io::Result<<JobTerminal as Terminal>::PrepareState> {
+        self.0.prepare(block_signals, report_signals)
+    }
+
+    unsafe fn prepare_with_lock(
+        &mut self,
+        lock: &mut dyn TerminalWriter<JobTerminal>,
+        block_signals: bool,
+        report_signals: SignalSet,
+    ) -> io::Result<<JobTerminal as Terminal>::PrepareState> {
+        self.0.prepare_with_lock(JobWriter::inner(lock), block_signals, report_signals)
+    }
+
+    fn restore(&mut self, state: <JobTerminal as Terminal>::PrepareState) -> io::Result<()> {
+        self.0.restore(state)
+    }
+
+    unsafe fn restore_with_lock(
+        &mut self,
+        lock: &mut dyn TerminalWriter<JobTerminal>,
+        state: <JobTerminal as Terminal>::PrepareState,
+    ) -> io::Result<()> {
+        self.0.restore_with_lock(JobWriter::inner(lock), state)
+    }
+
+    fn read(&mut self, buf: &mut Vec<u8>) -> io::Result<RawRead> {
+        self.0.read(buf)
+    }
+
+    /// Waits on the terminal and the SIGCHLD self-pipe together.
+    /// Returns false when a child changed state before any input arrived.
+    fn wait_for_input(&mut self, timeout: Option<Duration>) -> io::Result<bool> {
+        //input that is already buffered, or a signal, is seen straight away
+        if self.0.wait_for_input(Some(Duration::from_secs(0)))? {
+            return Ok(true);
+        }
+        let millis = match timeout {
+            Some(timeout) => timeout.as_millis().min(i32::MAX as u128) as i32,
+            None => -1,
+        };
+        let mut fds = [
+            PollFd::new(0, PollFlags::POLLIN),
+            PollFd::new(jobc::sigchld_fd(), PollFlags::POLLIN),
+        ];
+        match poll(&mut fds, millis) {
+            //the signals the line editor handles are picked up below
+            Ok(_) | Err(Error::Sys(Errno::EINTR)) => {}
+            Err(e) => return Err(nix_to_io(e)),
+        }
+        self.0.wait_for_input(Some(Duration::from_secs(0)))
+    }
+}
+
+impl<'a> TerminalWriter<JobTerminal> for JobWriter<'a> {
+    fn size(&self) -> io::Result<Size> {
+        self.0.size()
+    }
+
+    fn clear_screen(&mut self) -> io::Result<()> {
+        self.0.clear_screen()
+    }
+
+    fn clear_to_screen_end(&mut self) -> io::Result<()> {
+        self.0.clear_to_screen_end()
+    }
+
+    fn move_up(&mut self, n: usize) -> io::Result<()> {
+        self.0.move_up(n)
+    }
+
+    fn move_down(&mut self, n: usize) -> io::Result<()> {
+        self.0.move_down(n)
+    }
+
+    fn move_left(&mut self, n: usize) -> io::Result<()> {
+        self.0.move_left(n)
+    }
+
+    fn move_right(&mut self, n: usize) -> io::Result<()> {
+        self.0.move_right(n)
+    }
+
+    fn move_to_first_column(&mut self) -> io::Result<()> {
+        self.0.move_to_first_column()
+    }
+
+    fn set_cursor_mode(&mut self, mode: CursorMode) -> io::Result<()> {
+        self.0.set_cursor_mode(mode)
+    }
+
+    fn write(&mut self, s: &str) -> io::Result<()> {
+        self.0.write(s)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
@@ -1,7 +1,8 @@
 use std::fmt;
-use std::io;
+use std::io::{self, Write};
 use std::collections::HashMap;
 use std::process;
+use std::os::unix::io::RawFd;
 
 use glob::{PatternError, GlobError};
 
@@ -15,10 +16,13 @@ use crate::shell::{
 use crate::expansion::{
     expand_braces,
     expand_variables,
+    check_unbound,
     expand_tilde,
     substitute_commands,
     index_into,
+    expand_map_iter,
 };
+use crate::namespace;
 use crate::prompt::{
     BOLD,
     RESET,
@@ -35,6 +39,8 @@ pub enum TokenizeResult {
     UnmatchedCmdSub,
     UnmatchedSqBrkt,
     UnmatchedNmspce,
+    UnmatchedBrace,
+    UnmatchedHereDoc,
     EndsOnOr,
     EndsOnAnd,
     EndsOnPipe,
@@ -63,6 +69,12 @@ impl fmt::Display for TokenizeResult {
             TokenizeResult::UnmatchedNmspce => {
                 write!(f, "{}nmspce > {}", BOLD, RESET)
             }
+            TokenizeResult::UnmatchedBrace => {
+                write!(f, "{}brace > {}", BOLD, RESET)
+            }
+            TokenizeResult::UnmatchedHereDoc => {
+                write!(f, "{}heredoc > {}", BOLD, RESET)
+            }
             TokenizeResult::EndsOnAnd => {
                 write!(f, "{}cmdand > {}", BOLD, RESET )
             }
@@ -89,29 +101,34 @@ pub enum ParseResult {
     For,
     While,
     If,
+    Match,
     Func,
-    //Case,
     Good(Vec<Job>),
 }
 
+impl ParseResult {
+    /// The keyword that starts an unfinished construct.
+    pub fn name(&self) -> &'static str {
+        match self {
+            ParseResult::For => "for",
+            ParseResult::While => "while",
+            ParseResult::If => "if",
+            ParseResult::Match => "match",
+            ParseResult::Func => "func",
+            ParseResult::Good(_jobs) => "",
+        }
+    }
+}
+
 impl fmt::Display for ParseResult {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            ParseResult::For => {
-                write!(f, "{}for > {}", BOLD, RESET )
-            }
-            ParseResult::While => {
-                write!(f, "{}while > {}", BOLD, RESET)
-            }
-            ParseResult::If => {
-                write!(f, "{}if > {}", BOLD, RESET )
-            }
-            ParseResult::Func => {
-                write!(f, "{}func > {}", BOLD, RESET )
-            }
             ParseResult::Good(_jobs) => {
                 Ok(())
             }
+            _ => {
+                write!(f, "{}{} > {}", BOLD, self.name(), RESET)
+            }
         }
     }
 }
@@ -142,8 +159,8 @@ pub enum ParseError {
     StartsOnOr,
     StartsOnConsec,
     PipeMismatch,
-    InvalidFileRD,
-    InvalidFileDesc,
+    InvalidFileRD(Span),
+    InvalidFileDesc(Span),
     InvalidRDSyntax,
     FuncInShellConst,
     InvalidGlob,
@@ -152,10 +169,22 @@ pub enum ParseError {
     ConversionError,
     GenericError(String),
     EmptyCommand,
+    UnboundVariable(String),
 }
 
 impl std::error::Error for ParseError {}
 
+impl ParseError {
+    /// Returns the location of the token that caused the error, if known.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            ParseError::InvalidFileRD(span) |
+            ParseError::InvalidFileDesc(span) => Some(*span),
+            _ => None,
+        }
+    }
+}
+
 impl fmt::Display for ParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -171,10 +200,10 @@ impl fmt::Display for ParseError {
             ParseError::PipeMismatch => {
                 write!(f, "error: pipe mismatch")
             },
-            ParseError::InvalidFileRD => {
+            ParseError::InvalidFileRD(_) => {
                 write!(f, "error: redirecting to invalid file")
             },
-            ParseError::InvalidFileDesc => {
+            ParseError::InvalidFileDesc(_) => {
                 write!(f, "error: redirecting to invalid file descriptor")
             },
             ParseError::InvalidRDSyntax => {
@@ -201,6 +230,9 @@ impl fmt::Display for ParseError {
             ParseError::EmptyCommand => {
                 write!(f, "error: empty command")
             }
+            ParseError::UnboundVariable(name) => {
+                write!(f, "oyster: {}: unbound variable", name)
+            }
         }
     }
 }
@@ -326,11 +358,9 @@ pub enum Token {
     And, //handled!
     Or, //handled!
     Consec, //handled!
-    Redirect,
-    RDAppend,
-    RDStdin,
-    RDStdOutErr, //Always redirects to a file
-    RDFileDesc,  //Redirects to a file descriptor
+    //The file descriptor being redirected, if given, and where it was read from
+    Redirect { fd: Option<RawFd>, op: RedirectOp, span: Span },
+    HereDoc(String, bool), //The body, and whether to expand it
     Background,
 }
 
@@ -360,7 +390,7 @@ impl fmt::Display for Token {
                 write!(f, "{}", string)
             }
             NmSpce(string) => {
-                write!(f, "${{{}}}", string)
+                write!(f, "{}", display_nmspce(string))
             }
             SqBrkt(string) => {
                 write!(f, "{}", string)
@@ -380,20 +410,14 @@ impl fmt::Display for Token {
             Consec => {
                 write!(f, ";")
             }
-            Redirect => {
-                write!(f, ">")
-            }
-            RDAppend => {
-                write!(f, ">>")
-            }
-            RDStdin => {
-                write!(f, "<")
-            }
-            RDStdOutErr => {
-                write!(f, "&>")
+            Redirect { fd, op, .. } => {
+                if let Some(fd) = fd {
+                    write!(f, "{}", fd)?;
+                }
+                write!(f, "{}", op)
             }
-            RDFileDesc => {
-                write!(f, ">&")
+            HereDoc(string, _) => {
+                write!(f, "{}", string)
             }
             Background => {
                 write!(f, "&")
@@ -402,20 +426,146 @@ impl fmt::Display for Token {
     }
 }
 
-/// Produced during parsing, used as a redirect marker in Cmd
+/// Namespaced expansions are stored with their sigil and without braces,
+/// so `${ns:args}` is stored as `$ns:args`.
+fn display_nmspce(string: &str) -> String {
+    if let Some(inner) = string.strip_prefix('$') {
+        format!("${{{}}}", inner)
+    } else {
+        string.to_string()
+    }
+}
+
+/// A range of bytes in the shell input that a token was read from.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    /// Returns the line of `source` containing the span, with the span
+    /// underlined on the line below it.
+    ///
+    /// Returns None if the span does not lie on a single line of `source`.
+    pub fn underline(&self, source: &str) -> Option<String> {
+        let text = source.get(self.start..self.end)?;
+        if text.contains('\n') {
+            return None
+        }
+        let line_start = source[..self.start].rfind('\n').map_or(0, |idx| idx + 1);
+        let line_end = source[self.end..].find('\n')
+            .map_or(source.len(), |idx| self.end + idx);
+        Some(format!("  {}\n  {}{}",
+            &source[line_start..line_end],
+            " ".repeat(source[line_start..self.start].chars().count()),
+            "^".repeat(text.chars().count().max(1)),
+        ))
+    }
+}
+
+/// A redirection operator, as it was written in the shell input.
 #[derive(Debug, Clone, Copy, PartialEq)]
-pub enum Redirect { //* Origin is always a file descriptor
-    Override,
-    Append,
-    FromStdin,
+pub enum RedirectOp {
+    Output,    // >
+    Append,    // >>
+    Clobber,   // >|
+    Input,     // <
+    ReadWrite, // <>
+    OutputAll, // &>
+    AppendAll, // &>> and >>&
+    DupOutput, // >&
+    DupInput,  // <&
+    HereDoc,   // << and <<-, always followed by a HereDoc token
+    HereStr,   // <<<
 }
 
-impl Redirect {
-    pub fn display(&self) -> String {
-        match self {
-            Redirect::Override => String::from(">"),
-            Redirect::Append => String::from(">>"),
-            Redirect::FromStdin => String::from("<")
+impl fmt::Display for RedirectOp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let op = match self {
+            RedirectOp::Output => ">",
+            RedirectOp::Append => ">>",
+            RedirectOp::Clobber => ">|",
+            RedirectOp::Input => "<",
+            RedirectOp::ReadWrite => "<>",
+            RedirectOp::OutputAll => "&>",
+            RedirectOp::AppendAll => "&>>",
+            RedirectOp::DupOutput => ">&",
+            RedirectOp::DupInput => "<&",
+            RedirectOp::HereDoc => "<<",
+            RedirectOp::HereStr => "<<<",
+        };
+        write!(f, "{}", op)
+    }
+}
+
+/// What a file descriptor is redirected to.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Redirect {
+    /// `n>file`, `n>>file` and `n>|file`.
+    /// `clobber` truncates the file even if `noclobber` is set.
+    ToFile { path: String, append: bool, clobber: bool },
+    /// `n<file`
+    FromFile(String),
+    /// `n<>file`, opens the file for both reading and writing.
+    ReadWrite(String),
+    /// `n>&m` and `n<&m`, makes `n` a copy of `m`.
+    Duplicate(RawFd),
+    /// `n>&-` and `n<&-`
+    Close,
+    /// Feeds the text in the redirect to the file descriptor.
+    /// The text is expanded before execution if `expand` is set.
+    HereDoc { body: String, expand: bool },
+}
+
+/// A single redirection on a command, produced during parsing.
+/// The executor applies them in the order they were written.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Redirection {
+    pub fd: RawFd,
+    pub target: Redirect,
+    /// Where the redirection operator was read from.
+    pub span: Span,
+}
+
+impl Redirection {
+    pub fn new(fd: RawFd, target: Redirect, span: Span) -> Self {
+        Self { fd, target, span }
+    }
+}
+
+impl fmt::Display for Redirection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.target {
+            Redirect::ToFile { path, append, clobber } => {
+                let op = if *append {
+                    ">>"
+                } else if *clobber {
+                    ">|"
+                } else {
+                    ">"
+                };
+                write!(f, "{}{}{}", self.fd, op, path)
+            }
+            Redirect::FromFile(path) => {
+                write!(f, "{}<{}", self.fd, path)
+            }
+            Redirect::ReadWrite(path) => {
+                write!(f, "{}<>{}", self.fd, path)
+            }
+            Redirect::Duplicate(target) => {
+                write!(f, "{}>&{}", self.fd, target)
+            }
+            Redirect::Close => {
+                write!(f, "{}>&-", self.fd)
+            }
+            Redirect::HereDoc { body, .. } => {
+                write!(f, "{}<<{}", self.fd, body)
+            }
         }
     }
 }
@@ -446,7 +596,7 @@ impl Default for Quote {
 pub struct TokenCmd {
     pub cmd: (Quote, String),
     pub args: Vec<(Quote, String)>,
-    pub redirects: Vec<(String, Redirect, String)>,
+    pub redirects: Vec<Redirection>,
     pub pipe_stderr: bool,
 }
 
@@ -457,14 +607,11 @@ impl fmt::Display for TokenCmd {
                 Quote::NQuote => {
                     return format!("{}", string);
                 }
-                Quote::Variable => {
-                    return format!("${}", string);
+                Quote::Variable | Quote::CmdSub => {
+                    string.clone()
                 }
                 Quote::BQuote => {
-                    return format!("{}", string);
-                }
-                Quote::CmdSub => {
-                    return format!("$({})", string);
+                    format!("`{}`", string)
                 }
                 Quote::DQuote => {
                     return format!("\"{}\"", string);
@@ -479,16 +626,18 @@ impl fmt::Display for TokenCmd {
                     return format!("{}", string);
                 }
                 Quote::NmSpce => {
-                    return format!("${{{}}}", string);
+                    display_nmspce(string)
                 }
             }
         }).collect();
         let redirects: Vec<String> = self.redirects.iter().map(
-            |(string, rd, string2)| {
-                return format!("{}{}{}", string, rd.display(), string2);
-            }
+            |redirect| redirect.to_string()
         ).collect();
-        write!(f, "{} {}", cmd.join(" "), redirects.join(" "))
+        if redirects.is_empty() {
+            write!(f, "{}", cmd.join(" "))
+        } else {
+            write!(f, "{} {}", cmd.join(" "), redirects.join(" "))
+        }
     }
 }
 
@@ -497,7 +646,7 @@ impl fmt::Display for TokenCmd {
 pub struct Cmd {
     pub cmd: String,
     pub args: Vec<String>,
-    pub redirects: Vec<(String, Redirect, String)>,
+    pub redirects: Vec<Redirection>,
     pub pipe_stderr: bool,
 }
 
@@ -530,14 +679,17 @@ impl Cmd {
                 }
             }
             Quote::Variable => {
-                if let Some(var) = shell.get_variable(&cmd.cmd.1[1..]) {
+                if cmd.cmd.1.ends_with("]") {
+                    cmd.cmd.1 = index_into(shell, &cmd.cmd.1)?.to_string();
+                } else if let Some(var) = shell.get_variable(&cmd.cmd.1[1..]) {
                     cmd.cmd.1 = var.to_string();
                 } else {
+                    check_unbound(shell, &cmd.cmd.1[1..])?;
                     cmd.cmd.1 = "".to_string();
                 }
             }
             Quote::DQuote => {
-                expand_variables(shell, &mut cmd.cmd.1);
+                expand_variables(shell, &mut cmd.cmd.1)?;
                 match substitute_commands(shell, &cmd.cmd.1) {
                     Ok(string) => {
                         newargs.push(string.clone());
@@ -567,7 +719,7 @@ impl Cmd {
                 }
             }
             Quote::BQuote => {
-                expand_variables(shell, &mut cmd.cmd.1);
+                expand_variables(shell, &mut cmd.cmd.1)?;
                 match substitute_commands(shell, &cmd.cmd.1) {
                     Ok(string) => {
                         if cmd.cmd.1.starts_with("$") {
@@ -591,8 +743,12 @@ impl Cmd {
                 newargs.extend(expanded);
             }
             Quote::NmSpce => {
-                newargs.push(cmd.cmd.1.clone());
-                //TODO
+                let expanded = namespace::expand(shell, &cmd.cmd.1)?;
+                if expanded.is_empty() {
+                    return Err(ShellError::from("oyster: namespace expanded to nothing"))
+                }
+                cmd.cmd = (Quote::NQuote, expanded[0].clone());
+                newargs.extend(expanded);
             }
             Quote::SQuote => {
                 newargs.push(cmd.cmd.1.clone());
@@ -611,6 +767,9 @@ impl Cmd {
                         if string.contains("[") && string.ends_with("]") {
                             newargs.push(index_into(shell, &string)?.to_string());
                             continue;
+                        } else if let Some(expanded) = expand_map_iter(shell, &string) {
+                            newargs.extend(expanded?);
+                            continue;
                         } else if let Some(var) = shell.get_variable(&string[1..]) {
                             if let Variable::Arr(arr) = var {
                                 newargs.extend(arr.into_iter().map(|elem| {
@@ -626,13 +785,17 @@ impl Cmd {
                     }
                 }
                 Quote::Variable => {
-                    if let Some(var) = shell.get_variable(&string[1..]) {
+                    if string.ends_with("]") {
+                        newargs.push(index_into(shell, &string)?.to_string());
+                    } else if let Some(var) = shell.get_variable(&string[1..]) {
                         newargs.push(var.to_string());
+                    } else {
+                        check_unbound(shell, &string[1..])?;
                     }
                     continue
                 }
                 Quote::DQuote => {
-                    expand_variables(shell, &mut string);
+                    expand_variables(shell, &mut string)?;
                     match substitute_commands(shell, &string) {
                         Ok(newstring) => {
                             string = newstring;
@@ -681,7 +844,8 @@ impl Cmd {
                     continue;
                 }
                 Quote::NmSpce => {
-                    //TODO
+                    newargs.extend(namespace::expand(shell, &string)?);
+                    continue;
                 }
                 Quote::SQuote => {}
                 Quote::SqBrkt => {
@@ -691,6 +855,12 @@ impl Cmd {
             }
             newargs.push(string);
         }
+        for redirect in &mut cmd.redirects {
+            if let Redirect::HereDoc { body, expand: true } = &mut redirect.target {
+                expand_variables(shell, body)?;
+                *body = substitute_commands(shell, body)?;
+            }
+        }
         Ok(Cmd {
             cmd: cmd.cmd.1,
             args: newargs,
@@ -708,7 +878,7 @@ pub struct Function {
 }
 
 impl Function {
-    pub fn print(&self) {
+    pub fn print(&self) -> io::Result<()> {
         let paramscount = if let Some(count) = self.params {
             count.to_string()
         } else {
@@ -717,7 +887,8 @@ impl Function {
         let jobs: Vec<String> = self.jobs.iter().map(|job| {
             job.to_string()
         }).collect();
-        println!(
+        writeln!(
+            io::stdout(),
             "func {} {}\n   {}\nendfn",
             self.name,
             paramscount,
@@ -726,13 +897,65 @@ impl Function {
     }
 }
 
-#[derive(Debug, Clone)]
+/// An associative array, mapping string keys to variables.
+#[derive(Debug, Clone, Default)]
 pub struct Map {
     inner: HashMap<String, Variable>,
 }
 
 impl Map {
+    pub fn new() -> Self {
+        Map {
+            inner: HashMap::new(),
+        }
+    }
+
+    /// Inserts a value, returning the old value if the key existed.
+    pub fn insert(&mut self, key: &str, value: Variable) -> Option<Variable> {
+        self.inner.insert(key.to_string(), value)
+    }
 
+    pub fn get(&self, key: &str) -> Option<&Variable> {
+        self.inner.get(key)
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<Variable> {
+        self.inner.remove(key)
+    }
+
+    /// Returns the keys of the map in sorted order.
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self.inner.keys().cloned().collect();
+        keys.sort();
+        keys
+    }
+
+    /// Returns the values of the map, in the same order as `keys()`.
+    pub fn values(&self) -> Vec<Variable> {
+        self.keys().iter().map(|key| self.inner[key].clone()).collect()
+    }
+
+    pub fn print(&self) -> io::Result<()> {
+        writeln!(io::stdout(), "map: {}", self)
+    }
+}
+
+impl fmt::Display for Map {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let entries: Vec<String> = self.keys().into_iter().map(|key| {
+            let value = &self.inner[&key];
+            let key = if key.contains(|c: char| c.is_whitespace() || c == ':' || c == ',') {
+                format!("\"{}\"", key)
+            } else {
+                key
+            };
+            match value {
+                Variable::Str(string) => format!("{}: \"{}\"", key, string),
+                var => format!("{}: {}", key, var),
+            }
+        }).collect();
+        write!(f, "{{{}}}", entries.join(", "))
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, PartialOrd)]
@@ -744,19 +967,19 @@ pub enum Variable {
 }
 
 impl Variable {
-    pub fn print(&self) {
+    pub fn print(&self) -> io::Result<()> {
         match self {
             Variable::Str(string) => {
-                println!("str: \"{}\"", string);
+                writeln!(io::stdout(), "str: \"{}\"", string)
             }
             Variable::Int(int) => {
-                println!("int: {}", int);
+                writeln!(io::stdout(), "int: {}", int)
             }
             Variable::Flt(flt) => {
-                println!("flt: {}", flt);
+                writeln!(io::stdout(), "flt: {}", flt)
             }
             Variable::Arr(arr) => {
-                println!("arr: [{}]", arr.iter()
+                writeln!(io::stdout(), "arr: [{}]", arr.iter()
                     .map(|elem| elem.to_string())
                     .collect::<Vec<String>>().join(" ")
                 )
@@ -764,6 +987,16 @@ impl Variable {
         }
     }
 
+    /// The name of the variable's type, as used by `let` and `show`.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Variable::Str(_) => "str",
+            Variable::Int(_) => "int",
+            Variable::Flt(_) => "flt",
+            Variable::Arr(_) => "arr",
+        }
+    }
+
     pub fn types_match(lhs: &Variable, rhs: &Variable) -> bool {
         match lhs {
             Variable::Int(_) => {
@@ -834,16 +1067,41 @@ impl fmt::Display for Variable {
     }
 }
 
+/// A pending change in control flow, set by `break`, `continue` and `return`.
+///
+/// It travels up through `execute_jobs()` and the scripting constructs
+/// until it reaches the loop or function it applies to.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum ControlFlow {
+    /// Break out of this many enclosing loops.
+    Break(usize),
+    /// Skip to the next iteration of the nth enclosing loop.
+    Continue(usize),
+    /// Return from the current function with this status.
+    Return(i32),
+}
+
 #[derive(Debug, Copy, Clone, PartialEq)]
 pub enum Operator {
     Add,
     Sub,
     Mul,
     Div,
-    AddAssgn,
-    SubAssgn,
-    MulAssgn,
-    DivAssgn,
+    Mod,
+    Pow,
+}
+
+impl fmt::Display for Operator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operator::Add => write!(f, "+"),
+            Operator::Sub => write!(f, "-"),
+            Operator::Mul => write!(f, "*"),
+            Operator::Div => write!(f, "/"),
+            Operator::Mod => write!(f, "%"),
+            Operator::Pow => write!(f, "**"),
+        }
+    }
 }
 
 /// Emitted by `parse_tokens()`, its data is consumed by `execute_jobs()`
@@ -881,9 +1139,98 @@ pub struct JobTrack {
     pub firstcmd: String,
     pub id: i32,
     pub pgid: Pid,
+    /// Every process in the job, in pipeline order.
     pub pids: Vec<Pid>,
+    /// The exit status of each process in `pids`, once it has finished.
+    pub statuses: Vec<Option<i32>>,
     pub status: JobStatus,
     pub background: bool,
+    /// Set by `disown -h`, so that exiting the shell leaves the job running.
+    pub nohup: bool,
+}
+
+impl JobTrack {
+    /// The exit status of the job, from the processes that have finished.
+    pub fn exit_status(&self, pipefail: bool) -> i32 {
+        let statuses: Vec<i32> = self.statuses.iter().flatten().copied().collect();
+        pipeline_status(&statuses, pipefail)
+    }
+}
+
+/// Returns the exit status of a pipeline from the statuses of its commands:
+/// the status of the last command, or with `pipefail`,
+/// the last command to fail.
+pub fn pipeline_status(statuses: &[i32], pipefail: bool) -> i32 {
+    let last = statuses.last().copied().unwrap_or(0);
+    if pipefail {
+        statuses.iter().rev().find(|status| **status != 0).copied().unwrap_or(0)
+    } else {
+        last
+    }
+}
+
+/// Options that change how the shell behaves, set with `setopt` or `set -o`.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ShellOptions {
+    /// Exit the shell as soon as a job fails.
+    pub errexit: bool,
+    /// Redirecting output with `>` does not overwrite existing files.
+    pub noclobber: bool,
+    /// Words are not expanded as glob patterns.
+    pub noglob: bool,
+    /// Expanding a variable that is not set is an error.
+    pub nounset: bool,
+    /// A pipeline fails if any of its commands fails, not just the last.
+    pub pipefail: bool,
+    /// Print each job to stderr before running it.
+    pub xtrace: bool,
+}
+
+impl ShellOptions {
+    /// The name of every option.
+    pub const NAMES: &'static [&'static str] = &[
+        "errexit", "noclobber", "noglob", "nounset", "pipefail", "xtrace",
+    ];
+
+    /// Returns the value of the option called `name`.
+    pub fn get(&self, name: &str) -> Option<bool> {
+        match name {
+            "errexit" => Some(self.errexit),
+            "noclobber" => Some(self.noclobber),
+            "noglob" => Some(self.noglob),
+            "nounset" => Some(self.nounset),
+            "pipefail" => Some(self.pipefail),
+            "xtrace" => Some(self.xtrace),
+            _ => None,
+        }
+    }
+
+    /// Sets the option called `name`.
+    pub fn set(&mut self, name: &str, value: bool) -> Result<(), ShellError> {
+        match name {
+            "errexit" => self.errexit = value,
+            "noclobber" => self.noclobber = value,
+            "noglob" => self.noglob = value,
+            "nounset" => self.nounset = value,
+            "pipefail" => self.pipefail = value,
+            "xtrace" => self.xtrace = value,
+            _ => return Err(ShellError::from(format!("no such option: {}", name))),
+        }
+        Ok(())
+    }
+
+    /// Returns the name of the option set by a single letter flag,
+    /// such as `e` in `set -e`.
+    pub fn flag_name(flag: char) -> Option<&'static str> {
+        match flag {
+            'e' => Some("errexit"),
+            'C' => Some("noclobber"),
+            'f' => Some("noglob"),
+            'u' => Some("nounset"),
+            'x' => Some("xtrace"),
+            _ => None,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq)]